@@ -5,6 +5,7 @@ extern crate test;
 use self::test::Bencher;
 use ndarray::Array2;
 use slime_seed_finder::biome_layers::*;
+use slime_seed_finder::slime::*;
 
 // DIM: Base map size for the benchmarks.
 // To ensure a useful comparison between layers, the layers which increase
@@ -208,3 +209,31 @@ fn map_add_island_xhz(b: &mut Bencher) {
     let gen = MapAddIsland::new(base_seed, world_seed);
     b.iter(|| gen.get_map_from_pmap(&m));
 }
+
+// Slime chunk bruteforce: the same 2^16 seeds checked one at a time and LANES at a time.
+// Compare slime_try_seed_skip_18 with slime_try_seeds_skip_18 to see the speedup.
+const SLIME_SEEDS: u64 = 1 << 16;
+
+fn slime_chunks_bench() -> SlimeChunks {
+    let (c, nc) = generate_slime_chunks_and_not(1234, 40, 40);
+    SlimeChunks::new(&c, 3, &nc, 3)
+}
+
+#[bench]
+fn slime_try_seed_skip_18(b: &mut Bencher) {
+    let sc = slime_chunks_bench();
+    b.iter(|| (0..SLIME_SEEDS).filter(|&s| sc.try_seed_skip_18(s)).count());
+}
+
+#[bench]
+fn slime_try_seeds_skip_18(b: &mut Bencher) {
+    let sc = slime_chunks_bench();
+    b.iter(|| {
+        let mut found = 0;
+        for k in 0..SLIME_SEEDS / LANES as u64 {
+            let seeds: [u64; LANES] = std::array::from_fn(|i| k * LANES as u64 + i as u64);
+            found += sc.try_seeds_skip_18(&seeds).count_ones();
+        }
+        found
+    });
+}
@@ -191,7 +191,11 @@ pub unsafe extern "C" fn read_biome_map_from_mc_world(
                 biomes_arr[idx] = biome_id.0;
             }
         }
-        MinecraftVersion::Java1_18 => {
+        MinecraftVersion::Java1_18
+        | MinecraftVersion::Java1_19
+        | MinecraftVersion::Java1_20
+        | MinecraftVersion::Java1_21_3
+        | MinecraftVersion::Java1_21 => {
             let points = anvil::get_all_biomes_1_18(&mut chunk_provider);
             area = biome_layers::Area3D::from_coords4(
                 points.iter().map(|(_biome_id, point3d)| *point3d),
@@ -22,6 +22,7 @@ use slime_seed_finder::seed_info::BiomeId;
 use slime_seed_finder::seed_info::MinecraftVersion;
 use slime_seed_finder::seed_info::SeedInfo;
 use slime_seed_finder::slime::SlimeChunks;
+use slime_seed_finder::tile_cache::TileCache;
 use slime_seed_finder::*;
 
 use std::collections::HashMap;
@@ -288,6 +289,16 @@ pub fn find_seed_rivers(o: Options) -> Vec<i64> {
     }
 }
 
+/// Maximum size of the compressed biome tiles kept in memory by
+/// `generate_fragment_up_to_layer`
+const FRAGMENT_TILE_CACHE_SIZE: u64 = 64 << 20;
+
+thread_local! {
+    // Keep the generated tiles in memory, so going back to an area that was
+    // already visited does not need to generate it again
+    static FRAGMENT_TILE_CACHE: TileCache = TileCache::in_memory(FRAGMENT_TILE_CACHE_SIZE);
+}
+
 #[wasm_bindgen]
 pub fn generate_fragment(
     version: String,
@@ -415,7 +426,9 @@ pub fn generate_fragment_up_to_layer(
     };
     //let last_layer = 43;
     //let map = cubiomes_test::call_layer(last_layer, seed, area);
-    let v = biome_layers::generate_image_up_to_layer(version, area, seed, layer, y_offset);
+    let v = FRAGMENT_TILE_CACHE.with(|cache| {
+        cache.generate_image_up_to_layer(version, area, seed, layer, y_offset)
+    });
 
     v
 }
@@ -1154,7 +1167,11 @@ pub fn read_fragment_biome_map(
         | MinecraftVersion::Java1_17 => {
             biomes = anvil::get_biomes_from_area_1_15(&mut zip_chunk_provider, area, y_offset);
         }
-        MinecraftVersion::Java1_18 => {
+        MinecraftVersion::Java1_18
+        | MinecraftVersion::Java1_19
+        | MinecraftVersion::Java1_20
+        | MinecraftVersion::Java1_21_3
+        | MinecraftVersion::Java1_21 => {
             // Convert offset into level: offset goes from [0, 95], level goes from [-64, 319]
             let y_level: i64 = -64 + y_offset as i64 * 4;
             biomes = anvil::get_biomes_from_area_1_18(&mut zip_chunk_provider, area, y_level);
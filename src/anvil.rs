@@ -22,6 +22,7 @@ use crate::chunk::Point4;
 use crate::chunk::Point3D4;
 use crate::patterns::CompiledBlockPattern;
 use crate::seed_info::BiomeId;
+use crate::seed_info::ItemMap;
 use crate::seed_info::MinecraftVersion;
 use crate::fastanvil_ext::Dimension;
 use crate::fastanvil_ext::region_for_each_chunk;
@@ -325,6 +326,112 @@ pub fn get_rivers_and_some_extra_biomes_1_15<A: AnvilChunkProvider>(chunk_provid
     (vec![], vec![])
 }
 
+pub fn get_some_biomes_zip_1_18(input_zip: &Path, center_block_arg: Point) -> Vec<(BiomeId, Point3D4)> {
+    let mut chunk_provider = ZipChunkProvider::file(input_zip).unwrap();
+
+    get_some_biomes_1_18(&mut chunk_provider, center_block_arg)
+}
+
+/// Read the chunks around the center block and return a few biomes that can be used to filter
+/// seed candidates. For version >= 1.18
+///
+/// Since 1.18 there are no rivers that can be used to find the lower bits of the seed, so the
+/// biomes are only useful to filter candidates. To make the filter reliable, only the points whose
+/// 6 neighbors have the same biome are used, because points near a biome border can change with
+/// small floating point errors. And to make the filter effective, each chunk adds at most one
+/// point per biome, and each biome is limited to a few points.
+pub fn get_some_biomes_1_18<A: AnvilChunkProvider>(chunk_provider: &mut A, center_block_arg: Point) -> Vec<(BiomeId, Point3D4)> {
+    let max_points: usize = 128;
+    let max_points_per_biome: usize = 16;
+    let max_chunks: usize = 4096;
+
+    let center_chunk = (center_block_arg.x >> 4, center_block_arg.z >> 4);
+    let mut all_chunks = chunk_provider.list_chunks().expect("Error listing chunks");
+    // Read the chunks closest to the center first
+    all_chunks.sort_by_key(|(chunk_x, chunk_z)| {
+        std::cmp::max((i64::from(*chunk_x) - center_chunk.0).abs(), (i64::from(*chunk_z) - center_chunk.1).abs())
+    });
+
+    let mut biomes = vec![];
+    let mut points_per_biome: HashMap<BiomeId, usize> = HashMap::new();
+    for (chunk_x, chunk_z) in all_chunks.into_iter().take(max_chunks) {
+        if biomes.len() >= max_points {
+            break;
+        }
+        let c = chunk_provider.load_chunk(chunk_x, chunk_z).expect("Error loading chunk");
+        let chunk = match fastanvil::JavaChunk::from_bytes(c.as_slice()) {
+            Ok(x) => x,
+            Err(e) => {
+                debug!("Failed to read chunk {:?}: {:?}", (chunk_x, chunk_z), e);
+                continue;
+            }
+        };
+        // Biomes are stored in 1:4 scale, read one biome per 4x4x4 cell
+        let y_range = chunk.y_range();
+        let ys: Vec<isize> = y_range.step_by(4).collect();
+        let mut cells = vec![];
+        for &y in &ys {
+            for iz in 0..4 {
+                for ix in 0..4 {
+                    let b = match chunk.biome(ix * 4, y, iz * 4) {
+                        Some(fastanvil::biome::Biome::Unknown) | None => UNKNOWN_BIOME_ID,
+                        Some(b) => i32::from(b),
+                    };
+                    cells.push(b);
+                }
+            }
+        }
+        // Chunks that are not fully generated have all the biomes set to plains
+        if cells.iter().all(|b| *b == biome_id::plains) {
+            continue;
+        }
+        let get = |ix: usize, iy: usize, iz: usize| cells[iy * 16 + iz * 4 + ix];
+
+        let mut biomes_in_chunk = vec![];
+        // Only the cells that are not at the border of the chunk have 6 neighbors
+        for (iy, &y) in ys.iter().enumerate().take(ys.len().saturating_sub(1)).skip(1) {
+            for iz in 1..3 {
+                for ix in 1..3 {
+                    let b = get(ix, iy, iz);
+                    if b == UNKNOWN_BIOME_ID || biomes_in_chunk.contains(&b) {
+                        continue;
+                    }
+                    let neighbors = [
+                        get(ix - 1, iy, iz),
+                        get(ix + 1, iy, iz),
+                        get(ix, iy - 1, iz),
+                        get(ix, iy + 1, iz),
+                        get(ix, iy, iz - 1),
+                        get(ix, iy, iz + 1),
+                    ];
+                    if neighbors.iter().any(|n| *n != b) {
+                        continue;
+                    }
+                    biomes_in_chunk.push(b);
+                    let count = points_per_biome.entry(BiomeId(b)).or_default();
+                    if *count >= max_points_per_biome {
+                        continue;
+                    }
+                    *count += 1;
+                    let p = Point3D4 {
+                        x: i64::from(chunk_x) * 4 + ix as i64,
+                        y: (y >> 2) as i64,
+                        z: i64::from(chunk_z) * 4 + iz as i64,
+                    };
+                    biomes.push((BiomeId(b), p));
+                }
+            }
+        }
+    }
+
+    debug!("biomes.len(): {}", biomes.len());
+    if biomes.is_empty() {
+        error!("Found zero valid chunks. Is this even a minecraft save?");
+    }
+
+    biomes
+}
+
 /// Get all the biomes present in the chunk. For version >= 1.15 but < 1.18
 pub fn get_biomes_from_chunk_1_15(chunk: &CompoundTag) -> Result<Vec<i32>, String> {
     let level_compound_tag = chunk.get_compound_tag("Level").unwrap();
@@ -723,6 +830,113 @@ fn find_level_dat<R: Read + Seek>(zip_archive: &mut ZipArchive<R>) -> Result<Str
         .map_err(|e| format!("Failed to find level.dat in zip archive: {}", e))
 }
 
+/// Read all the map items from the `data/map_<id>.dat` files of a zipped world. Maps from the
+/// nether and the End are skipped, and so are the files that cannot be read.
+pub fn read_item_maps_zip(input_zip: &Path) -> Result<Vec<ItemMap>, String> {
+    let reader = OpenOptions::new()
+        .write(false)
+        .read(true)
+        .create(false)
+        .open(input_zip)
+        .map_err(|e| format!("Failed to open file: {:?}", e))?;
+
+    let mut zip_archive = ZipArchive::new(reader).map_err(|e| format!("Failed to read zip: {:?}", e))?;
+    let map_paths: Vec<String> = zip_archive.file_names().filter(|name| is_item_map_path(Path::new(name))).map(|name| name.to_string()).collect();
+    let mut maps = vec![];
+    for path in map_paths {
+        let mut file = zip_archive.by_name(&path).map_err(|e| format!("Failed to read {}: {:?}", path, e))?;
+        match read_item_map(&mut file) {
+            Ok(Some(item_map)) => maps.push(item_map),
+            Ok(None) => debug!("Skipping {}: not an overworld map", path),
+            Err(e) => warn!("Failed to read {}: {}", path, e),
+        }
+    }
+
+    Ok(maps)
+}
+
+/// Read all the map items from the `map_<id>.dat` files of this folder, usually the `data` folder
+/// of a world. Maps from the nether and the End are skipped, and so are the files that cannot be
+/// read.
+pub fn read_item_maps_folder(input_dir: &Path) -> Result<Vec<ItemMap>, String> {
+    let mut maps = vec![];
+    let entries = std::fs::read_dir(input_dir).map_err(|e| format!("Failed to read folder {:?}: {:?}", input_dir, e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| format!("Failed to read folder {:?}: {:?}", input_dir, e))?.path();
+        if !is_item_map_path(&path) {
+            continue;
+        }
+        let mut file = OpenOptions::new().read(true).open(&path).map_err(|e| format!("Failed to open file {:?}: {:?}", path, e))?;
+        match read_item_map(&mut file) {
+            Ok(Some(item_map)) => maps.push(item_map),
+            Ok(None) => debug!("Skipping {:?}: not an overworld map", path),
+            Err(e) => warn!("Failed to read {:?}: {}", path, e),
+        }
+    }
+
+    Ok(maps)
+}
+
+/// Read all the map items of a world: `input` can be a zipped world, or a folder with the
+/// `map_<id>.dat` files.
+pub fn read_item_maps(input: &Path) -> Result<Vec<ItemMap>, String> {
+    if input.is_dir() {
+        read_item_maps_folder(input)
+    } else {
+        read_item_maps_zip(input)
+    }
+}
+
+/// Returns true for paths like "world/data/map_12.dat"
+fn is_item_map_path(path: &Path) -> bool {
+    let file_name = match path.file_name().and_then(|x| x.to_str()) {
+        Some(x) => x,
+        None => return false,
+    };
+
+    match file_name.strip_prefix("map_").and_then(|x| x.strip_suffix(".dat")) {
+        Some(id) => !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// Read one map item from a `map_<id>.dat` file. Returns `None` if the map is not from the
+/// overworld.
+///
+/// root["data"]["xCenter"], root["data"]["zCenter"], root["data"]["scale"],
+/// root["data"]["dimension"], root["data"]["colors"]
+pub fn read_item_map<R: Read>(r: &mut R) -> Result<Option<ItemMap>, String> {
+    let root_tag = read_gzip_compound_tag(r).map_err(|e| format!("Failed to read gzip compount tag: {:?}", e))?;
+    let data_tag = root_tag.get_compound_tag("data").map_err(|e| format!("Failed to read {:?} tag: {:?}", "data", e))?;
+    // The dimension is a string since 1.16, an int from 1.13 to 1.15, and a byte before 1.13
+    let overworld = match (data_tag.get_str("dimension"), data_tag.get_i32("dimension"), data_tag.get_i8("dimension")) {
+        (Ok(s), _, _) => s == "minecraft:overworld",
+        (_, Ok(x), _) => x == 0,
+        (_, _, Ok(x)) => x == 0,
+        (Err(e), _, _) => return Err(format!("Failed to read {:?} tag: {:?}", "dimension", e)),
+    };
+    if !overworld {
+        return Ok(None);
+    }
+    let x_center = data_tag.get_i32("xCenter").map_err(|e| format!("Failed to read {:?} tag: {:?}", "xCenter", e))?;
+    let z_center = data_tag.get_i32("zCenter").map_err(|e| format!("Failed to read {:?} tag: {:?}", "zCenter", e))?;
+    let scale = data_tag.get_i8("scale").map_err(|e| format!("Failed to read {:?} tag: {:?}", "scale", e))?;
+    let colors = data_tag.get_i8_vec("colors").map_err(|e| format!("Failed to read {:?} tag: {:?}", "colors", e))?;
+    if !(0..=4).contains(&scale) {
+        return Err(format!("Invalid scale: {}", scale));
+    }
+    if colors.len() != 128 * 128 {
+        return Err(format!("Invalid number of pixels: {}", colors.len()));
+    }
+
+    Ok(Some(ItemMap {
+        x_center: i64::from(x_center),
+        z_center: i64::from(z_center),
+        scale: scale as u8,
+        colors: colors.iter().map(|&c| c as u8).collect(),
+    }))
+}
+
 pub fn find_dungeons<A: AnvilChunkProvider>(chunk_provider: &mut A) -> Result<Vec<((i64, i64, i64), SpawnerKind, Vec<String>)>, String> {
     let all_chunks = chunk_provider.list_chunks().expect("Error listing chunks");
     let mut dungeons = vec![];
@@ -1419,3 +1633,97 @@ pub fn iterate_find_block_pattern_callback<A: AnvilChunkProvider, R, F: FnOnce(D
 
     cb(overworld, all_chunks)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    // map_0.dat uses the format from before 1.13, map_1.dat is an overworld map from 1.16, and
+    // map_2.dat is a nether map from 1.16. Both overworld maps have the same pixels: grass with a
+    // river in the middle, and the bottom 8 rows unexplored.
+    const MAP_0: &[u8] = include_bytes!("../test_data/maps/map_0.dat");
+    const MAP_1: &[u8] = include_bytes!("../test_data/maps/map_1.dat");
+    const MAP_2: &[u8] = include_bytes!("../test_data/maps/map_2.dat");
+    const IDCOUNTS: &[u8] = include_bytes!("../test_data/maps/idcounts.dat");
+
+    fn check_colors(item_map: &ItemMap) {
+        assert_eq!(item_map.colors.len(), 128 * 128);
+        let color = |x: usize, z: usize| item_map.colors[z * 128 + x];
+        // Grass
+        assert_eq!(color(0, 1), 5);
+        // Water
+        assert_eq!(color(64, 1), 50);
+        // Sand
+        assert_eq!(color(59, 1), 9);
+        // Unexplored
+        assert_eq!(color(64, 127), 0);
+    }
+
+    #[test]
+    fn item_map_paths() {
+        assert!(is_item_map_path(Path::new("map_0.dat")));
+        assert!(is_item_map_path(Path::new("world/data/map_123.dat")));
+        assert!(!is_item_map_path(Path::new("world/data/idcounts.dat")));
+        assert!(!is_item_map_path(Path::new("world/data/map_.dat")));
+        assert!(!is_item_map_path(Path::new("world/data/map_1a.dat")));
+        assert!(!is_item_map_path(Path::new("world/data/map_1.dat_old")));
+    }
+
+    #[test]
+    fn read_item_map_before_1_13() {
+        // The dimension is a byte
+        let item_map = read_item_map(&mut Cursor::new(MAP_0)).unwrap().unwrap();
+        assert_eq!((item_map.x_center, item_map.z_center, item_map.scale), (64, 64, 1));
+        check_colors(&item_map);
+    }
+
+    #[test]
+    fn read_item_map_1_16() {
+        // The dimension is a string
+        let item_map = read_item_map(&mut Cursor::new(MAP_1)).unwrap().unwrap();
+        assert_eq!((item_map.x_center, item_map.z_center, item_map.scale), (-64, 192, 0));
+        check_colors(&item_map);
+    }
+
+    #[test]
+    fn skip_nether_item_map() {
+        assert_eq!(read_item_map(&mut Cursor::new(MAP_2)).unwrap(), None);
+    }
+
+    #[test]
+    fn read_item_map_errors() {
+        assert!(read_item_map(&mut Cursor::new(b"not a map")).is_err());
+        // Valid nbt, but not a map
+        assert!(read_item_map(&mut Cursor::new(IDCOUNTS)).is_err());
+    }
+
+    #[test]
+    fn read_item_maps_from_folder() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_data/maps");
+        let mut maps = read_item_maps(&dir).unwrap();
+        maps.sort_by_key(|m| m.x_center);
+        assert_eq!(maps.len(), 2);
+        assert_eq!((maps[0].x_center, maps[1].x_center), (-64, 64));
+    }
+
+    #[test]
+    fn read_item_maps_from_zip() {
+        let path = std::env::temp_dir().join("slime_seed_finder_item_maps_test.zip");
+        {
+            let mut w = ZipWriter::new(std::fs::File::create(&path).unwrap());
+            for (name, data) in [("world/data/map_1.dat", MAP_1), ("world/data/map_2.dat", MAP_2), ("world/data/idcounts.dat", IDCOUNTS), ("world/level.dat", IDCOUNTS)] {
+                w.start_file(name, FileOptions::default()).unwrap();
+                w.write_all(data).unwrap();
+            }
+            w.finish().unwrap();
+        }
+        let maps = read_item_maps(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(maps.len(), 1);
+        assert_eq!((maps[0].x_center, maps[0].z_center, maps[0].scale), (-64, 192, 0));
+        check_colors(&maps[0]);
+    }
+}
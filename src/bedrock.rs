@@ -0,0 +1,172 @@
+//! Bedrock Edition biome generation.
+//!
+//! Starting from 1.18, Bedrock Edition uses the same biome noise as Java
+//! Edition, so the 1:4 biome map is the same. But Bedrock does not use the
+//! voronoi zoom, so each 1:4 cell becomes a 4x4 square of blocks.
+use crate::biome_layers::draw_map_image;
+use crate::biome_layers::draw_map_image_noise;
+use crate::biome_layers::generate_up_to_layer_1_18;
+use crate::biome_layers::Area;
+use crate::biome_layers::Area3D;
+use crate::biome_layers::GetMap3D;
+use crate::biome_layers::Map;
+use crate::biome_layers::MapGenBiomeNoise3D118;
+use crate::biome_layers::MapQuarterToBlock;
+use crate::chunk::Point;
+use crate::heightmap::OffsetHeightmap118;
+use crate::seed_info::BiomeId;
+use crate::seed_info::MinecraftVersion;
+use log::debug;
+use std::rc::Rc;
+
+/// Bedrock biome generator up to `layer`. Layers [0, 8] are the same as in
+/// `biome_layers::generator_up_to_layer_1_18`, and layer 9 is the full
+/// resolution biome map.
+pub fn generator_up_to_layer(
+    world_seed: i64,
+    layer: u32,
+    version: MinecraftVersion,
+) -> Box<dyn GetMap3D> {
+    assert!(
+        version >= MinecraftVersion::Java1_18,
+        "Bedrock biome generation is only implemented starting from 1.18"
+    );
+    let g0 = MapGenBiomeNoise3D118::new(world_seed, version);
+    if layer <= 8 {
+        return Box::new(g0);
+    }
+
+    Box::new(MapQuarterToBlock {
+        parent: Rc::new(g0),
+    })
+}
+
+/// Bedrock version of `biome_layers::generate_up_to_layer`. `version` must be
+/// the Java version with the same biome generation, as returned by
+/// `MinecraftVersion::parse_edition`.
+pub fn generate_up_to_layer(
+    version: MinecraftVersion,
+    area: Area,
+    seed: i64,
+    num_layers: u32,
+    y_offset: u32,
+) -> Map {
+    let y_level = y_offset as i64 - 16;
+    let area = Area3D::from_area2d_and_y_level(area, y_level);
+    let map3d = match num_layers {
+        // The noise layers are the same as in Java
+        0..=8 | 50 | 51 => generate_up_to_layer_1_18(area, seed, num_layers, version),
+        _ => generator_up_to_layer(seed, num_layers, version).get_map_3d(area),
+    };
+
+    map3d.into_map2d()
+}
+
+pub fn generate_image_up_to_layer(
+    version: MinecraftVersion,
+    area: Area,
+    seed: i64,
+    layer: u32,
+    y_offset: u32,
+) -> Vec<u8> {
+    let map = generate_up_to_layer(version, area, seed, layer, y_offset);
+
+    match layer {
+        0..=7 | 50 | 51 => draw_map_image_noise(&map),
+        _ => draw_map_image(&map),
+    }
+}
+
+/// Bedrock version of `biome_layers::filter_seeds_using_biomes`
+pub fn filter_seeds_using_biomes(
+    candidates: &[i64],
+    extra_biomes: &[(BiomeId, Point)],
+    version: MinecraftVersion,
+) -> Vec<i64> {
+    let mut valid_seeds = vec![];
+
+    for world_seed in candidates {
+        let world_seed = *world_seed;
+        // The climate noises are the same as in Java. They are initialized
+        // once per seed, and shared by the heightmap and the biome generator
+        let climate = Rc::new(MapGenBiomeNoise3D118::new(world_seed, version));
+        let heightmap = OffsetHeightmap118::from_biome_generator(Rc::clone(&climate));
+        let g = MapQuarterToBlock { parent: climate };
+        let mut hits = 0;
+        let mut misses = 0;
+        let target = extra_biomes.len() * 90 / 100;
+        let max_misses = extra_biomes.len() - target;
+        for (biome, Point { x, z }) in extra_biomes.iter().cloned() {
+            let y_level = heightmap.surface_y_offset(x, z) as i64 - 16;
+            let area = Area3D::from_area2d_and_y_level(Area { x, z, w: 1, h: 1 }, y_level);
+            if g.get_map_3d(area).a[(0, 0, 0)] == biome.0 {
+                hits += 1;
+            } else {
+                misses += 1;
+                if misses > max_misses {
+                    break;
+                }
+            }
+        }
+
+        if hits >= target {
+            debug!("{:016X}: {}/{}", world_seed, hits, extra_biomes.len());
+            valid_seeds.push(world_seed);
+        }
+    }
+
+    valid_seeds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_as_java_at_quarter_scale() {
+        let version = MinecraftVersion::Java1_18;
+        let seed = 1234;
+        let area = Area {
+            x: 0,
+            z: 0,
+            w: 16,
+            h: 16,
+        };
+        let quarter = generate_up_to_layer(version, area, seed, 8, 32);
+        let java = crate::biome_layers::generate_up_to_layer(version, area, seed, 8, 32);
+        assert_eq!(quarter, java);
+
+        // Without voronoi, every block is equal to its 1:4 cell
+        let full_area = Area {
+            x: 0,
+            z: 0,
+            w: 64,
+            h: 64,
+        };
+        let full = generate_up_to_layer(version, full_area, seed, version.num_layers(), 32);
+        for x in 0..64 {
+            for z in 0..64 {
+                assert_eq!(full.a[(x, z)], quarter.a[(x / 4, z / 4)]);
+            }
+        }
+    }
+
+    #[test]
+    fn filter_biomes() {
+        let version = MinecraftVersion::Java1_18;
+        let seed = 1234;
+        let heightmap = OffsetHeightmap118::new(seed, version);
+        let extra_biomes: Vec<_> = (0..20)
+            .map(|i| {
+                let (x, z) = (i * 200, -i * 150);
+                let area = Area { x, z, w: 1, h: 1 };
+                let y_offset = heightmap.surface_y_offset(x, z);
+                let m = generate_up_to_layer(version, area, seed, version.num_layers(), y_offset);
+                (BiomeId(m.a[(0, 0)]), Point { x, z })
+            })
+            .collect();
+        let candidates = [seed, seed + 1, seed + 2];
+        let valid = filter_seeds_using_biomes(&candidates, &extra_biomes, version);
+        assert_eq!(valid, vec![seed]);
+    }
+}
@@ -0,0 +1,294 @@
+//! Compare the biomes of a saved world with the biomes generated by `biome_layers`.
+//!
+//! This is used to validate changes to the biome generators against reference worlds: instead of
+//! stopping at the first mismatch, all the points are compared and the result can be rendered as
+//! an image that highlights the wrong pixels.
+
+use crate::anvil;
+use crate::biome_info::biome_name;
+use crate::biome_info::UNKNOWN_BIOME_ID;
+use crate::biome_layers;
+use crate::biome_layers::Area;
+use crate::biome_layers::Area3D;
+use crate::biome_layers::Map;
+use crate::chunk::Point;
+use crate::chunk::Point3D;
+use crate::fastanvil_ext::AnvilChunkProvider;
+use crate::seed_info::BiomeId;
+use crate::seed_info::MinecraftVersion;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Color of the mismatched pixels in `BiomeDiff::draw_overlay`
+const MISMATCH_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+/// Result of comparing the biomes of a saved world with the generated biomes.
+///
+/// All the coordinates use the resolution of the biomes stored in the world: 1:1 until 1.14, and
+/// 1:4 since 1.15. The y coordinate is always 0 before 1.18.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BiomeDiff {
+    /// Biomes read from the saved world. Since 1.18, each pixel is the highest known biome of its
+    /// column.
+    pub expected: Map,
+    /// Number of compared points
+    pub total: usize,
+    /// Mismatched points, grouped by (expected biome, generated biome)
+    pub mismatches: BTreeMap<(i32, i32), Vec<Point3D>>,
+    /// Since 1.18: number of mismatches where the distance to the second closest biome is less
+    /// than 1. These can be caused by small floating point errors, so they are less important
+    /// than the rest.
+    pub near_border: usize,
+}
+
+impl BiomeDiff {
+    fn new(expected: Map) -> Self {
+        Self {
+            expected,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, p: Point3D, expected: i32, generated: i32) {
+        self.total += 1;
+        if expected != generated {
+            self.mismatches
+                .entry((expected, generated))
+                .or_default()
+                .push(p);
+        }
+    }
+
+    pub fn num_mismatches(&self) -> usize {
+        self.mismatches.values().map(|v| v.len()).sum()
+    }
+
+    /// Percentage of compared points that do not match, from 0.0 to 100.0
+    pub fn mismatch_percentage(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        self.num_mismatches() as f64 * 100.0 / self.total as f64
+    }
+
+    /// Draw the expected biomes with the mismatched pixels highlighted. The correct pixels are
+    /// drawn darker, so the mismatches are easy to see. Returns an RGBA image with the same size
+    /// as `self.expected`.
+    pub fn draw_overlay(&self) -> Vec<u8> {
+        let mut v = biome_layers::draw_map_image(&self.expected);
+        for px in v.chunks_exact_mut(4) {
+            for c in &mut px[0..3] {
+                *c /= 2;
+            }
+        }
+        let area = self.expected.area();
+        for &Point3D { x, y: _, z } in self.mismatches.values().flatten() {
+            let i = (z - area.z) as usize * area.w as usize + (x - area.x) as usize;
+            v[i * 4..i * 4 + 4].copy_from_slice(&MISMATCH_COLOR);
+        }
+
+        v
+    }
+}
+
+impl fmt::Display for BiomeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = |id: i32| biome_name(id).unwrap_or("Unknown");
+        writeln!(
+            f,
+            "{} mismatches out of {} biomes ({:.2}%)",
+            self.num_mismatches(),
+            self.total,
+            self.mismatch_percentage()
+        )?;
+        if self.near_border > 0 {
+            writeln!(
+                f,
+                "{} mismatches are near the border of the second biome",
+                self.near_border
+            )?;
+        }
+        for ((expected, generated), points) in &self.mismatches {
+            writeln!(
+                f,
+                "expected {} ({}) generated {} ({}): {} mismatches, first at {:?}",
+                name(*expected),
+                expected,
+                name(*generated),
+                generated,
+                points.len(),
+                points[0]
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Read all the biomes from a saved world and compare them with the biomes generated using the
+/// world seed.
+pub fn compare_world_biomes<A: AnvilChunkProvider>(
+    chunk_provider: &mut A,
+    version: MinecraftVersion,
+    world_seed: i64,
+) -> Result<BiomeDiff, String> {
+    match version {
+        MinecraftVersion::Java1_3
+        | MinecraftVersion::Java1_7
+        | MinecraftVersion::Java1_9
+        | MinecraftVersion::Java1_11
+        | MinecraftVersion::Java1_13
+        | MinecraftVersion::Java1_14 => {
+            let biomes = anvil::get_all_biomes_1_14(chunk_provider);
+            let area = Area::from_coords(biomes.iter().map(|(_, p)| *p));
+            // Generate area with 1:1 resolution
+            let y_offset = 0;
+            let map = biome_layers::generate(version, area, world_seed, y_offset);
+
+            Ok(diff_2d(&biomes, &map))
+        }
+        MinecraftVersion::Java1_15
+        | MinecraftVersion::Java1_16_1
+        | MinecraftVersion::Java1_16
+        | MinecraftVersion::Java1_17 => {
+            let biomes: Vec<_> = anvil::get_all_biomes_1_15(chunk_provider)
+                .into_iter()
+                .map(|(id, p)| (id, Point { x: p.x, z: p.z }))
+                .collect();
+            let area = Area::from_coords(biomes.iter().map(|(_, p)| *p));
+            // Generate area with 1:4 resolution
+            let map = biome_layers::generate_up_to_layer_1_15(
+                area,
+                world_seed,
+                version.num_layers() - 1,
+                version,
+            );
+
+            Ok(diff_2d(&biomes, &map))
+        }
+        MinecraftVersion::Java1_18
+        | MinecraftVersion::Java1_19
+        | MinecraftVersion::Java1_20
+        | MinecraftVersion::Java1_21_3
+        | MinecraftVersion::Java1_21 => {
+            let mut biomes = anvil::get_all_biomes_1_18(chunk_provider);
+            // Sort by y so the highest biome of each column is drawn last
+            biomes.sort_by_key(|(_, p)| p.y);
+            let area = Area::from_coords(biomes.iter().map(|(_, p)| Point { x: p.x, z: p.z }));
+            let mut expected = Map::from_area_fn(area, |(_, _)| UNKNOWN_BIOME_ID);
+            for (id, p) in &biomes {
+                expected.set(p.x, p.z, id.0);
+            }
+            let mut diff = BiomeDiff::new(expected);
+
+            // Generate area with 1:4 resolution
+            let layer = version.num_layers() - 1;
+            let distance_to_second_biome_layer = 50;
+            let generator = biome_layers::MapGenBiomeNoise3D118::new(world_seed, version);
+            for (expected_biome_id, p) in biomes {
+                if expected_biome_id.0 == 1 {
+                    // Bug: chunks that are not fully generated have biome id set to plains
+                    continue;
+                }
+                let a3 = Area3D {
+                    x: p.x,
+                    y: p.y,
+                    z: p.z,
+                    sx: 1,
+                    sy: 1,
+                    sz: 1,
+                };
+                let b = generator.partial_get_map_3d(a3, layer).a[(0, 0, 0)];
+                if b != expected_biome_id.0 {
+                    let distance_to_second_biome = generator
+                        .partial_get_map_3d(a3, distance_to_second_biome_layer)
+                        .a[(0, 0, 0)];
+                    if distance_to_second_biome < 1 {
+                        diff.near_border += 1;
+                    }
+                }
+                diff.add(
+                    Point3D {
+                        x: p.x,
+                        y: p.y,
+                        z: p.z,
+                    },
+                    expected_biome_id.0,
+                    b,
+                );
+            }
+
+            Ok(diff)
+        }
+        _ => Err(format!("Version {:?} is not supported", version)),
+    }
+}
+
+/// Compare a list of 2D biomes with the generated map, which must contain all the points.
+fn diff_2d(biomes: &[(BiomeId, Point)], map: &Map) -> BiomeDiff {
+    let mut expected = Map::from_area_fn(map.area(), |(_, _)| UNKNOWN_BIOME_ID);
+    for (id, p) in biomes {
+        expected.set(p.x, p.z, id.0);
+    }
+    let mut diff = BiomeDiff::new(expected);
+    for (id, p) in biomes {
+        diff.add(
+            Point3D {
+                x: p.x,
+                y: 0,
+                z: p.z,
+            },
+            id.0,
+            map.get(p.x, p.z),
+        );
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biome_info::biome_id;
+
+    #[test]
+    fn diff_groups_mismatches_by_biome_pair() {
+        let area = Area {
+            x: -2,
+            z: 3,
+            w: 4,
+            h: 2,
+        };
+        let map = Map::from_area_fn(area, |(_, _)| biome_id::plains);
+        let biomes = vec![
+            (BiomeId(biome_id::plains), Point { x: -2, z: 3 }),
+            (BiomeId(biome_id::desert), Point { x: -1, z: 3 }),
+            (BiomeId(biome_id::desert), Point { x: 1, z: 4 }),
+            (BiomeId(biome_id::ocean), Point { x: 0, z: 4 }),
+        ];
+        let diff = diff_2d(&biomes, &map);
+
+        assert_eq!(diff.total, 4);
+        assert_eq!(diff.num_mismatches(), 3);
+        assert_eq!(diff.mismatch_percentage(), 75.0);
+        assert_eq!(
+            diff.mismatches[&(biome_id::desert, biome_id::plains)],
+            vec![Point3D { x: -1, y: 0, z: 3 }, Point3D { x: 1, y: 0, z: 4 }]
+        );
+        assert_eq!(
+            diff.mismatches[&(biome_id::ocean, biome_id::plains)],
+            vec![Point3D { x: 0, y: 0, z: 4 }]
+        );
+        // Points not present in the world are unknown
+        assert_eq!(diff.expected.get(-2, 4), UNKNOWN_BIOME_ID);
+
+        let image = diff.draw_overlay();
+        assert_eq!(image.len(), 4 * 2 * 4);
+        let pixel = |x: usize, z: usize| &image[(z * 4 + x) * 4..(z * 4 + x) * 4 + 4];
+        assert_eq!(pixel(1, 0), MISMATCH_COLOR);
+        assert_eq!(pixel(2, 1), MISMATCH_COLOR);
+        assert_eq!(pixel(3, 1), MISMATCH_COLOR);
+        assert_ne!(pixel(0, 0), MISMATCH_COLOR);
+    }
+}
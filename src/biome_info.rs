@@ -78,6 +78,10 @@ pub fn biome_name(biome_id: i32) -> Option<&'static str> {
         modifiedBadlandsPlateau => "Modified Badlands Plateau",
         bambooJungle => "Bamboo Jungle",
         bambooJungleHills => "Bamboo Jungle Hills",
+        soulSandValley => "Soul Sand Valley",
+        crimsonForest => "Crimson Forest",
+        warpedForest => "Warped Forest",
+        basaltDeltas => "Basalt Deltas",
         174 => "DripstoneCaves",
         175 => "FrozenPeaks",
         176 => "Grove",
@@ -98,6 +102,10 @@ pub fn biome_name(biome_id: i32) -> Option<&'static str> {
         191 => "WindsweptHills",
         192 => "WindsweptSavanna",
         193 => "WoodedBadlands",
+        194 => "DeepDark",
+        195 => "MangroveSwamp",
+        196 => "CherryGrove",
+        197 => "PaleGarden",
         _ => return None,
     };
 
@@ -108,6 +116,11 @@ pub fn biome_name(biome_id: i32) -> Option<&'static str> {
 #[allow(non_upper_case_globals)]
 pub mod biome_id {
 pub type BiomeID = i32;
+// 1.16
+pub const basaltDeltas: BiomeID = 173;
+pub const warpedForest: BiomeID = 172;
+pub const crimsonForest: BiomeID = 171;
+pub const soulSandValley: BiomeID = 170;
 pub const bambooJungleHills: BiomeID = 169;
 pub const bambooJungle: BiomeID = 168;
 pub const modifiedBadlandsPlateau: BiomeID = 167;
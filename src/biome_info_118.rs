@@ -2,10 +2,37 @@ use crate::biome_info_118::biome_tree::BiomeTree;
 use crate::climate::Climate;
 use crate::climate::ClimateRange;
 use crate::seed_info::BiomeId;
+use crate::seed_info::MinecraftVersion;
 use lazy_static::lazy_static;
 
 lazy_static! {
-    pub static ref BIOME_LIST: BiomeList = biome_data::init();
+    static ref BIOME_LIST_1_18: BiomeList = biome_data::init(MinecraftVersion::Java1_18);
+    static ref BIOME_LIST_1_19: BiomeList = biome_data::init(MinecraftVersion::Java1_19);
+    static ref BIOME_LIST_1_20: BiomeList = biome_data::init(MinecraftVersion::Java1_20);
+    static ref BIOME_LIST_1_21: BiomeList = biome_data::init(MinecraftVersion::Java1_21);
+}
+
+// Biomes added after 1.18. They are not available in fastanvil::biome::Biome,
+// so continue its numbering.
+pub const DEEP_DARK: BiomeId = BiomeId(194);
+pub const MANGROVE_SWAMP: BiomeId = BiomeId(195);
+pub const CHERRY_GROVE: BiomeId = BiomeId(196);
+pub const PALE_GARDEN: BiomeId = BiomeId(197);
+
+/// The list of overworld biomes used by this version. Panics if the version
+/// is older than 1.18.
+pub fn biome_list(version: MinecraftVersion) -> &'static BiomeList {
+    match version {
+        MinecraftVersion::Java1_18 => &BIOME_LIST_1_18,
+        MinecraftVersion::Java1_19 => &BIOME_LIST_1_19,
+        // 1.21 did not add any new overworld biomes until 1.21.4
+        MinecraftVersion::Java1_20 | MinecraftVersion::Java1_21_3 => &BIOME_LIST_1_20,
+        MinecraftVersion::Java1_21 => &BIOME_LIST_1_21,
+        _ => panic!(
+            "Version {:?} does not use the 1.18 biome generation",
+            version
+        ),
+    }
 }
 
 #[derive(Default)]
@@ -23,6 +50,11 @@ impl BiomeList {
         self.list.push((climate_range, biome_id));
     }
 
+    /// Iterate over the climate ranges of all the biomes, in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = &(ClimateRange, BiomeId)> {
+        self.list.iter()
+    }
+
     /// Find the biome that most closely matches this climate
     pub fn search(&self, climate: &Climate) -> Option<BiomeId> {
         self.search_tree(climate)
@@ -113,7 +145,7 @@ impl BiomeList {
     }
 }
 
-/// Biome data for Minecraft 1.18.
+/// Biome data for Minecraft 1.18 and later.
 /// I wish this was autogenerated.
 mod biome_data {
     use super::*;
@@ -329,6 +361,45 @@ mod biome_data {
             ],
         ];
 
+        // 1.20 added the cherry grove
+        static ref PLATEAU_BIOMES_VARIANT_1_20: [[Option<BiomeId>; 5]; 5] = [
+            [
+                Some(BiomeId(IceSpikes as i32)),
+                None,
+                None,
+                None,
+                None,
+            ],
+            [
+                Some(CHERRY_GROVE),
+                None,
+                Some(BiomeId(Meadow as i32)),
+                Some(BiomeId(Meadow as i32)),
+                Some(BiomeId(OldGrowthPineTaiga as i32)),
+            ],
+            [
+                Some(CHERRY_GROVE),
+                Some(CHERRY_GROVE),
+                Some(BiomeId(Forest as i32)),
+                Some(BiomeId(BirchForest as i32)),
+                None,
+            ],
+            [
+                None,
+                None,
+                None,
+                None,
+                None,
+            ],
+            [
+                Some(BiomeId(ErodedBadlands as i32)),
+                Some(BiomeId(ErodedBadlands as i32)),
+                None,
+                None,
+                None,
+            ],
+        ];
+
         static ref EXTREME_HILLS: [[Option<BiomeId>; 5]; 5] = [
             [
                 Some(BiomeId(WindsweptGravellyHills as i32)),
@@ -380,13 +451,13 @@ mod biome_data {
         FarInland,
     }
 
-    /// Raw biome data for 1.18
-    pub fn init() -> BiomeList {
+    /// Raw biome data for 1.18 and later
+    pub fn init(version: MinecraftVersion) -> BiomeList {
         let mut v = BiomeList::default();
 
         add_off_coast_biomes(&mut v);
-        add_inland_biomes(&mut v);
-        add_underground_biomes(&mut v);
+        add_inland_biomes(&mut v, version);
+        add_underground_biomes(&mut v, version);
 
         v.tree = Some(BiomeTree::new(v.list.clone()));
 
@@ -437,23 +508,23 @@ mod biome_data {
         }
     }
 
-    pub fn add_inland_biomes(v: &mut BiomeList) {
-        add_mid_slice(v, -10000..=-9333);
-        add_high_slice(v, -9333..=-7667);
-        add_peaks(v, -7667..=-5667);
-        add_high_slice(v, -5667..=-4000);
-        add_mid_slice(v, -4000..=-2667);
-        add_low_slice(v, -2667..=-500);
-        add_valleys(v, -500..=500);
-        add_low_slice(v, 500..=2667);
-        add_mid_slice(v, 2667..=4000);
-        add_high_slice(v, 4000..=5667);
-        add_peaks(v, 5667..=7667);
-        add_high_slice(v, 7667..=9333);
-        add_mid_slice(v, 9333..=10000);
+    pub fn add_inland_biomes(v: &mut BiomeList, version: MinecraftVersion) {
+        add_mid_slice(v, -10000..=-9333, version);
+        add_high_slice(v, -9333..=-7667, version);
+        add_peaks(v, -7667..=-5667, version);
+        add_high_slice(v, -5667..=-4000, version);
+        add_mid_slice(v, -4000..=-2667, version);
+        add_low_slice(v, -2667..=-500, version);
+        add_valleys(v, -500..=500, version);
+        add_low_slice(v, 500..=2667, version);
+        add_mid_slice(v, 2667..=4000, version);
+        add_high_slice(v, 4000..=5667, version);
+        add_peaks(v, 5667..=7667, version);
+        add_high_slice(v, 7667..=9333, version);
+        add_mid_slice(v, 9333..=10000, version);
     }
 
-    pub fn add_peaks(v: &mut BiomeList, weirdness: RangeInclusive<i64>) {
+    pub fn add_peaks(v: &mut BiomeList, weirdness: RangeInclusive<i64>, version: MinecraftVersion) {
         for i in 0..TEMPERATURES.len() {
             let temperature = &TEMPERATURES[i];
 
@@ -462,9 +533,13 @@ mod biome_data {
 
                 let b0 = pick_middle_biome(i, j, weirdness.clone());
                 let b1 = pick_middle_biome_or_badlands_if_hot(i, j, weirdness.clone());
-                let b2 =
-                    pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, weirdness.clone());
-                let b3 = pick_plateau_biome(i, j, weirdness.clone());
+                let b2 = pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(
+                    i,
+                    j,
+                    weirdness.clone(),
+                    version,
+                );
+                let b3 = pick_plateau_biome(i, j, weirdness.clone(), version);
                 let b4 = pick_extreme_hills_biome(i, j, weirdness.clone());
                 let b5 = maybe_pick_shattered_biome(i, j, weirdness.clone()).unwrap_or(b4);
                 let b6 = pick_peak_biome(i, j, weirdness.clone());
@@ -610,7 +685,11 @@ mod biome_data {
         }
     }
 
-    pub fn add_high_slice(v: &mut BiomeList, weirdness: RangeInclusive<i64>) {
+    pub fn add_high_slice(
+        v: &mut BiomeList,
+        weirdness: RangeInclusive<i64>,
+        version: MinecraftVersion,
+    ) {
         for i in 0..TEMPERATURES.len() {
             let temperature = &TEMPERATURES[i];
 
@@ -619,12 +698,16 @@ mod biome_data {
 
                 let b0 = pick_middle_biome(i, j, weirdness.clone());
                 let b1 = pick_middle_biome_or_badlands_if_hot(i, j, weirdness.clone());
-                let b2 =
-                    pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, weirdness.clone());
-                let b3 = pick_plateau_biome(i, j, weirdness.clone());
+                let b2 = pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(
+                    i,
+                    j,
+                    weirdness.clone(),
+                    version,
+                );
+                let b3 = pick_plateau_biome(i, j, weirdness.clone(), version);
                 let b4 = pick_extreme_hills_biome(i, j, weirdness.clone());
                 let b5 = maybe_pick_shattered_biome(i, j, weirdness.clone()).unwrap_or(b0);
-                let b6 = pick_slope_biome(i, j, weirdness.clone());
+                let b6 = pick_slope_biome(i, j, weirdness.clone(), version);
                 let b7 = pick_peak_biome(i, j, weirdness.clone());
 
                 add_surface_biome(
@@ -785,7 +868,11 @@ mod biome_data {
         }
     }
 
-    pub fn add_mid_slice(v: &mut BiomeList, weirdness: RangeInclusive<i64>) {
+    pub fn add_mid_slice(
+        v: &mut BiomeList,
+        weirdness: RangeInclusive<i64>,
+        version: MinecraftVersion,
+    ) {
         add_surface_biome(
             v,
             -10000..=10000,
@@ -796,18 +883,14 @@ mod biome_data {
             0.0,
             BiomeId(StonyShore as i32),
         );
-        add_surface_biome(
+        add_swamps(
             v,
-            merge_ranges(TEMPERATURES[1].clone(), TEMPERATURES[4].clone()),
-            -10000..=10000,
             merge_ranges(
                 CONTINENTALNESSES[ContinentalnessIndex::NearInland as usize].clone(),
                 CONTINENTALNESSES[ContinentalnessIndex::FarInland as usize].clone(),
             ),
-            EROSIONS[6].clone(),
             weirdness.clone(),
-            0.0,
-            BiomeId(Swamp as i32),
+            version,
         );
 
         for i in 0..TEMPERATURES.len() {
@@ -818,14 +901,18 @@ mod biome_data {
 
                 let b0 = pick_middle_biome(i, j, weirdness.clone());
                 let b1 = pick_middle_biome_or_badlands_if_hot(i, j, weirdness.clone());
-                let b2 =
-                    pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, weirdness.clone());
+                let b2 = pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(
+                    i,
+                    j,
+                    weirdness.clone(),
+                    version,
+                );
                 let b3 = pick_extreme_hills_biome(i, j, weirdness.clone());
-                let b4 = pick_plateau_biome(i, j, weirdness.clone());
+                let b4 = pick_plateau_biome(i, j, weirdness.clone(), version);
                 let b5 = pick_beach_biome(i, j);
                 let b6 = maybe_pick_shattered_biome(i, j, weirdness.clone()).unwrap_or(b0);
                 let b7 = pick_shattered_coast_biome(i, j, weirdness.clone());
-                let b8 = pick_slope_biome(i, j, weirdness.clone());
+                let b8 = pick_slope_biome(i, j, weirdness.clone(), version);
 
                 add_surface_biome(
                     v,
@@ -1037,7 +1124,11 @@ mod biome_data {
         }
     }
 
-    pub fn add_low_slice(v: &mut BiomeList, weirdness: RangeInclusive<i64>) {
+    pub fn add_low_slice(
+        v: &mut BiomeList,
+        weirdness: RangeInclusive<i64>,
+        version: MinecraftVersion,
+    ) {
         add_surface_biome(
             v,
             -10000..=10000,
@@ -1048,18 +1139,14 @@ mod biome_data {
             0.0,
             BiomeId(StonyShore as i32),
         );
-        add_surface_biome(
+        add_swamps(
             v,
-            merge_ranges(TEMPERATURES[1].clone(), TEMPERATURES[4].clone()),
-            -10000..=10000,
             merge_ranges(
                 CONTINENTALNESSES[ContinentalnessIndex::NearInland as usize].clone(),
                 CONTINENTALNESSES[ContinentalnessIndex::FarInland as usize].clone(),
             ),
-            EROSIONS[6].clone(),
             weirdness.clone(),
-            0.0,
-            BiomeId(Swamp as i32),
+            version,
         );
 
         for i in 0..TEMPERATURES.len() {
@@ -1070,8 +1157,12 @@ mod biome_data {
 
                 let b0 = pick_middle_biome(i, j, weirdness.clone());
                 let b1 = pick_middle_biome_or_badlands_if_hot(i, j, weirdness.clone());
-                let b2 =
-                    pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, weirdness.clone());
+                let b2 = pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(
+                    i,
+                    j,
+                    weirdness.clone(),
+                    version,
+                );
                 let b3 = pick_beach_biome(i, j);
                 let b4 = maybe_pick_shattered_biome(i, j, weirdness.clone()).unwrap_or(b0);
                 let b5 = pick_shattered_coast_biome(i, j, weirdness.clone());
@@ -1208,7 +1299,11 @@ mod biome_data {
         }
     }
 
-    pub fn add_valleys(v: &mut BiomeList, weirdness: RangeInclusive<i64>) {
+    pub fn add_valleys(
+        v: &mut BiomeList,
+        weirdness: RangeInclusive<i64>,
+        version: MinecraftVersion,
+    ) {
         add_surface_biome(
             v,
             TEMPERATURES[0].clone(),
@@ -1303,18 +1398,14 @@ mod biome_data {
             0.0,
             BiomeId(River as i32),
         );
-        add_surface_biome(
+        add_swamps(
             v,
-            merge_ranges(TEMPERATURES[1].clone(), TEMPERATURES[4].clone()),
-            -10000..=10000,
             merge_ranges(
                 CONTINENTALNESSES[ContinentalnessIndex::Inland as usize].clone(),
                 CONTINENTALNESSES[ContinentalnessIndex::FarInland as usize].clone(),
             ),
-            EROSIONS[6].clone(),
             weirdness.clone(),
-            0.0,
-            BiomeId(Swamp as i32),
+            version,
         );
         add_surface_biome(
             v,
@@ -1355,7 +1446,7 @@ mod biome_data {
         }
     }
 
-    pub fn add_underground_biomes(v: &mut BiomeList) {
+    pub fn add_underground_biomes(v: &mut BiomeList, version: MinecraftVersion) {
         let dripstone_caves = BiomeId(fastanvil::biome::Biome::DripstoneCaves as i32);
         let lush_caves = BiomeId(fastanvil::biome::Biome::LushCaves as i32);
         add_underground_biome(
@@ -1378,6 +1469,60 @@ mod biome_data {
             0.0,
             lush_caves,
         );
+        if version >= MinecraftVersion::Java1_19 {
+            add_bottom_biome(
+                v,
+                -10000..=10000,
+                -10000..=10000,
+                -10000..=10000,
+                merge_ranges(EROSIONS[0].clone(), EROSIONS[1].clone()),
+                -10000..=10000,
+                DEEP_DARK,
+            );
+        }
+    }
+
+    /// Before 1.19 all the swamps use the same parameters, since 1.19 the
+    /// warm swamps are mangrove swamps
+    pub fn add_swamps(
+        v: &mut BiomeList,
+        continentalness: RangeInclusive<i64>,
+        weirdness: RangeInclusive<i64>,
+        version: MinecraftVersion,
+    ) {
+        if version < MinecraftVersion::Java1_19 {
+            add_surface_biome(
+                v,
+                merge_ranges(TEMPERATURES[1].clone(), TEMPERATURES[4].clone()),
+                -10000..=10000,
+                continentalness,
+                EROSIONS[6].clone(),
+                weirdness,
+                0.0,
+                BiomeId(Swamp as i32),
+            );
+        } else {
+            add_surface_biome(
+                v,
+                merge_ranges(TEMPERATURES[1].clone(), TEMPERATURES[2].clone()),
+                -10000..=10000,
+                continentalness.clone(),
+                EROSIONS[6].clone(),
+                weirdness.clone(),
+                0.0,
+                BiomeId(Swamp as i32),
+            );
+            add_surface_biome(
+                v,
+                merge_ranges(TEMPERATURES[3].clone(), TEMPERATURES[4].clone()),
+                -10000..=10000,
+                continentalness,
+                EROSIONS[6].clone(),
+                weirdness,
+                0.0,
+                MANGROVE_SWAMP,
+            );
+        }
     }
 
     pub fn pick_middle_biome(i: usize, j: usize, weirdness: RangeInclusive<i64>) -> BiomeId {
@@ -1404,9 +1549,10 @@ mod biome_data {
         i: usize,
         j: usize,
         weirdness: RangeInclusive<i64>,
+        version: MinecraftVersion,
     ) -> BiomeId {
         if i == 0 {
-            pick_slope_biome(i, j, weirdness)
+            pick_slope_biome(i, j, weirdness, version)
         } else {
             pick_middle_biome_or_badlands_if_hot(i, j, weirdness)
         }
@@ -1460,11 +1606,26 @@ mod biome_data {
         }
     }
 
-    pub fn pick_plateau_biome(i: usize, j: usize, weirdness: RangeInclusive<i64>) -> BiomeId {
+    pub fn pick_plateau_biome(
+        i: usize,
+        j: usize,
+        weirdness: RangeInclusive<i64>,
+        version: MinecraftVersion,
+    ) -> BiomeId {
+        let plateau = PLATEAU_BIOMES[i][j];
+        // 1.21.4 added the pale garden as the variant of the dark forest
+        let variant = if version >= MinecraftVersion::Java1_21 && (i, j) == (2, 4) {
+            Some(PALE_GARDEN)
+        } else if version >= MinecraftVersion::Java1_20 {
+            PLATEAU_BIOMES_VARIANT_1_20[i][j]
+        } else {
+            PLATEAU_BIOMES_VARIANT[i][j]
+        };
+
         if *weirdness.end() < 0 {
-            PLATEAU_BIOMES[i][j]
+            plateau
         } else {
-            PLATEAU_BIOMES_VARIANT[i][j].unwrap_or_else(|| PLATEAU_BIOMES[i][j])
+            variant.unwrap_or(plateau)
         }
     }
 
@@ -1482,9 +1643,14 @@ mod biome_data {
         }
     }
 
-    pub fn pick_slope_biome(i: usize, j: usize, weirdness: RangeInclusive<i64>) -> BiomeId {
+    pub fn pick_slope_biome(
+        i: usize,
+        j: usize,
+        weirdness: RangeInclusive<i64>,
+        version: MinecraftVersion,
+    ) -> BiomeId {
         if i >= 3 {
-            pick_plateau_biome(i, j, weirdness)
+            pick_plateau_biome(i, j, weirdness, version)
         } else if j <= 1 {
             BiomeId(SnowySlopes as i32)
         } else {
@@ -1546,6 +1712,30 @@ mod biome_data {
         );
     }
 
+    /// Biomes at the bottom of the world, below the underground biomes
+    pub fn add_bottom_biome(
+        v: &mut BiomeList,
+        temperature: RangeInclusive<i64>,
+        humidity: RangeInclusive<i64>,
+        continentalness: RangeInclusive<i64>,
+        erosion: RangeInclusive<i64>,
+        weirdness: RangeInclusive<i64>,
+        biome_id: BiomeId,
+    ) {
+        let depth = 11000..=11000;
+        v.add(
+            biome_id,
+            ClimateRange {
+                temperature,
+                humidity,
+                continentalness,
+                erosion,
+                depth,
+                weirdness,
+            },
+        );
+    }
+
     pub fn merge_ranges(a: RangeInclusive<i64>, b: RangeInclusive<i64>) -> RangeInclusive<i64> {
         *a.start()..=*b.end()
     }
@@ -1820,3 +2010,89 @@ mod biome_tree {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fastanvil::biome::Biome;
+
+    fn climate(t: i64, h: i64, c: i64, e: i64, d: i64, w: i64) -> Climate {
+        Climate {
+            temperature: t,
+            humidity: h,
+            continentalness: c,
+            erosion: e,
+            depth: d,
+            weirdness: w,
+        }
+    }
+
+    fn biome_in_each_version(c: &Climate) -> Vec<BiomeId> {
+        [
+            MinecraftVersion::Java1_18,
+            MinecraftVersion::Java1_19,
+            MinecraftVersion::Java1_20,
+            MinecraftVersion::Java1_21_3,
+            MinecraftVersion::Java1_21,
+        ]
+        .iter()
+        .map(|v| {
+            let list = biome_list(*v);
+            let b = list.search(c).unwrap();
+            assert_eq!(list.search_bruteforce(c), Some(b));
+            b
+        })
+        .collect()
+    }
+
+    #[test]
+    fn deep_dark() {
+        let c = climate(0, 0, 2000, -5000, 11000, 0);
+        let b = biome_in_each_version(&c);
+        assert_ne!(b[0], DEEP_DARK);
+        assert_eq!(b[1..], [DEEP_DARK; 4]);
+    }
+
+    #[test]
+    fn mangrove_swamp() {
+        let c = climate(6000, 0, 2000, 8000, 0, 1500);
+        let swamp = BiomeId(Biome::Swamp as i32);
+        assert_eq!(
+            biome_in_each_version(&c),
+            [
+                swamp,
+                MANGROVE_SWAMP,
+                MANGROVE_SWAMP,
+                MANGROVE_SWAMP,
+                MANGROVE_SWAMP
+            ]
+        );
+        // Cold swamps did not change
+        let c = climate(0, 0, 2000, 8000, 0, 1500);
+        assert_eq!(biome_in_each_version(&c), [swamp; 5]);
+    }
+
+    #[test]
+    fn cherry_grove_and_pale_garden() {
+        let c = climate(-3000, -5000, 5000, -3000, 0, 5000);
+        let meadow = BiomeId(Biome::Meadow as i32);
+        assert_eq!(
+            biome_in_each_version(&c),
+            [meadow, meadow, CHERRY_GROVE, CHERRY_GROVE, CHERRY_GROVE]
+        );
+        let c = climate(0, 5000, 5000, -3000, 0, -5000);
+        let dark_forest = BiomeId(Biome::DarkForest as i32);
+        assert_eq!(biome_in_each_version(&c), [dark_forest; 5]);
+        let c = climate(0, 5000, 5000, -3000, 0, 5000);
+        assert_eq!(
+            biome_in_each_version(&c),
+            [
+                dark_forest,
+                dark_forest,
+                dark_forest,
+                dark_forest,
+                PALE_GARDEN
+            ]
+        );
+    }
+}
@@ -14,6 +14,7 @@ use std::sync::Arc;
 use std::sync::RwLock;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::convert::TryFrom;
 use std::convert::TryInto;
 use crate::java_rng::JavaRng;
@@ -26,8 +27,12 @@ use crate::biome_info::biome_id;
 use crate::biome_info::BIOME_COLORS;
 use crate::biome_info::BIOME_INFO;
 use crate::biome_info::UNKNOWN_BIOME_ID;
+use crate::biome_info_118;
+use crate::biome_info_118::BiomeList;
 use crate::spline::Spline;
 use crate::climate::Climate;
+use crate::climate::ClimateRange;
+use crate::heightmap::OffsetHeightmap118;
 
 // The different Map* layers are copied from
 // https://github.com/Cubitect/cubiomes
@@ -555,8 +560,24 @@ pub fn biome_to_color(id: i32) -> [u8; 4] {
         id &= 0xFF;
     }
 
-    if (174..(174+20)).contains(&id) {
-        // 1.18 biomes are not present in BIOME_COLORS map, hardcode them here for the moment
+    if (40..=43).contains(&id) || (170..=173).contains(&id) {
+        // End and nether biomes are not present in BIOME_COLORS map either
+        // Colors from cubiomes util.c
+        let [r, g, b] = match id {
+            40 => [75, 75, 171],
+            41 => [201, 201, 89],
+            42 => [181, 181, 54],
+            43 => [112, 112, 204],
+            170 => [77, 58, 46],
+            171 => [152, 26, 17],
+            172 => [73, 144, 123],
+            _ => [100, 95, 99],
+        };
+        return [r, g, b, 255];
+    }
+
+    if (174..(174+24)).contains(&id) {
+        // 1.18+ biomes are not present in BIOME_COLORS map, hardcode them here for the moment
         // Colors from cubiomes util.c
         /*
         174 => "DripstoneCaves",
@@ -579,6 +600,10 @@ pub fn biome_to_color(id: i32) -> [u8; 4] {
         191 => "WindsweptHills",
         192 => "WindsweptSavanna",
         193 => "WoodedBadlands",
+        194 => "DeepDark",
+        195 => "MangroveSwamp",
+        196 => "CherryGrove",
+        197 => "PaleGarden",
         */
         let [r, g, b] = match id {
             174 => [78, 48, 18],
@@ -601,6 +626,10 @@ pub fn biome_to_color(id: i32) -> [u8; 4] {
             191 => [0x59, 0x55, 0x53],
             192 => [0x7e, 0x79, 0x58],
             193 => [0x83, 0x38, 0x06],
+            194 => [0x03, 0x1f, 0x29],
+            195 => [0x2c, 0xcc, 0x8e],
+            196 => [0xff, 0x91, 0xc8],
+            197 => [0x69, 0x6d, 0x95],
             // Give unique colors to unknown biomes, so they can be shown as "Biome #id" in the web
             // demo
             id => [1, 255, id as u8],
@@ -1401,10 +1430,149 @@ impl GetMap3D for MapVoronoiZoom118 {
             }
         }
 
+        // The y coordinate is ignored, so all the y levels are equal to y = 0
+        for y in 1..area.sy as usize {
+            for x in 0..area.sx as usize {
+                for z in 0..area.sz as usize {
+                    m.a[(x, y, z)] = m.a[(x, 0, z)];
+                }
+            }
+        }
+
         m
     }
 }
 
+/// Converts a 1:4 map into a 1:1 map without voronoi, as used by the nether and
+/// End before 1.15, and by Bedrock Edition
+pub struct MapQuarterToBlock {
+    pub parent: Rc<dyn GetMap3D>,
+}
+
+impl GetMap3D for MapQuarterToBlock {
+    fn get_map_3d(&self, area: Area3D) -> Map3D {
+        let parea = Area3D {
+            x: area.x >> 2,
+            y: area.y >> 2,
+            z: area.z >> 2,
+            sx: (((area.x + area.sx as i64 - 1) >> 2) - (area.x >> 2) + 1) as u64,
+            sy: (((area.y + area.sy as i64 - 1) >> 2) - (area.y >> 2) + 1) as u64,
+            sz: (((area.z + area.sz as i64 - 1) >> 2) - (area.z >> 2) + 1) as u64,
+        };
+        let pmap = self.parent.get_map_3d(parea);
+
+        Map3D::from_area_fn(area, |(x, y, z)| {
+            pmap.get(
+                (area.x + x as i64) >> 2,
+                (area.y + y as i64) >> 2,
+                (area.z + z as i64) >> 2,
+            )
+        })
+    }
+
+    fn get_map_from_pmap_3d(&self, pmap: &Map3D) -> Map3D {
+        self.get_map_3d(pmap.area())
+    }
+}
+
+lazy_static! {
+    static ref CONTINENTAL_SPLINE: Arc<Spline> = Arc::new(Spline::new_continental());
+}
+
+/// The noises used by the 1.18 biome generator. The climate noises are listed in the order used
+/// by `filter_seeds_using_biomes_118`, from the cheapest to the most expensive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClimateNoise118 {
+    Temperature,
+    Humidity,
+    Weirdness,
+    Erosion,
+    Continentalness,
+    Shift,
+}
+
+impl ClimateNoise118 {
+    const CLIMATE: [Self; 5] = [Self::Temperature, Self::Humidity, Self::Weirdness, Self::Erosion, Self::Continentalness];
+
+    /// The two halves of the seed used to initialize all the noises
+    pub fn xoroshiro_seed(world_seed: i64) -> (u64, u64) {
+        let mut pxr = Xoroshiro128PlusPlus::with_u64_seed(world_seed as u64);
+        let xlo = pxr.next_long();
+        let xhi = pxr.next_long();
+
+        (xlo, xhi)
+    }
+
+    pub fn new_noise(self, xlo: u64, xhi: u64) -> NoiseGeneratorDoublePerlin128 {
+        let (amp, omin, md5): (&[f64], i32, (u64, u64)) = match self {
+            // md5 "minecraft:offset"
+            Self::Shift => (&[1.0, 1.0, 1.0, 0.0], -3, (0x080518cf6af25384, 0x3f3dfb40a54febd5)),
+            // md5 "minecraft:temperature"
+            Self::Temperature => (&[1.5, 0.0, 1.0, 0.0, 0.0, 0.0], -10, (0x5c7e6b29735f0d7f, 0xf7d86f1bbc734988)),
+            // md5 "minecraft:vegetation"
+            Self::Humidity => (&[1.0, 1.0, 0.0, 0.0, 0.0, 0.0], -8, (0x81bb4d22e8dc168e, 0xf1c8b4bea16303cd)),
+            // md5 "minecraft:continentalness"
+            Self::Continentalness => (&[1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 1.0, 1.0, 1.0], -9, (0x83886c9d0ae3a662, 0xafa638a61b42e8ad)),
+            // md5 "minecraft:erosion"
+            Self::Erosion => (&[1.0, 1.0, 0.0, 1.0, 1.0], -9, (0xd02491e6058f6fd8, 0x4792512c94c17a80)),
+            // md5 "minecraft:ridge"
+            Self::Weirdness => (&[1.0, 2.0, 1.0, 0.0, 0.0, 0.0], -7, (0xefc8ef4d36102b34, 0x1beeeb324a0f24ea)),
+        };
+        let mut pxr = Xoroshiro128PlusPlus::new(xlo ^ md5.0, xhi ^ md5.1);
+
+        NoiseGeneratorDoublePerlin128::new(&mut pxr, amp, omin)
+    }
+
+    fn value(self, climate: &mut Climate) -> &mut i64 {
+        match self {
+            Self::Temperature => &mut climate.temperature,
+            Self::Humidity => &mut climate.humidity,
+            Self::Weirdness => &mut climate.weirdness,
+            Self::Erosion => &mut climate.erosion,
+            Self::Continentalness => &mut climate.continentalness,
+            Self::Shift => panic!("Shift is not a climate parameter"),
+        }
+    }
+
+    fn range(self, climate_range: &ClimateRange) -> &RangeInclusive<i64> {
+        match self {
+            Self::Temperature => &climate_range.temperature,
+            Self::Humidity => &climate_range.humidity,
+            Self::Weirdness => &climate_range.weirdness,
+            Self::Erosion => &climate_range.erosion,
+            Self::Continentalness => &climate_range.continentalness,
+            Self::Shift => panic!("Shift is not a climate parameter"),
+        }
+    }
+
+    fn range_mut(self, climate_range: &mut ClimateRange) -> &mut RangeInclusive<i64> {
+        match self {
+            Self::Temperature => &mut climate_range.temperature,
+            Self::Humidity => &mut climate_range.humidity,
+            Self::Weirdness => &mut climate_range.weirdness,
+            Self::Erosion => &mut climate_range.erosion,
+            Self::Continentalness => &mut climate_range.continentalness,
+            Self::Shift => panic!("Shift is not a climate parameter"),
+        }
+    }
+}
+
+/// Input of the terrain splines: continentalness, erosion, ridges and weirdness
+pub fn spline_params_118(c: f64, e: f64, w: f64) -> [f32; 4] {
+    let np_param = [c, e, -3.0 * ((w.abs() - 0.6666667).abs() - 0.33333334), w];
+
+    np_param.map(|x| x as f32)
+}
+
+/// Depth climate parameter, the y coordinate uses 1:4 scale
+fn climate_depth_118(sp: &Spline, c: f64, e: f64, w: f64, y: i64) -> f32 {
+    let np_param = spline_params_118(c, e, w);
+    let off = sp.get_spline(&np_param) + 0.015;
+
+    //double py = y + sampleDoublePerlin(&bn->shift, y, z, x) * 4.0;
+    1.0 - ((y << 2) as f32) / 128.0 - 83.0/160.0 + off
+}
+
 /// Overworld and Nether biome generator for 1.18
 pub struct MapGenBiomeNoise3D118 {
     world_seed: i64,
@@ -1415,50 +1583,38 @@ pub struct MapGenBiomeNoise3D118 {
     erosion: NoiseGeneratorDoublePerlin128,
     weirdness: NoiseGeneratorDoublePerlin128,
     sp: Arc<Spline>,
+    biome_list: &'static BiomeList,
 }
 
 impl MapGenBiomeNoise3D118 {
-    pub fn new(world_seed: i64) -> Self {
-        let mut pxr = Xoroshiro128PlusPlus::with_u64_seed(world_seed as u64);
-        let xlo = pxr.next_long();
-        let xhi = pxr.next_long();
-
-        let amp_s = [1.0, 1.0, 1.0, 0.0];
-        // md5 "minecraft:offset"
-        pxr = Xoroshiro128PlusPlus::new(xlo ^ 0x080518cf6af25384, xhi ^ 0x3f3dfb40a54febd5);
-        let shift = NoiseGeneratorDoublePerlin128::new(&mut pxr, &amp_s, -3);
-
-        let amp_t = [1.5, 0.0, 1.0, 0.0, 0.0, 0.0];
-        // md5 "minecraft:temperature"
-        pxr = Xoroshiro128PlusPlus::new(xlo ^ 0x5c7e6b29735f0d7f, xhi ^ 0xf7d86f1bbc734988);
-        let temperature = NoiseGeneratorDoublePerlin128::new(&mut pxr, &amp_t, -10);
-
-        let amp_h = [1.0, 1.0, 0.0, 0.0, 0.0, 0.0];
-        // md5 "minecraft:vegetation"
-        pxr = Xoroshiro128PlusPlus::new(xlo ^ 0x81bb4d22e8dc168e, xhi ^ 0xf1c8b4bea16303cd);
-        let humidity = NoiseGeneratorDoublePerlin128::new(&mut pxr, &amp_h, -8);
+    pub fn new(world_seed: i64, version: MinecraftVersion) -> Self {
+        let (xlo, xhi) = ClimateNoise118::xoroshiro_seed(world_seed);
+        let shift = ClimateNoise118::Shift.new_noise(xlo, xhi);
+        let temperature = ClimateNoise118::Temperature.new_noise(xlo, xhi);
+        let humidity = ClimateNoise118::Humidity.new_noise(xlo, xhi);
+        let continentalness = ClimateNoise118::Continentalness.new_noise(xlo, xhi);
+        let erosion = ClimateNoise118::Erosion.new_noise(xlo, xhi);
+        let weirdness = ClimateNoise118::Weirdness.new_noise(xlo, xhi);
+        let sp = Arc::clone(&CONTINENTAL_SPLINE);
 
-        let amp_c = [1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 1.0, 1.0, 1.0];
-        // md5 "minecraft:continentalness"
-        pxr = Xoroshiro128PlusPlus::new(xlo ^ 0x83886c9d0ae3a662, xhi ^ 0xafa638a61b42e8ad);
-        let continentalness = NoiseGeneratorDoublePerlin128::new(&mut pxr, &amp_c, -9);
+        let biome_list = biome_info_118::biome_list(version);
 
-        let amp_e = [1.0, 1.0, 0.0, 1.0, 1.0];
-        // md5 "minecraft:erosion"
-        pxr = Xoroshiro128PlusPlus::new(xlo ^ 0xd02491e6058f6fd8, xhi ^ 0x4792512c94c17a80);
-        let erosion = NoiseGeneratorDoublePerlin128::new(&mut pxr, &amp_e, -9);
+        Self { world_seed, shift, temperature, humidity, continentalness, erosion, weirdness, sp, biome_list }
+    }
 
-        let amp_w = [1.0, 2.0, 1.0, 0.0, 0.0, 0.0];
-        // md5 "minecraft:ridge"
-        pxr = Xoroshiro128PlusPlus::new(xlo ^ 0xefc8ef4d36102b34, xhi ^ 0x1beeeb324a0f24ea);
-        let weirdness = NoiseGeneratorDoublePerlin128::new(&mut pxr, &amp_w, -7);
+    /// Input of the terrain splines at this position, using 1:4 coordinates. See
+    /// `spline_params_118`.
+    pub fn spline_params(&self, x: i64, z: i64) -> [f32; 4] {
+        let x = x as f64;
+        let z = z as f64;
+        let px = x + self.shift.sample(x, 0.0, z) * 4.0;
+        let pz = z + self.shift.sample(z, x, 0.0) * 4.0;
 
-        lazy_static! {
-            static ref CONTINENTAL_SPLINE: Arc<Spline> = Arc::new(Spline::new_continental());
-        }
-        let sp = Arc::clone(&CONTINENTAL_SPLINE);
+        let c = self.continentalness.sample(px, 0.0, pz);
+        let e = self.erosion.sample(px, 0.0, pz);
+        let w = self.weirdness.sample(px, 0.0, pz);
 
-        Self { world_seed, shift, temperature, humidity, continentalness, erosion, weirdness, sp }
+        spline_params_118(c, e, w)
     }
 
     fn sample_biome_noise(&self, np: Option<&mut Climate>, pos: Point3D, dat: &mut u64) -> i32 {
@@ -1475,12 +1631,7 @@ impl MapGenBiomeNoise3D118 {
         let e = self.erosion.sample(px, 0.0, pz);
         let w = self.weirdness.sample(px, 0.0, pz);
 
-        let np_param = [c, e, -3.0 * ((w.abs() - 0.6666667).abs() - 0.33333334), w];
-        let np_param = np_param.map(|x| x as f32);
-        let off = self.sp.get_spline(&np_param) + 0.015;
-
-        //double py = y + sampleDoublePerlin(&bn->shift, y, z, x) * 4.0;
-        let d = 1.0 - ((y << 2) as f32) / 128.0 - 83.0/160.0 + off;
+        let d = climate_depth_118(&self.sp, c, e, w, y);
 
         let t = self.temperature.sample(px, 0.0, pz);
         let h = self.humidity.sample(px, 0.0, pz);
@@ -1501,7 +1652,7 @@ impl MapGenBiomeNoise3D118 {
         p_np.depth = (10000.0*d) as i64;
         p_np.weirdness = (10000.0*w) as i64;
 
-        let id = p2overworld(p_np, dat);
+        let id = p2overworld(self.biome_list, p_np, dat);
 
         id
     }
@@ -1732,19 +1883,19 @@ impl MapGenBiomeNoise3D118 {
         }
 
         if part == 50 {
-            let dist = debug_distance_to_second_biome(p_np, dat);
+            let dist = debug_distance_to_second_biome(self.biome_list, p_np, dat);
             // +1 because in case of 0 distance log2(0) = -inf we want log2(1) = 0
             let dist_log2 = (1.0 + dist as f64).log2();
             return clamp_float_to_u8_range(dist_log2, 0.0, 12.5);
         }
         if part == 51 {
-            let diff = debug_search_bruteforce_xor_search_tree(p_np, dat);
+            let diff = debug_search_bruteforce_xor_search_tree(self.biome_list, p_np, dat);
             // Binary image
             let dist = if diff == 0 { 0 } else { 255 };
             return clamp_float_to_u8_range(dist as f64, 0.0, 256.0);
         }
 
-        let id = p2overworld(p_np, dat);
+        let id = p2overworld(self.biome_list, p_np, dat);
 
         if part == 8 {
             return id;
@@ -1783,17 +1934,17 @@ impl GetMap3D for MapGenBiomeNoise3D118 {
     }
 }
 
-fn p2overworld(np: &Climate, dat: &mut u64) -> i32 {
-    crate::biome_info_118::BIOME_LIST.search(np).unwrap().0
+fn p2overworld(biome_list: &BiomeList, np: &Climate, dat: &mut u64) -> i32 {
+    biome_list.search(np).unwrap().0
 }
 
-fn debug_distance_to_second_biome(np: &Climate, dat: &mut u64) -> i64 {
-    crate::biome_info_118::BIOME_LIST.distance_to_second_biome(np).unwrap()
+fn debug_distance_to_second_biome(biome_list: &BiomeList, np: &Climate, dat: &mut u64) -> i64 {
+    biome_list.distance_to_second_biome(np).unwrap()
 }
 
-fn debug_search_bruteforce_xor_search_tree(np: &Climate, dat: &mut u64) -> i32 {
-    let bb = crate::biome_info_118::BIOME_LIST.search_bruteforce(np).unwrap();
-    let bt = crate::biome_info_118::BIOME_LIST.search_tree(np).unwrap();
+fn debug_search_bruteforce_xor_search_tree(biome_list: &BiomeList, np: &Climate, dat: &mut u64) -> i32 {
+    let bb = biome_list.search_bruteforce(np).unwrap();
+    let bt = biome_list.search_tree(np).unwrap();
 
     bb.0 ^ bt.0
 }
@@ -5360,21 +5511,67 @@ pub fn river_seed_finder_range(river_coords_voronoi: &[Point], extra_biomes: &[(
     candidates_64
 }
 
+/// Surface biomes of one world seed, for 1.18 and later. The biomes are 3D, and the biome seen by
+/// the player is the one at the surface, or at sea level in oceans. The climate noises are
+/// initialized once, and used by both the heightmap and the biome generator.
+pub struct SurfaceBiomes118 {
+    heightmap: OffsetHeightmap118,
+    voronoi: MapVoronoiZoom118,
+}
+
+impl SurfaceBiomes118 {
+    pub fn new(world_seed: i64, version: MinecraftVersion) -> Self {
+        let climate = Rc::new(MapGenBiomeNoise3D118::new(world_seed, version));
+        let heightmap = OffsetHeightmap118::from_biome_generator(Rc::clone(&climate));
+        let mut voronoi = MapVoronoiZoom118::new(world_seed);
+        voronoi.parent = Some(climate);
+
+        Self { heightmap, voronoi }
+    }
+
+    /// Biome at this block, the same as `generate` with the `y_offset` of
+    /// `OffsetHeightmap118::surface_y_offset`
+    pub fn get_biome(&self, x: i64, z: i64) -> i32 {
+        let y_level = self.heightmap.surface_y_offset(x, z) as i64 - 16;
+        let area = Area3D::from_area2d_and_y_level(Area { x, z, w: 1, h: 1 }, y_level);
+
+        self.voronoi.get_map_3d(area).a[(0, 0, 0)]
+    }
+}
+
+/// Remove the seeds that do not generate the expected biomes. Since 1.18, the biomes are checked at
+/// the surface, or at sea level in oceans, see `SurfaceBiomes118`.
 pub fn filter_seeds_using_biomes(candidates: &[i64], extra_biomes: &[(BiomeId, Point)], version: MinecraftVersion) -> Vec<i64> {
+    filter_seeds_using_biomes_matching(candidates, extra_biomes, version, |generated, expected| generated == expected)
+}
+
+/// Remove the seeds that do not match the land and water observed in maps, see
+/// `ItemMap::to_map`. Ocean means any ocean or river, and plains means any other biome. Lakes and
+/// islands do not follow the biomes, so only 90% of the observations must match.
+pub fn filter_seeds_using_land_and_water(candidates: &[i64], observations: &[(BiomeId, Point)], version: MinecraftVersion) -> Vec<i64> {
+    let is_water = |id| is_oceanic(id) || id == biome_id::river || id == biome_id::frozenRiver;
+    filter_seeds_using_biomes_matching(candidates, observations, version, |generated, expected| is_water(generated) == is_water(expected))
+}
+
+fn filter_seeds_using_biomes_matching<F: Fn(i32, i32) -> bool>(candidates: &[i64], extra_biomes: &[(BiomeId, Point)], version: MinecraftVersion, matches: F) -> Vec<i64> {
     let mut valid_seeds = vec![];
     let last_layer = version.num_layers();
 
     for world_seed in candidates {
         let world_seed = *world_seed;
+        // The 1.18 generator is initialized once per seed, instead of once per point
+        let surface = if version >= MinecraftVersion::Java1_18 { Some(SurfaceBiomes118::new(world_seed, version)) } else { None };
         // When most rivers match, try extra biomes
         let mut hits = 0;
         let mut misses = 0;
         let target = extra_biomes.len() * 90 / 100;
         let max_misses = extra_biomes.len() - target;
         for (biome, Point {x, z}) in extra_biomes.iter().cloned() {
-            let area = Area { x, z, w: 1, h: 1 };
-            let g43 = generate_up_to_layer(version, area, world_seed, last_layer, 0);
-            if g43.a[(0, 0)] == biome.0 {
+            let g43 = match &surface {
+                Some(surface) => surface.get_biome(x, z),
+                None => generate_up_to_layer(version, Area { x, z, w: 1, h: 1 }, world_seed, last_layer, 0).a[(0, 0)],
+            };
+            if matches(g43, biome.0) {
                 hits += 1;
             } else {
                 misses += 1;
@@ -5393,6 +5590,115 @@ pub fn filter_seeds_using_biomes(candidates: &[i64], extra_biomes: &[(BiomeId, P
     valid_seeds
 }
 
+/// Remove the seeds that do not generate the expected biomes. For version >= 1.18
+///
+/// The biome coordinates use 1:4 scale in all the axes, as returned by
+/// `anvil::get_some_biomes_1_18`. All the biomes must match.
+pub fn filter_seeds_using_biomes_3d(candidates: &[i64], biomes: &[(BiomeId, Point3D4)], version: MinecraftVersion) -> Vec<i64> {
+    candidates.iter().copied().filter(|&world_seed| {
+        let generator = MapGenBiomeNoise3D118::new(world_seed, version);
+        biomes.iter().all(|(biome, p)| {
+            let p = Point3D { x: p.x, y: p.y, z: p.z };
+            generator.sample_biome_noise(None, p, &mut 0) == biome.0
+        })
+    }).collect()
+}
+
+/// Faster alternative to `filter_seeds_using_biomes_3d`. For version >= 1.18
+///
+/// Instead of generating the full climate of every point, the climate noises are initialized and
+/// sampled one at a time, and the seed is rejected as soon as one point has a climate that cannot
+/// generate the expected biome. Most of the seeds are rejected after sampling the temperature
+/// noise at a few points.
+///
+/// The early exit is only used for points with surface biomes, and it assumes that the
+/// parameters that have not been sampled yet are inside the range of the surface biomes. Then,
+/// a biome can only be the closest if the distance to one of its ranges, using only the sampled
+/// parameters, is not greater than the distance from the sampled parameters to the range of the
+/// surface biomes. The remaining seeds are checked using the full climate, exactly like
+/// `filter_seeds_using_biomes_3d`.
+pub fn filter_seeds_using_biomes_118(candidates: &[i64], biomes: &[(BiomeId, Point3D4)], version: MinecraftVersion) -> Vec<i64> {
+    // Used to ignore the parameters that have not been sampled yet
+    const ANY: RangeInclusive<i64> = -(1 << 40)..=(1 << 40);
+    let biome_list = biome_info_118::biome_list(version);
+    let is_surface_range = |r: &ClimateRange| r.depth == (0..=0) || r.depth == (10000..=10000);
+    let mut surface_envelope: Option<ClimateRange> = None;
+    for (r, _) in biome_list.iter().filter(|(r, _)| is_surface_range(r)) {
+        let e = surface_envelope.get_or_insert_with(|| r.clone());
+        for noise in ClimateNoise118::CLIMATE {
+            let (a, b) = (noise.range_mut(e), noise.range(r));
+            *a = std::cmp::min(*a.start(), *b.start())..=std::cmp::max(*a.end(), *b.end());
+        }
+    }
+    let surface_envelope = surface_envelope.expect("biome list has no surface biomes");
+
+    // For each surface biome and each step, the ranges of that biome using only the sampled parameters
+    let mut partial_ranges: HashMap<i32, Vec<Vec<ClimateRange>>> = HashMap::new();
+    for (biome, _) in biomes {
+        let surface_biome = biome_list.iter().filter(|(_, b)| b == biome).all(|(r, _)| is_surface_range(r));
+        if !surface_biome || partial_ranges.contains_key(&biome.0) {
+            continue;
+        }
+        let steps = (0..ClimateNoise118::CLIMATE.len()).map(|step| {
+            biome_list.iter().filter(|(_, b)| b == biome).map(|(r, _)| {
+                let mut r = r.clone();
+                for noise in &ClimateNoise118::CLIMATE[step + 1..] {
+                    *noise.range_mut(&mut r) = ANY;
+                }
+                r.depth = ANY;
+                r
+            }).collect()
+        }).collect();
+        partial_ranges.insert(biome.0, steps);
+    }
+
+    let mut dat = 0;
+
+    candidates.iter().copied().filter(|&world_seed| {
+        let (xlo, xhi) = ClimateNoise118::xoroshiro_seed(world_seed);
+        let shift = ClimateNoise118::Shift.new_noise(xlo, xhi);
+        // Shifted positions, computed while sampling the first noise
+        let mut pos: Vec<(f64, f64)> = Vec::with_capacity(biomes.len());
+        let mut climates: Vec<Climate> = biomes.iter().map(|_| Climate::default()).collect();
+        let mut raw = vec![[0.0; 5]; biomes.len()];
+        // Squared distance from the sampled parameters to the range of the surface biomes
+        let mut outside_distance = vec![0; biomes.len()];
+
+        for (step, noise) in ClimateNoise118::CLIMATE.into_iter().enumerate() {
+            let generator = noise.new_noise(xlo, xhi);
+            let envelope = noise.range(&surface_envelope);
+            for (i, (biome, p)) in biomes.iter().enumerate() {
+                if i == pos.len() {
+                    let x = p.x as f64;
+                    let z = p.z as f64;
+                    pos.push((x + shift.sample(x, 0.0, z) * 4.0, z + shift.sample(z, x, 0.0) * 4.0));
+                }
+                let (px, pz) = pos[i];
+                let v = generator.sample(px, 0.0, pz);
+                raw[i][step] = v;
+                let v = (10000.0*v) as i64;
+                *noise.value(&mut climates[i]) = v;
+                let outside = std::cmp::max(std::cmp::max(envelope.start() - v, v - envelope.end()), 0);
+                outside_distance[i] += outside * outside;
+
+                if let Some(steps) = partial_ranges.get(&biome.0) {
+                    if steps[step].iter().all(|r| r.distance_if_lower_than(&climates[i], outside_distance[i]).is_none()) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        biomes.iter().enumerate().all(|(i, (biome, p))| {
+            let [_t, _h, w, e, c] = raw[i];
+            let d = climate_depth_118(&CONTINENTAL_SPLINE, c, e, w, p.y);
+            climates[i].depth = (10000.0*d) as i64;
+
+            p2overworld(biome_list, &climates[i], &mut dat) == biome.0
+        })
+    }).collect()
+}
+
 /// Treasure Map River Seed Finder
 ///
 /// range_lo: 0
@@ -5954,7 +6260,7 @@ pub fn generate_fragment_treasure_map(version: MinecraftVersion, area: Area, see
 
             Rc::from(mhv)
         }
-        MinecraftVersion::Java1_18 => {
+        MinecraftVersion::Java1_18 | MinecraftVersion::Java1_19 | MinecraftVersion::Java1_20 | MinecraftVersion::Java1_21_3 | MinecraftVersion::Java1_21 => {
             let mut mhv = MapHalfVoronoiZoom115::new(seed);
             let parent = Rc::from(Map3DToMap2D { map_3d: generator_up_to_layer_1_18(seed, 8, version), y_level: 63 });
             mhv.parent = Some(parent);
@@ -5978,16 +6284,21 @@ pub fn generate_image(version: MinecraftVersion, area: Area, seed: i64, y_offset
 pub fn generate_image_up_to_layer(version: MinecraftVersion, area: Area, seed: i64, layer: u32, y_offset: u32) -> Vec<u8> {
     let map = generate_up_to_layer(version, area, seed, layer, y_offset);
 
+    draw_map_image_up_to_layer(version, layer, &map)
+}
+
+/// Draw a map generated using `generate_up_to_layer`, with the colors used for that layer
+pub fn draw_map_image_up_to_layer(version: MinecraftVersion, layer: u32, map: &Map) -> Vec<u8> {
     match (version, layer) {
         // Layers [0, 7] are used to visualize noise.
         // Layers 8 and 9 can use the default draw_map_image.
         // Layer 50 is similar to noise, except it should be easy to spot low values.
         // Layer 51 is a binary map of biome ids that are different in search_bruteforce and
         // search_tree
-        (MinecraftVersion::Java1_18, 0..=7 | 50 | 51) => {
-            draw_map_image_noise(&map)
+        (v, 0..=7 | 50 | 51) if v >= MinecraftVersion::Java1_18 => {
+            draw_map_image_noise(map)
         }
-        _ => draw_map_image(&map),
+        _ => draw_map_image(map),
     }
 }
 
@@ -6013,7 +6324,7 @@ pub fn generate_up_to_layer(version: MinecraftVersion, area: Area, seed: i64, nu
         MinecraftVersion::Java1_16 => generate_up_to_layer_1_15(area, seed, num_layers, version),
         MinecraftVersion::Java1_17 => generate_up_to_layer_1_15(area, seed, num_layers, version),
         // 1.18 introduces 3D biomes in the overworld
-        MinecraftVersion::Java1_18 => {
+        MinecraftVersion::Java1_18 | MinecraftVersion::Java1_19 | MinecraftVersion::Java1_20 | MinecraftVersion::Java1_21_3 | MinecraftVersion::Java1_21 => {
             let y_level = y_offset as i64 - 16;
             let area = Area3D::from_area2d_and_y_level(area, y_level);
             let map3d = generate_up_to_layer_1_18(area, seed, num_layers, version);
@@ -7013,7 +7324,7 @@ pub fn generator_up_to_layer_1_15(world_seed: i64, layer: u32, version: Minecraf
 pub fn generate_up_to_layer_1_18(a: Area3D, world_seed: i64, layer: u32, version: MinecraftVersion) -> Map3D {
     match layer {
         0..=8 | 50 | 51 => {
-            let g0 = MapGenBiomeNoise3D118::new(world_seed);
+            let g0 = MapGenBiomeNoise3D118::new(world_seed, version);
             g0.partial_get_map_3d(a, layer)
         }
         _ => {
@@ -7028,7 +7339,7 @@ pub fn generator_up_to_layer_1_18(world_seed: i64, layer: u32, version: Minecraf
     // 9: after voronoi
     // TODO: use helper layer to call partial_get_map_3d
     // currently this function just assumes that layers [0, 8] are always 8
-    let g0 = MapGenBiomeNoise3D118::new(world_seed);
+    let g0 = MapGenBiomeNoise3D118::new(world_seed, version);
     if layer <= 8 { return Box::new(g0); }
     let mut g1 = MapVoronoiZoom118::new(world_seed);
     g1.parent = Some(Rc::new(g0));
@@ -7901,6 +8212,119 @@ mod tests {
         assert_eq!(m.a[(0, 0)], 4);
     }
 
+    #[test]
+    fn filter_seeds_using_biomes_3d_1_18() {
+        let world_seed = -4100855569562546563;
+        let version = MinecraftVersion::Java1_18;
+        // Spread the points to sample many different biomes
+        let mut biomes = vec![];
+        for i in 0..8 {
+            for j in 0..8 {
+                let p = Point3D4 { x: i * 64 - 256, y: 16 - (i + j) % 3 * 8, z: j * 64 - 256 };
+                let a = Area3D { x: p.x, y: p.y, z: p.z, sx: 1, sy: 1, sz: 1 };
+                let m = generate_up_to_layer_1_18(a, world_seed, version.num_layers() - 1, version);
+                biomes.push((BiomeId(m.a[(0, 0, 0)]), p));
+            }
+        }
+
+        let candidates = [world_seed ^ 1, world_seed, world_seed.wrapping_add(1 << 48), 1234];
+        assert_eq!(filter_seeds_using_biomes_3d(&candidates, &biomes, version), vec![world_seed]);
+    }
+
+    #[test]
+    fn filter_seeds_using_biomes_118_early_exit() {
+        // The early exit must never reject the correct seed
+        for version in [MinecraftVersion::Java1_18, MinecraftVersion::Java1_21] {
+            let mut r = JavaRng::with_seed(1234);
+            for _ in 0..20 {
+                let world_seed = r.next_long();
+                let mut biomes = vec![];
+                for i in 0..4 {
+                    for j in 0..4 {
+                        let p = Point3D4 { x: i * 256 - 512 + j, y: 16 - (i + j) % 4 * 12, z: j * 256 - 512 + i };
+                        let a = Area3D { x: p.x, y: p.y, z: p.z, sx: 1, sy: 1, sz: 1 };
+                        let m = generate_up_to_layer_1_18(a, world_seed, version.num_layers() - 1, version);
+                        biomes.push((BiomeId(m.a[(0, 0, 0)]), p));
+                    }
+                }
+
+                let candidates: Vec<i64> = (0..20).map(|k| world_seed.wrapping_add(k << 40)).collect();
+                let slow = filter_seeds_using_biomes_3d(&candidates, &biomes, version);
+                assert_eq!(filter_seeds_using_biomes_118(&candidates, &biomes, version), slow);
+                assert!(slow.contains(&world_seed));
+            }
+        }
+    }
+
+    #[test]
+    fn oceans_1_18_follow_vanilla_climate_table() {
+        // The ocean variants of OverworldBiomeBuilder: deep oceans have continentalness between
+        // -1.05 and -0.455, and shallow oceans between -0.455 and -0.19. The temperature is split
+        // at -0.45, -0.15, 0.2 and 0.55, and there is no deep warm ocean
+        use biome_id::*;
+        let oceans = [
+            [frozenDeepOcean, coldDeepOcean, deepOcean, lukewarmDeepOcean, warmOcean],
+            [frozenOcean, coldOcean, ocean, lukewarmOcean, warmOcean],
+        ];
+        let version = MinecraftVersion::Java1_18;
+        for world_seed in [1234, -4100855569562546563] {
+            let climate = Rc::new(MapGenBiomeNoise3D118::new(world_seed, version));
+            let heightmap = OffsetHeightmap118::from_biome_generator(Rc::clone(&climate));
+            let mut seen = std::collections::HashSet::new();
+            for i in 0..60 {
+                for j in 0..60 {
+                    let (x, z) = (i * 80 - 2400, j * 80 - 2400);
+                    let y = heightmap.surface_y_offset(x * 4, z * 4) as i64 - 16;
+                    let mut np = Climate::default();
+                    let biome = climate.sample_biome_noise(Some(&mut np), Point3D { x, y, z }, &mut 0);
+                    let c = np.continentalness;
+                    let t = np.temperature;
+                    // Skip the borders, where the other parameters can change the biome
+                    let near = |v: i64, limits: &[i64]| limits.iter().any(|l| (v - l).abs() < 100);
+                    if !(-10500..-1900).contains(&c) || near(c, &[-4550]) || near(t, &[-4500, -1500, 2000, 5500]) {
+                        continue;
+                    }
+                    let ti = [-4500, -1500, 2000, 5500].iter().filter(|&&l| t >= l).count();
+                    let expected = oceans[(c >= -4550) as usize][ti];
+                    assert_eq!(biome, expected, "seed {} at ({}, {}, {}): {:?}", world_seed, x, y, z, np);
+                    seen.insert(expected);
+                }
+            }
+            assert!(seen.len() >= 6, "{:?}", seen);
+        }
+    }
+
+    #[test]
+    fn filter_seeds_using_biomes_1_18_oceans() {
+        // 2D biomes are the surface biomes, which in oceans is the ocean variant. At y = -64, humid
+        // oceans are lush caves
+        let version = MinecraftVersion::Java1_18;
+        let world_seed = 1234;
+        let heightmap = OffsetHeightmap118::new(world_seed, version);
+        let surface = SurfaceBiomes118::new(world_seed, version);
+        let mut oceans = vec![];
+        let mut differ_at_bottom = 0;
+        for i in 0..40 {
+            for j in 0..40 {
+                let (x, z) = (i * 200 - 4000, j * 200 - 4000);
+                let area = Area { x, z, w: 1, h: 1 };
+                let y_offset = heightmap.surface_y_offset(x, z);
+                let m = generate_up_to_layer(version, area, world_seed, version.num_layers(), y_offset);
+                let biome = m.a[(0, 0)];
+                assert_eq!(surface.get_biome(x, z), biome);
+                if is_oceanic(biome) {
+                    if generate_up_to_layer(version, area, world_seed, version.num_layers(), 0).a[(0, 0)] != biome {
+                        differ_at_bottom += 1;
+                    }
+                    oceans.push((BiomeId(biome), Point { x, z }));
+                }
+            }
+        }
+
+        assert!(differ_at_bottom > 0);
+        assert_eq!(filter_seeds_using_biomes(&[world_seed + 1, world_seed], &oceans, version), vec![world_seed]);
+    }
+
     #[test]
     fn test_generation_1_18_test2() {
         let world_seed = -4100855569562546563;
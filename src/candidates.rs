@@ -53,24 +53,68 @@ use crate::mc_rng::McRng;
 // bruteforcing. Do we need an additional heuristic to know which algorithms to check first?
 // In that case we can use a hardcoded cost.
 //
-struct Candidates {
-    list: Vec<CandidateKind>,
+// The graph is implemented below as BruteforceStep and find_shortest_path. Since the cost of a
+// step depends on the number of input candidates, which depends on the previous steps, the cost
+// of an edge is not fixed. But the graph is very small, so we can just try all the paths.
+//
+pub enum CandidateKind {
+    Low(LowBitsCandidates),
 }
 
-enum CandidateKind {
-    Low(LowBitsCandidates),
+/// A list of candidates where only the lower `num_known_bits` bits are known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LowBitsCandidates {
+    pub num_known_bits: u8,
+    pub patterns: Vec<u64>,
+    /// The similar biome seed of each pattern is also a candidate
+    pub add_similar_seed: bool,
 }
 
-struct LowBitsCandidates {
-    num_known_bits: u8,
-    patterns: Vec<u64>,
-    add_similar_seed: bool,
+impl LowBitsCandidates {
+    pub fn new(num_known_bits: u8, patterns: Vec<u64>) -> Self {
+        Self {
+            num_known_bits,
+            patterns,
+            add_similar_seed: false,
+        }
+    }
+
+    /// Number of candidates, including similar seeds
+    pub fn len(&self) -> usize {
+        if self.add_similar_seed {
+            self.patterns.len() * 2
+        } else {
+            self.patterns.len()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Return all the candidates, including similar seeds
+    pub fn into_seeds(self) -> Vec<u64> {
+        if !self.add_similar_seed {
+            return self.patterns;
+        }
+
+        let msk = mask(self.num_known_bits);
+        self.patterns
+            .into_iter()
+            .flat_map(|x| {
+                let similar_seed = McRng::similar_biome_seed(x as i64) as u64 & msk;
+                std::iter::once(x).chain(std::iter::once(similar_seed))
+            })
+            .collect()
+    }
 }
 
 impl CandidateKind {
-    pub fn from_end_pillars_16(patterns: Vec<u64>) -> Self {
+    /// The 16-bit pillar seed only depends on the lower 32 bits of the world seed, see the
+    /// end_pillars module
+    pub fn from_end_pillars_32(patterns: Vec<u64>) -> Self {
         Self::Low(LowBitsCandidates {
-            num_known_bits: 16,
+            num_known_bits: 32,
             patterns,
             add_similar_seed: false,
         })
@@ -192,6 +236,149 @@ impl MoreBits {
     }
 }
 
+/// An edge of the bruteforce graph: an algorithm that takes candidates with `input_bits` known
+/// bits and returns candidates with `output_bits` known bits. If both are equal, the step is a
+/// filter that removes some candidates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BruteforceStep<K> {
+    pub kind: K,
+    pub input_bits: u8,
+    pub output_bits: u8,
+    /// Seconds per input candidate
+    pub cost: f64,
+    /// Expected number of output candidates per input candidate
+    pub output_ratio: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlannedStep<K> {
+    pub step: BruteforceStep<K>,
+    /// Expected number of input candidates
+    pub input_candidates: f64,
+    pub estimated_seconds: f64,
+}
+
+/// A path in the bruteforce graph
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan<K> {
+    pub initial_bits: u8,
+    pub steps: Vec<PlannedStep<K>>,
+}
+
+impl<K> Plan<K> {
+    /// Number of known bits after running all the steps
+    pub fn output_bits(&self) -> u8 {
+        self.steps
+            .last()
+            .map(|s| s.step.output_bits)
+            .unwrap_or(self.initial_bits)
+    }
+
+    pub fn estimated_seconds(&self) -> f64 {
+        self.steps.iter().map(|s| s.estimated_seconds).sum()
+    }
+}
+
+impl<K: std::fmt::Debug> std::fmt::Display for Plan<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Estimated time: {}",
+            format_duration(self.estimated_seconds())
+        )?;
+        for s in &self.steps {
+            writeln!(
+                f,
+                "{:?}: {} -> {} bits, {:.0} candidates, {}",
+                s.step.kind,
+                s.step.input_bits,
+                s.step.output_bits,
+                s.input_candidates,
+                format_duration(s.estimated_seconds)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Human readable duration, rounded to seconds, minutes, hours or days
+pub fn format_duration(secs: f64) -> String {
+    if secs < 60.0 {
+        format!("{:.1} seconds", secs)
+    } else if secs < 2.0 * 3600.0 {
+        format!("{} minutes", (secs / 60.0).round())
+    } else if secs < 2.0 * 24.0 * 3600.0 {
+        format!("{} hours", (secs / 3600.0).round())
+    } else {
+        format!("{} days", (secs / (24.0 * 3600.0)).round())
+    }
+}
+
+/// Find the fastest path from `initial_bits` known bits to 64 known bits.
+///
+/// Each step can only be used once. All the filters that match the current number of known bits
+/// are always used, because they reduce the number of candidates. If it is not possible to reach
+/// 64 bits, returns the fastest path that reaches the highest number of bits.
+pub fn find_shortest_path<K: Clone + PartialEq>(
+    steps: &[BruteforceStep<K>],
+    initial_bits: u8,
+    initial_candidates: f64,
+) -> Plan<K> {
+    fn visit<K: Clone + PartialEq>(
+        steps: &[BruteforceStep<K>],
+        mut path: Vec<PlannedStep<K>>,
+        bits: u8,
+        mut candidates: f64,
+        best: &mut Option<(u8, f64, Vec<PlannedStep<K>>)>,
+    ) {
+        let used = |path: &[PlannedStep<K>], kind: &K| path.iter().any(|s| s.step.kind == *kind);
+        for s in steps {
+            if s.input_bits == bits && s.output_bits == bits && !used(&path, &s.kind) {
+                path.push(PlannedStep {
+                    step: s.clone(),
+                    input_candidates: candidates,
+                    estimated_seconds: s.cost * candidates,
+                });
+                candidates *= s.output_ratio;
+            }
+        }
+
+        let total: f64 = path.iter().map(|s| s.estimated_seconds).sum();
+        let is_better = match best {
+            None => true,
+            Some((best_bits, best_total, _)) => {
+                bits > *best_bits || (bits == *best_bits && total < *best_total)
+            }
+        };
+        if is_better {
+            *best = Some((bits, total, path.clone()));
+        }
+
+        for s in steps {
+            if s.input_bits == bits && s.output_bits > bits && !used(&path, &s.kind) {
+                let mut next_path = path.clone();
+                next_path.push(PlannedStep {
+                    step: s.clone(),
+                    input_candidates: candidates,
+                    estimated_seconds: s.cost * candidates,
+                });
+                let next_candidates = candidates * s.output_ratio;
+                visit(steps, next_path, s.output_bits, next_candidates, best);
+            }
+        }
+    }
+
+    let mut best = None;
+    visit(steps, vec![], initial_bits, initial_candidates, &mut best);
+    let (_bits, _total, steps) = best.unwrap();
+
+    Plan {
+        initial_bits,
+        steps,
+    }
+}
+
 /// Return an iterator over all the possible n-bit values
 pub fn iter_bits_u32<'a>(n: u8) -> impl Iterator<Item = u32> + 'a {
     if n >= 32 {
@@ -228,4 +415,45 @@ mod tests {
         let vx1: Vec<_> = x1.collect();
         assert_eq!(vx0, vx1);
     }
+
+    #[test]
+    fn shortest_path() {
+        fn step(
+            kind: &'static str,
+            input_bits: u8,
+            output_bits: u8,
+            cost: f64,
+            output_ratio: f64,
+        ) -> BruteforceStep<&'static str> {
+            BruteforceStep {
+                kind,
+                input_bits,
+                output_bits,
+                cost,
+                output_ratio,
+            }
+        }
+        let steps = vec![
+            step("slime48", 0, 48, 1000.0, 10.0),
+            step("rivers26", 0, 26, 10.0, 100.0),
+            step("slime48_from_26", 26, 48, 1.0, 0.1),
+            step("extend48", 48, 64, 0.001, 1.0),
+            step("biomes64", 64, 64, 0.01, 0.1),
+        ];
+        let plan = find_shortest_path(&steps, 0, 1.0);
+        let kinds: Vec<_> = plan.steps.iter().map(|s| s.step.kind).collect();
+        assert_eq!(
+            kinds,
+            vec!["rivers26", "slime48_from_26", "extend48", "biomes64"]
+        );
+        assert_eq!(plan.output_bits(), 64);
+        assert!((plan.estimated_seconds() - (10.0 + 100.0 + 0.01 + 0.1)).abs() < 1e-9);
+
+        // Without the rivers the only path is slime48
+        let plan = find_shortest_path(&steps[..1], 0, 1.0);
+        assert_eq!(plan.output_bits(), 48);
+        let plan = find_shortest_path(&steps[3..], 0, 1.0);
+        assert_eq!(plan.output_bits(), 0);
+        assert!(plan.steps.is_empty());
+    }
 }
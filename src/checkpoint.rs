@@ -0,0 +1,571 @@
+//! Save the progress of long bruteforce searches, to be able to stop them and
+//! continue later.
+//!
+//! A search is a list of steps, and each step bruteforces the range
+//! `0..total`, split into small blocks. The checkpoint stores the blocks of
+//! the current step that have already been searched and the candidates found
+//! in them, so resuming the search only tries the remaining blocks.
+use crate::seed_info::ReadError;
+use log::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Minimum time between two automatic saves
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Ask all the running searches to stop as soon as possible. This is meant to
+/// be called from a Ctrl-C handler.
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+/// Allow the searches to run again after `interrupt`. This is used by the
+/// interactive shell, which keeps running after a search is interrupted.
+pub fn clear_interrupted() {
+    INTERRUPTED.store(false, Ordering::Relaxed);
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Identifier of a search: the name of the search followed by a hash of its
+/// arguments, so a checkpoint cannot be resumed with different arguments.
+pub fn task_id<T: Serialize>(name: &str, args: &T) -> String {
+    let args = serde_json::to_vec(args).expect("Serialization fail");
+    format!("{} {:x}", name, Sha256::digest(args))
+}
+
+/// The search was interrupted before finishing
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Interrupted")
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    /// Identifies the search, see `task_id`
+    pub task: String,
+    /// Data that must stay the same when resuming the search, for example the
+    /// list of steps
+    #[serde(default)]
+    pub plan: serde_json::Value,
+    /// Index of the current step
+    #[serde(default)]
+    pub step: usize,
+    /// Input candidates of the current step, if any
+    #[serde(default)]
+    pub input: Option<Vec<u64>>,
+    /// Sorted and non-overlapping ranges of the current step that have already
+    /// been searched
+    #[serde(default)]
+    pub done: Vec<(u64, u64)>,
+    /// Candidates found in the `done` ranges
+    #[serde(default)]
+    pub found: Vec<u64>,
+}
+
+impl Checkpoint {
+    pub fn new(task: String) -> Self {
+        Self {
+            task,
+            ..Default::default()
+        }
+    }
+
+    /// Mark the range `lo..hi` as searched
+    pub fn add_done(&mut self, lo: u64, hi: u64, found: Vec<u64>) {
+        self.found.extend(found);
+        if lo >= hi {
+            return;
+        }
+        let i = self.done.partition_point(|r| r.0 < lo);
+        self.done.insert(i, (lo, hi));
+
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(self.done.len());
+        for r in self.done.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.1 >= r.0 => last.1 = cmp::max(last.1, r.1),
+                _ => merged.push(r),
+            }
+        }
+        self.done = merged;
+    }
+
+    /// The ranges of `0..total` that have not been searched yet
+    pub fn remaining(&self, total: u64) -> Vec<(u64, u64)> {
+        let mut r = vec![];
+        let mut lo = 0;
+        for &(done_lo, done_hi) in &self.done {
+            if done_lo >= total {
+                break;
+            }
+            if done_lo > lo {
+                r.push((lo, done_lo));
+            }
+            lo = cmp::max(lo, done_hi);
+        }
+        if lo < total {
+            r.push((lo, total));
+        }
+
+        r
+    }
+
+    /// Number of elements that have already been searched
+    pub fn done_count(&self) -> u64 {
+        self.done.iter().map(|(lo, hi)| hi - lo).sum()
+    }
+
+    /// Finish the current step, using `output` as the input of the next one
+    pub fn next_step(&mut self, output: Vec<u64>) {
+        self.step += 1;
+        self.input = Some(output);
+        self.done.clear();
+        self.found.clear();
+    }
+}
+
+/// A `Checkpoint` that is periodically saved to a file
+pub struct Checkpointer {
+    pub state: Checkpoint,
+    path: Option<PathBuf>,
+    last_save: Option<Instant>,
+    /// Directory and name of the file created by `save_interrupted`, if the
+    /// checkpoint does not have a file yet
+    interrupt_file: Option<(PathBuf, String)>,
+}
+
+impl Checkpointer {
+    /// Keep the checkpoint in memory: the search can be interrupted, but not
+    /// resumed.
+    pub fn in_memory(task: String) -> Self {
+        Self {
+            state: Checkpoint::new(task),
+            path: None,
+            last_save: None,
+            interrupt_file: None,
+        }
+    }
+
+    /// Start a new search, saving the progress to `path`
+    pub fn create(path: PathBuf, task: String) -> Self {
+        Self {
+            state: Checkpoint::new(task),
+            path: Some(path),
+            last_save: Some(Instant::now()),
+            interrupt_file: None,
+        }
+    }
+
+    /// Start a new search that is kept in memory, and only saved to a file in
+    /// `dir` if it is interrupted, see `save_interrupted`. So searches that
+    /// finish or crash do not leave any file behind.
+    pub fn create_on_interrupt(dir: &Path, name: &str, task: String) -> Self {
+        Self {
+            interrupt_file: Some((dir.to_path_buf(), name.to_string())),
+            ..Self::in_memory(task)
+        }
+    }
+
+    /// Save the checkpoint of an interrupted search. If the checkpoint does
+    /// not have a file yet, it is saved to `<name>.checkpoint.json`. If that
+    /// file already exists it belongs to another search, so
+    /// `<name>.1.checkpoint.json`, `<name>.2.checkpoint.json`, etc are tried
+    /// instead.
+    pub fn save_interrupted(&mut self) -> io::Result<()> {
+        if self.path.is_none() {
+            if let Some((dir, name)) = self.interrupt_file.take() {
+                self.path = Some(create_unique_file(&dir, &name)?);
+            }
+        }
+
+        self.save()
+    }
+
+    /// Continue the search saved in `path`. If the file does not exist, start
+    /// a new search.
+    pub fn resume(path: PathBuf, task: String) -> Result<Self, ReadError> {
+        if !path.exists() {
+            return Ok(Self::create(path, task));
+        }
+        let file = fs::File::open(&path)?;
+        let state: Checkpoint = serde_json::from_reader(io::BufReader::new(file))?;
+        if state.task != task {
+            return Err(ReadError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Checkpoint {} belongs to a different search: {:?}",
+                    path.display(),
+                    state.task
+                ),
+            )));
+        }
+        info!(
+            "Resuming from step {}, {} already searched",
+            state.step,
+            state.done_count()
+        );
+
+        Ok(Self {
+            state,
+            path: Some(path),
+            last_save: Some(Instant::now()),
+            interrupt_file: None,
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Write the checkpoint to the file. The file is replaced atomically, so
+    /// it is never left half-written.
+    pub fn save(&mut self) -> io::Result<()> {
+        if let Some(path) = &self.path {
+            write_checkpoint(path, &self.state)?;
+            self.last_save = Some(Instant::now());
+        }
+
+        Ok(())
+    }
+
+    /// Save the checkpoint if enough time has passed since the last save
+    pub fn autosave(&mut self) {
+        if let Some((path, state)) = self.autosave_copy() {
+            log_save_error(write_checkpoint(&path, &state));
+        }
+    }
+
+    /// If enough time has passed since the last save, return a copy of the
+    /// checkpoint to be written by the caller. This way the file can be written
+    /// without holding a lock shared with other threads.
+    fn autosave_copy(&mut self) -> Option<(PathBuf, Checkpoint)> {
+        let path = self.path.as_ref()?;
+        if self.last_save.is_none_or(|t| t.elapsed() < SAVE_INTERVAL) {
+            return None;
+        }
+        self.last_save = Some(Instant::now());
+
+        Some((path.clone(), self.state.clone()))
+    }
+
+    fn save_or_log(&mut self) {
+        log_save_error(self.save());
+    }
+
+    /// Finish the current step and save the checkpoint
+    pub fn next_step(&mut self, output: Vec<u64>) {
+        self.state.next_step(output);
+        self.save_or_log();
+    }
+
+    /// Call `f(lo, hi)` for all the blocks of `0..total` that have not been
+    /// searched yet, using `num_threads` threads, and return all the
+    /// candidates found, including the ones found before resuming.
+    ///
+    /// When `num_threads` is 0 or 1 no threads are spawned, so this also works
+    /// in wasm.
+    pub fn run_range<F>(
+        &mut self,
+        num_threads: usize,
+        total: u64,
+        block: u64,
+        f: F,
+    ) -> Result<Vec<u64>, Interrupted>
+    where
+        F: Fn(u64, u64) -> Vec<u64> + Sync,
+    {
+        self.run(num_threads, total, block, false, f)
+    }
+
+    /// Same as `run_range`, but stop as soon as one block returns some
+    /// candidates.
+    pub fn run_range_until_found<F>(
+        &mut self,
+        num_threads: usize,
+        total: u64,
+        block: u64,
+        f: F,
+    ) -> Result<Vec<u64>, Interrupted>
+    where
+        F: Fn(u64, u64) -> Vec<u64> + Sync,
+    {
+        self.run(num_threads, total, block, true, f)
+    }
+
+    /// Same as `run_range`, but splitting a slice of candidates. The slice
+    /// must be the same when resuming.
+    pub fn run_slice<F>(
+        &mut self,
+        num_threads: usize,
+        candidates: &[u64],
+        block: u64,
+        f: F,
+    ) -> Result<Vec<u64>, Interrupted>
+    where
+        F: Fn(&[u64]) -> Vec<u64> + Sync,
+    {
+        self.run(
+            num_threads,
+            candidates.len() as u64,
+            block,
+            false,
+            |lo, hi| f(&candidates[lo as usize..hi as usize]),
+        )
+    }
+
+    fn run<F>(
+        &mut self,
+        num_threads: usize,
+        total: u64,
+        block: u64,
+        stop_when_found: bool,
+        f: F,
+    ) -> Result<Vec<u64>, Interrupted>
+    where
+        F: Fn(u64, u64) -> Vec<u64> + Sync,
+    {
+        let stopped = |state: &Checkpoint| {
+            state.remaining(total).is_empty() || (stop_when_found && !state.found.is_empty())
+        };
+        if !stopped(&self.state) {
+            let blocks = Mutex::new(Blocks::new(self.state.remaining(total), block));
+            let this = Mutex::new(&mut *self);
+            let work = || {
+                while !interrupted() {
+                    let next = blocks.lock().unwrap().next();
+                    let (lo, hi) = match next {
+                        Some(x) => x,
+                        None => return,
+                    };
+                    let found = f(lo, hi);
+                    let any_found = !found.is_empty();
+                    let copy = {
+                        let mut this = this.lock().unwrap();
+                        this.state.add_done(lo, hi, found);
+                        this.autosave_copy()
+                    };
+                    if let Some((path, state)) = copy {
+                        log_save_error(write_checkpoint(&path, &state));
+                    }
+                    if stop_when_found && any_found {
+                        blocks.lock().unwrap().clear();
+                        return;
+                    }
+                }
+            };
+
+            if num_threads <= 1 {
+                work();
+            } else {
+                thread::scope(|s| {
+                    for _ in 0..num_threads {
+                        s.spawn(work);
+                    }
+                });
+            }
+        }
+
+        if !stopped(&self.state) {
+            log_save_error(self.save_interrupted());
+            return Err(Interrupted);
+        }
+
+        let mut found = self.state.found.clone();
+        found.sort_unstable();
+
+        Ok(found)
+    }
+}
+
+/// Write the checkpoint to `path`. The file is replaced atomically, so it is
+/// never left half-written.
+fn write_checkpoint(path: &Path, state: &Checkpoint) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec(state)?)?;
+
+    fs::rename(&tmp, path)
+}
+
+fn log_save_error(r: io::Result<()>) {
+    if let Err(e) = r {
+        error!("Error saving checkpoint: {}", e);
+    }
+}
+
+/// Create a new empty file `<name>.checkpoint.json` in `dir`, or a numbered
+/// one if that file already exists. The file is created immediately, so two
+/// searches interrupted at the same time never share it.
+fn create_unique_file(dir: &Path, name: &str) -> io::Result<PathBuf> {
+    for n in 0u32.. {
+        let file_name = if n == 0 {
+            format!("{}.checkpoint.json", name)
+        } else {
+            format!("{}.{}.checkpoint.json", name, n)
+        };
+        let path = dir.join(file_name);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    unreachable!()
+}
+
+/// Queue of blocks of at most `block` elements
+struct Blocks {
+    /// Remaining ranges, in reverse order
+    ranges: Vec<(u64, u64)>,
+    block: u64,
+}
+
+impl Blocks {
+    fn new(mut ranges: Vec<(u64, u64)>, block: u64) -> Self {
+        ranges.reverse();
+        Self {
+            ranges,
+            block: cmp::max(block, 1),
+        }
+    }
+
+    fn next(&mut self) -> Option<(u64, u64)> {
+        let r = self.ranges.last_mut()?;
+        let lo = r.0;
+        let hi = cmp::min(lo.saturating_add(self.block), r.1);
+        if hi == r.1 {
+            self.ranges.pop();
+        } else {
+            r.0 = hi;
+        }
+
+        Some((lo, hi))
+    }
+
+    fn clear(&mut self) {
+        self.ranges.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_done_ranges() {
+        let mut c = Checkpoint::new("test".to_string());
+        c.add_done(10, 20, vec![]);
+        c.add_done(30, 40, vec![]);
+        assert_eq!(c.remaining(50), vec![(0, 10), (20, 30), (40, 50)]);
+        c.add_done(20, 30, vec![25]);
+        assert_eq!(c.done, vec![(10, 40)]);
+        c.add_done(0, 5, vec![]);
+        assert_eq!(c.remaining(50), vec![(5, 10), (40, 50)]);
+        assert_eq!(c.remaining(8), vec![(5, 8)]);
+        assert_eq!(c.done_count(), 35);
+        assert_eq!(c.found, vec![25]);
+    }
+
+    #[test]
+    fn run_range_only_remaining() {
+        let mut c = Checkpointer::in_memory("test".to_string());
+        c.state.add_done(0, 50, vec![7]);
+        let tried = Mutex::new(vec![]);
+        let found = c
+            .run_range(3, 100, 7, |lo, hi| {
+                tried.lock().unwrap().push((lo, hi));
+                (lo..hi).filter(|x| x % 10 == 7).collect()
+            })
+            .unwrap();
+        assert_eq!(found, vec![7, 57, 67, 77, 87, 97]);
+        let mut tried = tried.into_inner().unwrap();
+        tried.sort_unstable();
+        assert_eq!(tried[0], (50, 57));
+        assert_eq!(tried.last(), Some(&(99, 100)));
+        assert_eq!(c.state.done, vec![(0, 100)]);
+    }
+
+    #[test]
+    fn run_slice_all_candidates() {
+        let candidates: Vec<u64> = (100..200).collect();
+        let mut c = Checkpointer::in_memory("test".to_string());
+        let found = c
+            .run_slice(1, &candidates, 10, |seeds| {
+                seeds.iter().cloned().filter(|&s| s % 50 == 0).collect()
+            })
+            .unwrap();
+        assert_eq!(found, vec![100, 150]);
+        assert_eq!(c.state.remaining(100), vec![]);
+    }
+
+    #[test]
+    fn run_range_until_found_stops() {
+        let mut c = Checkpointer::in_memory("test".to_string());
+        let found = c
+            .run_range_until_found(1, 100, 10, |lo, hi| (lo..hi).filter(|&x| x == 42).collect())
+            .unwrap();
+        assert_eq!(found, vec![42]);
+        assert_eq!(c.state.remaining(100), vec![(50, 100)]);
+    }
+
+    #[test]
+    fn save_and_resume() {
+        let path = std::env::temp_dir().join("slime_seed_finder_checkpoint_test.json");
+        let mut c = Checkpointer::create(path.clone(), "test".to_string());
+        c.state.add_done(0, 10, vec![3]);
+        c.next_step(vec![1, 2, 3]);
+        c.state.add_done(5, 10, vec![2]);
+        c.save().unwrap();
+
+        let r = Checkpointer::resume(path.clone(), "test".to_string()).unwrap();
+        assert_eq!(r.state, c.state);
+        assert!(Checkpointer::resume(path.clone(), "other".to_string()).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn files_are_only_created_on_interrupt() {
+        let dir = std::env::temp_dir().join(format!(
+            "slime_seed_finder_checkpoint_unique_test_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut a = Checkpointer::create_on_interrupt(&dir, "find", "a".to_string());
+        let mut b = Checkpointer::create_on_interrupt(&dir, "find", "b".to_string());
+        a.run_range(1, 10, 1, |_, _| vec![]).unwrap();
+        a.save().unwrap();
+        assert_eq!(a.path(), None);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        a.save_interrupted().unwrap();
+        b.save_interrupted().unwrap();
+        assert_eq!(a.path(), Some(dir.join("find.checkpoint.json").as_path()));
+        assert_eq!(b.path(), Some(dir.join("find.1.checkpoint.json").as_path()));
+        // The first file still belongs to the first search
+        assert!(Checkpointer::resume(dir.join("find.checkpoint.json"), "a".to_string()).is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
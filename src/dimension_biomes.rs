@@ -0,0 +1,449 @@
+//! Biome generation for the nether and the End.
+//!
+//! Both dimensions generate biomes at 1:4 scale, and the biome does not depend
+//! on the y coordinate. Starting from 1.15 the final biome map is obtained using
+//! the same voronoi zoom as in the overworld.
+use crate::biome_info::biome_id;
+use crate::biome_layers::draw_map_image;
+use crate::biome_layers::generate_up_to_layer;
+use crate::biome_layers::Area;
+use crate::biome_layers::Area3D;
+use crate::biome_layers::GetMap3D;
+use crate::biome_layers::Map;
+use crate::biome_layers::Map3D;
+use crate::biome_layers::MapQuarterToBlock;
+use crate::biome_layers::MapVoronoiZoom118;
+use crate::chunk::Point;
+use crate::java_rng::JavaRng;
+use crate::noise_generator::NoiseGeneratorDoublePerlin;
+use crate::noise_generator::NoiseGeneratorSimplex;
+use crate::seed_info::BiomeId;
+use crate::seed_info::Dimension;
+use crate::seed_info::MinecraftVersion;
+use log::debug;
+use std::rc::Rc;
+
+/// Number of layers of the nether and End generators: layer 0 is the 1:4
+/// biome map, and layer 1 is the full resolution biome map.
+pub const NUM_LAYERS: u32 = 1;
+
+/// Nether biome generator, at 1:4 scale.
+///
+/// Before 1.16 the nether only has one biome.
+pub struct MapGenNetherBiomes {
+    noise: Option<(NoiseGeneratorDoublePerlin, NoiseGeneratorDoublePerlin)>,
+}
+
+impl MapGenNetherBiomes {
+    pub fn new(world_seed: i64, version: MinecraftVersion) -> Self {
+        if version < MinecraftVersion::Java1_16_1 {
+            return Self { noise: None };
+        }
+
+        let amplitudes = [1.0, 1.0];
+        let mut r = JavaRng::with_seed(world_seed as u64);
+        let temperature = NoiseGeneratorDoublePerlin::new(&mut r, &amplitudes, -7);
+        let mut r = JavaRng::with_seed(world_seed.wrapping_add(1) as u64);
+        let humidity = NoiseGeneratorDoublePerlin::new(&mut r, &amplitudes, -7);
+
+        Self {
+            noise: Some((temperature, humidity)),
+        }
+    }
+
+    /// Biome at quarter-scale coordinates (x, z)
+    pub fn get_biome(&self, x: i64, z: i64) -> i32 {
+        let (temperature, humidity) = match &self.noise {
+            Some(x) => x,
+            None => return biome_id::hell,
+        };
+
+        // (temperature, humidity, offset^2, biome)
+        let points: [(f32, f32, f32, i32); 5] = [
+            (0.0, 0.0, 0.0, biome_id::hell),
+            (0.0, -0.5, 0.0, biome_id::soulSandValley),
+            (0.4, 0.0, 0.0, biome_id::crimsonForest),
+            (0.0, 0.5, 0.375 * 0.375, biome_id::warpedForest),
+            (-0.5, 0.0, 0.175 * 0.175, biome_id::basaltDeltas),
+        ];
+
+        let (x, z) = (x as f64, z as f64);
+        let t = temperature.sample(x, 0.0, z) as f32;
+        let h = humidity.sample(x, 0.0, z) as f32;
+
+        let mut best = biome_id::hell;
+        let mut best_distance = f32::INFINITY;
+        for (pt, ph, offset, biome) in points {
+            let distance = (pt - t) * (pt - t) + (ph - h) * (ph - h) + offset;
+            if distance < best_distance {
+                best_distance = distance;
+                best = biome;
+            }
+        }
+
+        best
+    }
+}
+
+impl GetMap3D for MapGenNetherBiomes {
+    fn get_map_3d(&self, area: Area3D) -> Map3D {
+        Map3D::from_area_fn(area, |(x, _y, z)| {
+            self.get_biome(area.x + x as i64, area.z + z as i64)
+        })
+    }
+
+    // MapGenNetherBiomes is the first layer, so it does not need pmap
+    fn get_map_from_pmap_3d(&self, pmap: &Map3D) -> Map3D {
+        self.get_map_3d(pmap.area())
+    }
+}
+
+/// End biome generator, at 1:4 scale.
+///
+/// Before 1.13 the End only has one biome.
+pub struct MapGenEndBiomes {
+    island_noise: Option<NoiseGeneratorSimplex>,
+}
+
+impl MapGenEndBiomes {
+    pub fn new(world_seed: i64, version: MinecraftVersion) -> Self {
+        if version < MinecraftVersion::Java1_13 {
+            return Self { island_noise: None };
+        }
+
+        let mut r = JavaRng::with_seed(world_seed as u64);
+        r.next_n_calls(17292);
+        let island_noise = NoiseGeneratorSimplex::with_rng(&mut r);
+
+        Self {
+            island_noise: Some(island_noise),
+        }
+    }
+
+    /// Biome at quarter-scale coordinates (x, z)
+    pub fn get_biome(&self, x: i64, z: i64) -> i32 {
+        let island_noise = match &self.island_noise {
+            Some(x) => x,
+            None => return biome_id::sky,
+        };
+
+        // The biome only depends on the chunk coordinates
+        let chunk_x = x >> 2;
+        let chunk_z = z >> 2;
+        if chunk_x * chunk_x + chunk_z * chunk_z <= 4096 {
+            return biome_id::sky;
+        }
+
+        let height = end_island_height(
+            island_noise,
+            (chunk_x as i32).wrapping_mul(2) + 1,
+            (chunk_z as i32).wrapping_mul(2) + 1,
+        );
+
+        if height > 40.0 {
+            biome_id::skyIslandHigh
+        } else if height >= 0.0 {
+            biome_id::skyIslandMedium
+        } else if height < -20.0 {
+            biome_id::skyIslandLow
+        } else {
+            biome_id::skyIslandBarren
+        }
+    }
+}
+
+impl GetMap3D for MapGenEndBiomes {
+    fn get_map_3d(&self, area: Area3D) -> Map3D {
+        Map3D::from_area_fn(area, |(x, _y, z)| {
+            self.get_biome(area.x + x as i64, area.z + z as i64)
+        })
+    }
+
+    // MapGenEndBiomes is the first layer, so it does not need pmap
+    fn get_map_from_pmap_3d(&self, pmap: &Map3D) -> Map3D {
+        self.get_map_3d(pmap.area())
+    }
+}
+
+/// Height of the End islands at (x, z), in units of 8 blocks.
+///
+/// All the operations use wrapping integers and f32, like in Java.
+fn end_island_height(island_noise: &NoiseGeneratorSimplex, x: i32, z: i32) -> f32 {
+    let hx = x / 2;
+    let hz = z / 2;
+    let ox = x % 2;
+    let oz = z % 2;
+    let d = x.wrapping_mul(x).wrapping_add(z.wrapping_mul(z));
+    let mut height = (100.0 - (d as f32).sqrt() * 8.0).clamp(-100.0, 80.0);
+
+    for i in -12..=12 {
+        for j in -12..=12 {
+            let k = (hx + i) as i64;
+            let l = (hz + j) as i64;
+            if k * k + l * l > 4096
+                && island_noise.sample_2d(k as f64, l as f64) < f64::from(-0.9f32)
+            {
+                let island_size =
+                    ((k as f32).abs() * 3439.0 + (l as f32).abs() * 147.0) % 13.0 + 9.0;
+                let dx = (ox - i * 2) as f32;
+                let dz = (oz - j * 2) as f32;
+                let island_height =
+                    (100.0 - (dx * dx + dz * dz).sqrt() * island_size).clamp(-100.0, 80.0);
+                height = height.max(island_height);
+            }
+        }
+    }
+
+    height
+}
+
+/// Biome generator for the nether or the End, up to `layer`.
+///
+/// Panics if the dimension is the overworld.
+pub fn generator_up_to_layer(
+    dimension: Dimension,
+    world_seed: i64,
+    layer: u32,
+    version: MinecraftVersion,
+) -> Box<dyn GetMap3D> {
+    let g0: Box<dyn GetMap3D> = match dimension {
+        Dimension::Nether => Box::new(MapGenNetherBiomes::new(world_seed, version)),
+        Dimension::End => Box::new(MapGenEndBiomes::new(world_seed, version)),
+        Dimension::Overworld => panic!("Use biome_layers to generate the overworld"),
+    };
+    if layer == 0 {
+        return g0;
+    }
+
+    if version >= MinecraftVersion::Java1_15 {
+        let mut g1 = MapVoronoiZoom118::new(world_seed);
+        g1.parent = Some(Rc::from(g0));
+        Box::new(g1)
+    } else {
+        Box::new(MapQuarterToBlock {
+            parent: Rc::from(g0),
+        })
+    }
+}
+
+/// Generate the biome map of any dimension. `num_layers` and `y_offset` have
+/// the same meaning as in `biome_layers::generate_up_to_layer`. The nether and
+/// End only have layers 0 (1:4 scale) and 1 (1:1 scale), any greater layer is
+/// treated as layer 1.
+pub fn generate_up_to_layer_dimension(
+    dimension: Dimension,
+    version: MinecraftVersion,
+    area: Area,
+    seed: i64,
+    num_layers: u32,
+    y_offset: u32,
+) -> Map {
+    if dimension == Dimension::Overworld {
+        return generate_up_to_layer(version, area, seed, num_layers, y_offset);
+    }
+
+    let y_level = y_offset as i64 - 16;
+    let area = Area3D::from_area2d_and_y_level(area, y_level);
+    let g = generator_up_to_layer(dimension, seed, num_layers.min(NUM_LAYERS), version);
+
+    g.get_map_3d(area).into_map2d()
+}
+
+pub fn generate_image_up_to_layer_dimension(
+    dimension: Dimension,
+    version: MinecraftVersion,
+    area: Area,
+    seed: i64,
+    num_layers: u32,
+    y_offset: u32,
+) -> Vec<u8> {
+    if dimension == Dimension::Overworld {
+        return crate::biome_layers::generate_image_up_to_layer(
+            version, area, seed, num_layers, y_offset,
+        );
+    }
+
+    let map = generate_up_to_layer_dimension(dimension, version, area, seed, num_layers, y_offset);
+
+    draw_map_image(&map)
+}
+
+/// Remove the candidates that do not match the nether or End biomes. At most 10%
+/// of the biomes can be wrong. The biomes are checked at y=64.
+pub fn filter_seeds_using_dimension_biomes(
+    candidates: &[i64],
+    extra_biomes: &[(BiomeId, Point)],
+    dimension: Dimension,
+    version: MinecraftVersion,
+) -> Vec<i64> {
+    let mut valid_seeds = vec![];
+    // Quarter-scale y coordinate of y=64
+    let y_level = 64 >> 2;
+
+    for world_seed in candidates {
+        let world_seed = *world_seed;
+        let g = generator_up_to_layer(dimension, world_seed, NUM_LAYERS, version);
+        let mut hits = 0;
+        let mut misses = 0;
+        let target = extra_biomes.len() * 90 / 100;
+        let max_misses = extra_biomes.len() - target;
+        for (biome, Point { x, z }) in extra_biomes.iter().cloned() {
+            let area = Area { x, z, w: 1, h: 1 };
+            let area = Area3D::from_area2d_and_y_level(area, y_level);
+            let m = g.get_map_3d(area);
+            if m.a[(0, 0, 0)] == biome.0 {
+                hits += 1;
+            } else {
+                misses += 1;
+                if misses > max_misses {
+                    break;
+                }
+            }
+        }
+
+        if hits >= target {
+            debug!("{:016X}: {}/{}", world_seed, hits, extra_biomes.len());
+            valid_seeds.push(world_seed);
+        }
+    }
+
+    valid_seeds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nether_before_1_16_is_nether_wastes() {
+        let area = Area {
+            x: 0,
+            z: 0,
+            w: 64,
+            h: 64,
+        };
+        let map = generate_up_to_layer_dimension(
+            Dimension::Nether,
+            MinecraftVersion::Java1_15,
+            area,
+            1234,
+            1,
+            32,
+        );
+        assert!(map.a.iter().all(|b| *b == biome_id::hell));
+    }
+
+    #[test]
+    fn nether_biomes_1_16() {
+        let area = Area {
+            x: -2000,
+            z: -2000,
+            w: 1000,
+            h: 1000,
+        };
+        let map = generate_up_to_layer_dimension(
+            Dimension::Nether,
+            MinecraftVersion::Java1_16,
+            area,
+            1234,
+            0,
+            32,
+        );
+        // A big enough area has all the nether biomes
+        for biome in [
+            biome_id::hell,
+            biome_id::soulSandValley,
+            biome_id::crimsonForest,
+            biome_id::warpedForest,
+            biome_id::basaltDeltas,
+        ] {
+            assert!(map.a.iter().any(|b| *b == biome), "{} not found", biome);
+        }
+    }
+
+    #[test]
+    fn nether_biomes_1_16_1() {
+        // 1.16 and 1.16.1 already have the nether biomes, generated in the same way as in 1.16.2
+        let version: MinecraftVersion = "1.16.1".parse().unwrap();
+        assert_eq!(version, MinecraftVersion::Java1_16_1);
+        let area = Area {
+            x: -2000,
+            z: -2000,
+            w: 1000,
+            h: 1000,
+        };
+        let map = generate_up_to_layer_dimension(Dimension::Nether, version, area, 1234, 0, 32);
+        assert!(map.a.iter().any(|b| *b == biome_id::crimsonForest));
+        assert!(map.a.iter().any(|b| *b == biome_id::basaltDeltas));
+        let map_1_16_2 = generate_up_to_layer_dimension(
+            Dimension::Nether,
+            MinecraftVersion::Java1_16,
+            area,
+            1234,
+            0,
+            32,
+        );
+        assert_eq!(map, map_1_16_2);
+    }
+
+    #[test]
+    fn end_main_island() {
+        let g = MapGenEndBiomes::new(1234, MinecraftVersion::Java1_16);
+        // The main island is always "The End"
+        assert_eq!(g.get_biome(0, 0), biome_id::sky);
+        assert_eq!(g.get_biome(255, 0), biome_id::sky);
+        // The End biome is only used inside a radius of 64 chunks
+        assert_ne!(g.get_biome(260, 0), biome_id::sky);
+    }
+
+    #[test]
+    fn end_outer_islands() {
+        let g = MapGenEndBiomes::new(1234, MinecraftVersion::Java1_16);
+        let area = Area3D {
+            x: 1000,
+            y: 0,
+            z: 1000,
+            sx: 400,
+            sy: 1,
+            sz: 400,
+        };
+        let map = g.get_map_3d(area);
+        for biome in [
+            biome_id::skyIslandLow,
+            biome_id::skyIslandMedium,
+            biome_id::skyIslandHigh,
+            biome_id::skyIslandBarren,
+        ] {
+            assert!(map.a.iter().any(|b| *b == biome), "{} not found", biome);
+        }
+    }
+
+    #[test]
+    fn filter_end_biomes() {
+        let version = MinecraftVersion::Java1_16;
+        let seed = 1234;
+        let g = generator_up_to_layer(Dimension::End, seed, NUM_LAYERS, version);
+        let extra_biomes: Vec<_> = (0..20)
+            .map(|i| {
+                let (x, z) = (2000 + i * 100, 1000 - i * 70);
+                let area = Area3D {
+                    x,
+                    y: 16,
+                    z,
+                    sx: 1,
+                    sy: 1,
+                    sz: 1,
+                };
+                (BiomeId(g.get_map_3d(area).a[(0, 0, 0)]), Point { x, z })
+            })
+            .collect();
+        let candidates = [seed, seed + 1, seed + 2];
+        let valid = filter_seeds_using_dimension_biomes(
+            &candidates,
+            &extra_biomes,
+            Dimension::End,
+            version,
+        );
+        assert_eq!(valid, vec![seed]);
+    }
+}
@@ -0,0 +1,581 @@
+//! Split a bruteforce search between many worker processes, possibly running
+//! on different machines.
+//!
+//! The coordinator (`serve`) splits the range of the search into work units
+//! and hands them out to the workers (`work`) over TCP. The protocol is one
+//! JSON message per line: the worker sends a `Request` and the coordinator
+//! answers with a `Response`. Work units that are not finished before a
+//! timeout are given to another worker, so workers can be stopped at any
+//! time. The progress is stored in a `Checkpointer`, so the coordinator can
+//! also be stopped and resumed.
+use crate::biome_info::biome_id;
+use crate::biome_layers;
+use crate::checkpoint;
+use crate::checkpoint::Checkpointer;
+use crate::find;
+use crate::population;
+use crate::seed_info::BiomeId;
+use crate::seed_info::SeedInfo;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Function that searches the range `lo..hi` of a job
+pub type RangeFn<'a> = Box<dyn Fn(u64, u64) -> Vec<u64> + Sync + 'a>;
+
+/// A search that can be split into ranges
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Job {
+    /// Find the lower 48 bits using slime chunks
+    SlimeChunks { seed_info: SeedInfo },
+    /// Find 64-bit seeds using full resolution rivers and biomes
+    Rivers { seed_info: SeedInfo },
+    /// Bruteforce the upper 38 bits of 26-bit candidates using the seed hash
+    #[serde(rename_all = "camelCase")]
+    SeedHash {
+        seed_hash: i64,
+        not_from_java_next_long: bool,
+        candidates: Vec<u64>,
+    },
+    /// Find the world seed from 3 dungeon seeds, as
+    /// `(dungeon_seed, chunk_x, chunk_z, limit_steps_back)`
+    Dungeon {
+        dungeon_seeds: [(u64, i32, i32, u32); 3],
+    },
+}
+
+impl Job {
+    /// Size of the range of this search
+    pub fn total(&self) -> u64 {
+        match self {
+            Job::SlimeChunks { .. } => 1 << (48 - 18),
+            Job::Rivers { .. } => 1 << 24,
+            Job::SeedHash { .. } => 1 << 32,
+            Job::Dungeon { dungeon_seeds } => {
+                u64::from(dungeon_seeds.iter().map(|d| d.3).max().unwrap_or(0))
+            }
+        }
+    }
+
+    /// Default size of a work unit. Each work unit should take a few seconds.
+    pub fn default_block(&self) -> u64 {
+        match self {
+            Job::SlimeChunks { .. } => 1 << 16,
+            Job::Rivers { .. } => 1 << 10,
+            Job::SeedHash { .. } => 1 << 14,
+            Job::Dungeon { .. } => 1,
+        }
+    }
+
+    /// Searches that only have one solution can stop as soon as it is found
+    pub fn stop_when_found(&self) -> bool {
+        matches!(self, Job::SeedHash { .. } | Job::Dungeon { .. })
+    }
+
+    /// The function that searches one range of this job
+    pub fn range_fn(&self) -> Result<RangeFn<'_>, String> {
+        Ok(match self {
+            Job::SlimeChunks { seed_info } => {
+                let sc = find::slime_chunks(seed_info);
+                Box::new(move |lo, hi| sc.find_seed_range(lo as u32, (hi - lo) as u32))
+            }
+            Job::Rivers { seed_info } => {
+                let version = seed_info.version()?;
+                let river = BiomeId(biome_id::river);
+                let rivers = seed_info
+                    .biomes
+                    .get(&river)
+                    .ok_or_else(|| "No rivers in seedInfo".to_string())?;
+                let extra_biomes: Vec<_> = seed_info
+                    .biomes
+                    .iter()
+                    .filter(|(id, _)| **id != river)
+                    .flat_map(|(id, vec_xz)| vec_xz.iter().map(move |p| (*id, *p)))
+                    .collect();
+                Box::new(move |lo, hi| {
+                    biome_layers::river_seed_finder_range(
+                        rivers,
+                        &extra_biomes,
+                        version,
+                        lo as u32,
+                        hi as u32,
+                    )
+                    .into_iter()
+                    .map(|s| s as u64)
+                    .collect()
+                })
+            }
+            Job::SeedHash {
+                seed_hash,
+                not_from_java_next_long,
+                candidates,
+            } => Box::new(move |lo, hi| {
+                let r = if *not_from_java_next_long {
+                    biome_layers::seed_hash_bruteforce_26_range(
+                        *seed_hash,
+                        candidates,
+                        lo as u32,
+                        (hi - 1) as u32,
+                    )
+                } else {
+                    biome_layers::seed_hash_bruteforce_26_java_range(
+                        *seed_hash,
+                        candidates,
+                        lo as u32,
+                        (hi - 1) as u32,
+                    )
+                };
+                r.into_iter().map(|s| s as u64).collect()
+            }),
+            Job::Dungeon { dungeon_seeds } => {
+                let [i1, i2, i3] = *dungeon_seeds;
+                Box::new(move |lo, hi| {
+                    (lo..hi)
+                        .flat_map(|step| {
+                            population::dungeon_seed_to_world_seed_any_version_step(
+                                i1,
+                                i2,
+                                i3,
+                                step as u32,
+                            )
+                        })
+                        .map(|s| s as u64)
+                        .collect()
+                })
+            }
+        })
+    }
+}
+
+/// Message from the worker to the coordinator
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Request {
+    GetJob,
+    GetWork,
+    Submit { id: u64, found: Vec<u64> },
+}
+
+/// Message from the coordinator to the worker
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Response {
+    Job {
+        job: Box<Job>,
+    },
+    Work {
+        id: u64,
+        lo: u64,
+        hi: u64,
+    },
+    /// All the remaining work is assigned to other workers, ask again later
+    Wait {
+        seconds: u64,
+    },
+    /// The search is finished
+    Done,
+    Ok,
+}
+
+/// A work unit that has been sent to a worker
+#[derive(Copy, Clone, Debug)]
+struct Assigned {
+    lo: u64,
+    hi: u64,
+    deadline: Instant,
+}
+
+/// Keeps track of the work units of one job
+pub struct Coordinator {
+    pub job: Job,
+    pub ckpt: Checkpointer,
+    block: u64,
+    timeout: Duration,
+    assigned: HashMap<u64, Assigned>,
+    next_id: u64,
+}
+
+impl Coordinator {
+    /// Work units not submitted after `timeout` will be assigned to another
+    /// worker
+    pub fn new(job: Job, ckpt: Checkpointer, block: u64, timeout: Duration) -> Self {
+        Self {
+            job,
+            ckpt,
+            block: std::cmp::max(block, 1),
+            timeout,
+            assigned: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        let total = self.job.total();
+        self.ckpt.state.remaining(total).is_empty()
+            || (self.job.stop_when_found() && !self.ckpt.state.found.is_empty())
+    }
+
+    pub fn handle(&mut self, request: Request, now: Instant) -> Response {
+        match request {
+            Request::GetJob => Response::Job {
+                job: Box::new(self.job.clone()),
+            },
+            Request::GetWork => self.get_work(now),
+            Request::Submit { id, found } => {
+                self.submit(id, found);
+                Response::Ok
+            }
+        }
+    }
+
+    fn get_work(&mut self, now: Instant) -> Response {
+        if self.is_finished() {
+            return Response::Done;
+        }
+        match self.next_free_range(now) {
+            Some((lo, hi)) => {
+                let id = self.next_id;
+                self.next_id += 1;
+                let deadline = now + self.timeout;
+                self.assigned.insert(id, Assigned { lo, hi, deadline });
+                debug!("Assigned work unit {} from {:X} to {:X}", id, lo, hi);
+                Response::Work { id, lo, hi }
+            }
+            None => {
+                let wait = self
+                    .assigned
+                    .values()
+                    .map(|a| a.deadline.saturating_duration_since(now))
+                    .min()
+                    .unwrap_or_default();
+                Response::Wait {
+                    seconds: wait.as_secs().clamp(1, 10),
+                }
+            }
+        }
+    }
+
+    /// First block of the remaining ranges that is not assigned to a worker.
+    /// Work units that have timed out are considered free.
+    fn next_free_range(&self, now: Instant) -> Option<(u64, u64)> {
+        let mut busy: Vec<(u64, u64)> = self
+            .assigned
+            .values()
+            .filter(|a| a.deadline > now)
+            .map(|a| (a.lo, a.hi))
+            .collect();
+        busy.sort_unstable();
+
+        for (lo, hi) in self.ckpt.state.remaining(self.job.total()) {
+            let mut cursor = lo;
+            for &(busy_lo, busy_hi) in &busy {
+                if busy_hi <= cursor {
+                    continue;
+                }
+                if busy_lo >= hi {
+                    break;
+                }
+                if busy_lo > cursor {
+                    return Some((cursor, std::cmp::min(busy_lo, cursor + self.block)));
+                }
+                cursor = busy_hi;
+            }
+            if cursor < hi {
+                return Some((cursor, std::cmp::min(hi, cursor + self.block)));
+            }
+        }
+
+        None
+    }
+
+    fn submit(&mut self, id: u64, found: Vec<u64>) {
+        let a = match self.assigned.remove(&id) {
+            Some(a) => a,
+            None => {
+                warn!("Unknown work unit {}", id);
+                return;
+            }
+        };
+        let total = self.job.total();
+        let already_done = self
+            .ckpt
+            .state
+            .remaining(total)
+            .iter()
+            .all(|&(lo, hi)| hi <= a.lo || lo >= a.hi);
+        if already_done {
+            // This work unit timed out and was finished by another worker
+            return;
+        }
+        if !found.is_empty() {
+            info!("Found {} candidates in work unit {}", found.len(), id);
+        }
+        self.ckpt.state.add_done(a.lo, a.hi, found);
+        self.ckpt.autosave();
+    }
+}
+
+fn send<T: Serialize>(w: &mut impl Write, msg: &T) -> io::Result<()> {
+    let mut buf = serde_json::to_vec(msg)?;
+    buf.push(b'\n');
+    w.write_all(&buf)?;
+    w.flush()
+}
+
+fn recv<T: for<'de> Deserialize<'de>>(r: &mut impl BufRead) -> io::Result<Option<T>> {
+    let mut line = String::new();
+    if r.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+/// Answer the requests of one worker
+fn handle_connection(stream: TcpStream, coordinator: &Mutex<Coordinator>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    while let Some(request) = recv(&mut reader)? {
+        let response = coordinator.lock().unwrap().handle(request, Instant::now());
+        send(&mut writer, &response)?;
+    }
+
+    Ok(())
+}
+
+/// Accept workers until the search is finished or interrupted, and return the
+/// candidates found. Each worker connection is handled in a different thread.
+pub fn serve(
+    listener: TcpListener,
+    coordinator: Arc<Mutex<Coordinator>>,
+) -> Result<Vec<u64>, checkpoint::Interrupted> {
+    {
+        let coordinator = Arc::clone(&coordinator);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(x) => x,
+                    Err(e) => {
+                        warn!("Error accepting connection: {}", e);
+                        continue;
+                    }
+                };
+                let coordinator = Arc::clone(&coordinator);
+                thread::spawn(move || {
+                    let peer = stream.peer_addr();
+                    if let Err(e) = handle_connection(stream, &coordinator) {
+                        warn!("Connection with worker {:?} closed: {}", peer, e);
+                    }
+                });
+            }
+        });
+    }
+
+    loop {
+        {
+            let mut c = coordinator.lock().unwrap();
+            if c.is_finished() {
+                if let Err(e) = c.ckpt.save() {
+                    error!("Error saving checkpoint: {}", e);
+                }
+                let mut found = c.ckpt.state.found.clone();
+                found.sort_unstable();
+                return Ok(found);
+            }
+            if checkpoint::interrupted() {
+                if let Err(e) = c.ckpt.save_interrupted() {
+                    error!("Error saving checkpoint: {}", e);
+                }
+                return Err(checkpoint::Interrupted);
+            }
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Connect to the coordinator at `addr` and process work units using
+/// `num_threads` threads, until the search is finished. Returns the number of
+/// work units processed.
+pub fn work<A: ToSocketAddrs>(addr: A, num_threads: usize) -> io::Result<u64> {
+    let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
+    let connect = || -> io::Result<(BufReader<TcpStream>, TcpStream)> {
+        let stream = TcpStream::connect(&addrs[..])?;
+        Ok((BufReader::new(stream.try_clone()?), stream))
+    };
+    let (mut reader, mut writer) = connect()?;
+    send(&mut writer, &Request::GetJob)?;
+    let job = match recv(&mut reader)? {
+        Some(Response::Job { job }) => job,
+        x => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Expected job, got {:?}", x),
+            ))
+        }
+    };
+    drop((reader, writer));
+    let f = job
+        .range_fn()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    info!("Received job {:?}", job);
+
+    let work_thread = || -> io::Result<u64> {
+        let (mut reader, mut writer) = connect()?;
+        let mut units = 0;
+        loop {
+            if checkpoint::interrupted() {
+                return Ok(units);
+            }
+            send(&mut writer, &Request::GetWork)?;
+            match recv(&mut reader)? {
+                Some(Response::Work { id, lo, hi }) => {
+                    let found = f(lo, hi);
+                    send(&mut writer, &Request::Submit { id, found })?;
+                    recv::<Response>(&mut reader)?;
+                    units += 1;
+                }
+                Some(Response::Wait { seconds }) => {
+                    thread::sleep(Duration::from_secs(seconds));
+                }
+                // The coordinator exits when the search is finished
+                Some(Response::Done) | None => return Ok(units),
+                Some(x) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unexpected response {:?}", x),
+                    ))
+                }
+            }
+        }
+    };
+
+    if num_threads <= 1 {
+        return work_thread();
+    }
+    thread::scope(|s| {
+        let handles: Vec<_> = (0..num_threads).map(|_| s.spawn(work_thread)).collect();
+        let mut units = 0;
+        for h in handles {
+            units += h.join().unwrap()?;
+        }
+        Ok(units)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed_hash_job(seed: i64) -> Job {
+        Job::SeedHash {
+            seed_hash: biome_layers::sha256_long_to_long(seed),
+            not_from_java_next_long: true,
+            candidates: vec![seed as u64 & ((1 << 26) - 1)],
+        }
+    }
+
+    #[test]
+    fn reassign_after_timeout() {
+        let job = seed_hash_job(1234);
+        let ckpt = Checkpointer::in_memory(String::new());
+        let timeout = Duration::from_secs(60);
+        let mut c = Coordinator::new(job, ckpt, 10, timeout);
+        let t0 = Instant::now();
+        assert_eq!(
+            c.handle(Request::GetWork, t0),
+            Response::Work {
+                id: 0,
+                lo: 0,
+                hi: 10
+            }
+        );
+        assert_eq!(
+            c.handle(Request::GetWork, t0),
+            Response::Work {
+                id: 1,
+                lo: 10,
+                hi: 20
+            }
+        );
+        assert_eq!(
+            c.handle(
+                Request::Submit {
+                    id: 1,
+                    found: vec![]
+                },
+                t0
+            ),
+            Response::Ok
+        );
+        assert_eq!(
+            c.handle(Request::GetWork, t0),
+            Response::Work {
+                id: 2,
+                lo: 20,
+                hi: 30
+            }
+        );
+        // Work unit 0 timed out
+        let t1 = t0 + timeout + Duration::from_secs(1);
+        assert_eq!(
+            c.handle(Request::GetWork, t1),
+            Response::Work {
+                id: 3,
+                lo: 0,
+                hi: 10
+            }
+        );
+        c.handle(
+            Request::Submit {
+                id: 3,
+                found: vec![],
+            },
+            t1,
+        );
+        // The late result of work unit 0 is ignored
+        c.handle(
+            Request::Submit {
+                id: 0,
+                found: vec![5],
+            },
+            t1,
+        );
+        assert!(c.ckpt.state.found.is_empty());
+        assert_eq!(c.ckpt.state.done, vec![(0, 20)]);
+
+        c.handle(
+            Request::Submit {
+                id: 2,
+                found: vec![25],
+            },
+            t1,
+        );
+        assert!(c.is_finished());
+        assert_eq!(c.handle(Request::GetWork, t1), Response::Done);
+    }
+
+    #[test]
+    fn serve_and_work_locally() {
+        let seed = (3 << 32) | (5 << 26) | 1234;
+        let job = seed_hash_job(seed);
+        let ckpt = Checkpointer::in_memory(String::new());
+        let c = Coordinator::new(job, ckpt, 1, Duration::from_secs(60));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let workers: Vec<_> = (0..2)
+            .map(|_| thread::spawn(move || work(addr, 2)))
+            .collect();
+        let found = serve(listener, Arc::new(Mutex::new(c))).unwrap();
+        assert_eq!(found, vec![seed as u64]);
+        for w in workers {
+            w.join().unwrap().unwrap();
+        }
+    }
+}
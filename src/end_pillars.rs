@@ -0,0 +1,167 @@
+//! End spikes, also known as end pillars or obsidian towers.
+//!
+//! Starting from Minecraft 1.9, the height of the ten end pillars depends on a
+//! 16-bit seed derived from the world seed:
+//!
+//! ```java
+//! Random random = new Random(worldSeed);
+//! long pillarSeed = random.nextLong() & 65535L;
+//! List<Integer> list = range(0, 10);
+//! Collections.shuffle(list, new Random(pillarSeed));
+//! ```
+//!
+//! The pillar seed only depends on the lower 32 bits of the world seed, so
+//! each pillar seed corresponds to 2^16 possible values of the lower 32 bits.
+use crate::candidates::LowBitsCandidates;
+use crate::java_rng::mask;
+use crate::java_rng::JavaRng;
+use crate::seed_info::MinecraftVersion;
+use std::f64::consts::PI;
+
+pub const NUM_PILLARS: usize = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EndPillar {
+    /// Block coordinates of the center of the pillar
+    pub x: i32,
+    pub z: i32,
+    pub radius: u8,
+    /// Y coordinate of the top of the pillar
+    pub height: u8,
+    /// The end crystal on top of this pillar is surrounded by iron bars
+    pub guarded: bool,
+}
+
+/// The 16-bit seed used to shuffle the end pillars
+pub fn pillar_seed(world_seed: i64) -> u16 {
+    let mut r = JavaRng::with_seed(world_seed as u64);
+
+    (r.next_long() & 0xFFFF) as u16
+}
+
+/// Returns None if the version does not have deterministic end pillars
+pub fn end_pillars(world_seed: i64, version: MinecraftVersion) -> Option<[EndPillar; NUM_PILLARS]> {
+    if version < MinecraftVersion::Java1_9 {
+        return None;
+    }
+
+    Some(end_pillars_from_pillar_seed(pillar_seed(world_seed)))
+}
+
+pub fn end_pillars_from_pillar_seed(pillar_seed: u16) -> [EndPillar; NUM_PILLARS] {
+    let sizes = shuffled_sizes(pillar_seed);
+    let mut pillars = [EndPillar {
+        x: 0,
+        z: 0,
+        radius: 0,
+        height: 0,
+        guarded: false,
+    }; NUM_PILLARS];
+
+    for (i, (p, size)) in pillars.iter_mut().zip(sizes.iter()).enumerate() {
+        let angle = 2.0 * (-PI + (PI / 10.0) * i as f64);
+        p.x = (42.0 * angle.cos()).floor() as i32;
+        p.z = (42.0 * angle.sin()).floor() as i32;
+        p.radius = 2 + size / 3;
+        p.height = 76 + size * 3;
+        p.guarded = *size == 1 || *size == 2;
+    }
+
+    pillars
+}
+
+/// Java `Collections.shuffle(list, new Random(pillarSeed))` of the list 0..10
+fn shuffled_sizes(pillar_seed: u16) -> [u8; NUM_PILLARS] {
+    let mut sizes = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+    let mut r = JavaRng::with_seed(u64::from(pillar_seed));
+    for i in (2..=NUM_PILLARS).rev() {
+        let j = r.next_int_n(i as i32) as usize;
+        sizes.swap(i - 1, j);
+    }
+
+    sizes
+}
+
+/// Find all the pillar seeds that generate these pillar heights. The heights
+/// must be in the same order as returned by `end_pillars`.
+pub fn pillar_seeds_from_heights(heights: &[u8]) -> Vec<u16> {
+    if heights.len() != NUM_PILLARS {
+        return vec![];
+    }
+
+    (0..=u16::max_value())
+        .filter(|&p| {
+            let sizes = shuffled_sizes(p);
+            sizes
+                .iter()
+                .zip(heights.iter())
+                .all(|(size, height)| 76 + size * 3 == *height)
+        })
+        .collect()
+}
+
+/// All the possible values of the lower 32 bits of the world seed that result
+/// in this pillar seed. Always returns 2^16 values.
+pub fn low_32_bits_from_pillar_seed(pillar_seed: u16) -> Vec<u64> {
+    // The pillar seed is equal to bits [16, 31] of the internal state after 2
+    // calls to next(), so we can just try all the possible values of bits
+    // [0, 15] and go back 2 steps.
+    (0..(1u64 << 16))
+        .map(|lo| {
+            let state = (u64::from(pillar_seed) << 16) | lo;
+            let state = JavaRng::previous_state(JavaRng::previous_state(state));
+            let r = JavaRng::with_raw_seed(state);
+
+            r.get_seed() & mask(32)
+        })
+        .collect()
+}
+
+/// Use the height of the end pillars to find the lower 32 bits of the seed.
+/// The result has 2^16 candidates for each pillar seed.
+pub fn low_bits_candidates_from_heights(heights: &[u8]) -> LowBitsCandidates {
+    let patterns = pillar_seeds_from_heights(heights)
+        .into_iter()
+        .flat_map(low_32_bits_from_pillar_seed)
+        .collect();
+
+    LowBitsCandidates::new(32, patterns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pillar_seed_roundtrip() {
+        let world_seed = 1234;
+        let p = pillar_seed(world_seed);
+        let heights: Vec<u8> = end_pillars(world_seed, MinecraftVersion::Java1_16)
+            .unwrap()
+            .iter()
+            .map(|p| p.height)
+            .collect();
+        assert!(pillar_seeds_from_heights(&heights).contains(&p));
+
+        let low32 = low_32_bits_from_pillar_seed(p);
+        assert_eq!(low32.len(), 1 << 16);
+        assert!(low32.contains(&((world_seed as u64) & mask(32))));
+        for x in low32.iter().take(100) {
+            assert_eq!(pillar_seed(*x as i64), p);
+        }
+    }
+
+    #[test]
+    fn pillar_shape() {
+        let pillars = end_pillars(0, MinecraftVersion::Java1_13).unwrap();
+        let mut heights: Vec<u8> = pillars.iter().map(|p| p.height).collect();
+        heights.sort();
+        assert_eq!(heights, vec![76, 79, 82, 85, 88, 91, 94, 97, 100, 103]);
+        assert_eq!(pillars.iter().filter(|p| p.guarded).count(), 2);
+        for p in &pillars {
+            assert_eq!(p.guarded, p.height == 79 || p.height == 82);
+        }
+        assert_eq!((pillars[0].x, pillars[0].z), (42, 0));
+        assert_eq!(end_pillars(0, MinecraftVersion::Java1_7), None);
+    }
+}
@@ -46,6 +46,14 @@ impl CompoundTag {
         }
     }
 
+    pub fn get_i8(&self, name: &str) -> Result<i8, CompoundTagError> {
+        match self.m.get(name) {
+            Some(Value::Byte(x)) => Ok(*x),
+            Some(_) => Err(CompoundTagError::TagWrongType),
+            None => Err(CompoundTagError::TagNotFound),
+        }
+    }
+
     pub fn get_i32(&self, name: &str) -> Result<i32, CompoundTagError> {
         match self.m.get(name) {
             Some(Value::Int(x)) => Ok(*x),
@@ -119,3 +127,30 @@ pub fn read_gzip_compound_tag<R: Read>(reader: &mut R) -> Result<CompoundTag, Ta
 
     Ok(tag)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_i8() {
+        // Compound tag with a byte "a" = -5 and an int "b" = 7
+        let data = [
+            10, 0, 0, //
+            1, 0, 1, b'a', 0xFB, //
+            3, 0, 1, b'b', 0, 0, 0, 7, //
+            0,
+        ];
+        let tag = CompoundTag::from_bytes(&data).unwrap();
+        assert_eq!(tag.get_i8("a").unwrap(), -5);
+        assert_eq!(tag.get_i32("b").unwrap(), 7);
+        assert!(matches!(
+            tag.get_i8("b"),
+            Err(CompoundTagError::TagWrongType)
+        ));
+        assert!(matches!(
+            tag.get_i8("c"),
+            Err(CompoundTagError::TagNotFound)
+        ));
+    }
+}
@@ -0,0 +1,1591 @@
+//! Find the world seed using all the information available in a `SeedInfo`.
+//!
+//! Each kind of clue can only recover some bits of the seed: slime chunks give
+//! the lower 48 bits, quarter-scale rivers give the lower 26 bits, and so on.
+//! This module chooses which bruteforce stages to run and in which order, and
+//! passes the candidates of each stage to the next one, until all the 64 bits
+//! of the seed are known. The order is chosen using the bruteforce graph from
+//! the `candidates` module, after measuring the cost of each stage.
+use crate::bedrock;
+use crate::biome_info::biome_id;
+use crate::biome_layers;
+use crate::biome_layers::Area;
+use crate::candidates::find_shortest_path;
+use crate::candidates::BruteforceStep;
+use crate::candidates::LowBitsCandidates;
+use crate::candidates::Plan;
+use crate::candidates::PlannedStep;
+use crate::checkpoint::Checkpointer;
+use crate::checkpoint::Interrupted;
+use crate::chunk::Point;
+use crate::dimension_biomes;
+use crate::end_pillars;
+use crate::java_rng::JavaRng;
+use crate::population;
+use crate::population::DungeonClue;
+use crate::population::MossyFloor;
+use crate::seed_info::BiomeId;
+use crate::seed_info::Dimension;
+use crate::seed_info::Edition;
+use crate::seed_info::MinecraftVersion;
+use crate::seed_info::SeedInfo;
+use crate::slime;
+use crate::slime::ChunkTransform;
+use crate::slime::SlimeChunks;
+use crate::slime::SlimeChunksPattern;
+use crate::slime::SlimeEvidenceScore;
+use crate::structures;
+use crate::structures::StructureChunks;
+use crate::structures::StructureKind;
+use crate::structures::StructurePlacement;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
+
+/// One step of the seed search.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Stage {
+    /// Find the lower 26 bits using the quarter-scale rivers
+    RiversQuarterScale26,
+    /// Find the lower 26 bits (34 bits before 1.15) using the rivers of the
+    /// first treasure map
+    TreasureMap,
+    /// Find the lower 32 bits using the height of the end pillars, or remove
+    /// candidates that do not match them
+    EndPillars32,
+    /// Find or extend candidates to 48 bits using slime chunks
+    SlimeChunks48,
+    /// Find or extend candidates to 48 bits using the weighted slime chunk
+    /// clues, keeping the seeds whose log-likelihood is close to the best
+    SlimeEvidence48,
+    /// Remove 48-bit candidates that do not match the buried treasures
+    BuriedTreasures48,
+    /// Find 48-bit seeds using region-based structures, or remove 48-bit
+    /// candidates that do not match them
+    Structures48,
+    /// Find 48-bit seeds using the positions of the dungeon spawners, or
+    /// remove 48-bit candidates that do not match them
+    Dungeons48,
+    /// Find 64-bit seeds using full resolution rivers and biomes
+    Rivers64,
+    /// Extend 48-bit candidates to 64 bits, assuming that the seed was
+    /// generated using `Random.nextLong()`
+    Extend48,
+    /// Try all the possible values of the upper 16 bits of 48-bit candidates
+    Upper16,
+    /// Bruteforce the upper 38 bits of 26-bit candidates using the seed hash,
+    /// or remove 64-bit seeds that do not match the seed hash
+    SeedHash64,
+    /// Bruteforce the upper 16 bits of 48-bit candidates using the dungeons,
+    /// or remove 64-bit seeds that do not match them, using the floor when
+    /// available. Used since 1.18, where the dungeons depend on all the 64
+    /// bits of the seed
+    Dungeons64,
+    /// Remove 64-bit seeds that do not match the biomes
+    Biomes64,
+    /// Remove 64-bit seeds that do not match the nether or End biomes
+    DimensionBiomes64,
+}
+
+impl Stage {
+    /// Number of known bits after running this stage
+    pub fn output_bits(&self, version: MinecraftVersion) -> u32 {
+        match self {
+            Stage::RiversQuarterScale26 => 26,
+            Stage::EndPillars32 => 32,
+            Stage::TreasureMap => {
+                if version < MinecraftVersion::Java1_15 {
+                    34
+                } else {
+                    26
+                }
+            }
+            Stage::SlimeChunks48
+            | Stage::SlimeEvidence48
+            | Stage::BuriedTreasures48
+            | Stage::Structures48
+            | Stage::Dungeons48 => 48,
+            Stage::Rivers64
+            | Stage::Extend48
+            | Stage::Upper16
+            | Stage::SeedHash64
+            | Stage::Dungeons64
+            | Stage::Biomes64
+            | Stage::DimensionBiomes64 => 64,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FindError {
+    InvalidVersion(String),
+    /// The SeedInfo does not have any clue that can be used to start the search
+    NotEnoughInformation,
+    /// The search finished but some bits of the seed are still unknown.
+    Incomplete(LowBitsCandidates),
+    /// The search was stopped, it can be continued using the checkpoint
+    Interrupted,
+    /// The checkpoint was created using a different plan
+    CheckpointMismatch,
+}
+
+impl From<Interrupted> for FindError {
+    fn from(_: Interrupted) -> Self {
+        FindError::Interrupted
+    }
+}
+
+impl fmt::Display for FindError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FindError::InvalidVersion(v) => write!(f, "Invalid version: {:?}", v),
+            FindError::NotEnoughInformation => write!(
+                f,
+                "Not enough information: need slime chunks, rivers or a treasure map"
+            ),
+            FindError::Incomplete(c) => write!(
+                f,
+                "Only the lower {} bits are known, found {} candidates",
+                c.num_known_bits,
+                c.len()
+            ),
+            FindError::Interrupted => write!(f, "Interrupted"),
+            FindError::CheckpointMismatch => {
+                write!(f, "The checkpoint was created using a different plan")
+            }
+        }
+    }
+}
+
+/// Typical number of candidates returned by the river seed finders. This is
+/// only used to estimate the cost of the next steps.
+const EXPECTED_CANDIDATES_26: f64 = 64.0;
+const EXPECTED_CANDIDATES_34: f64 = 8.0;
+
+/// All the steps that can be used with the clues from the SeedInfo, as
+/// `(stage, input_bits, output_bits)`.
+pub fn available_steps(seed_info: &SeedInfo) -> Result<Vec<(Stage, u8, u8)>, FindError> {
+    let version = seed_info.version().map_err(FindError::InvalidVersion)?;
+    let java = !seed_info.options.not_from_java_next_long;
+    let river = BiomeId(biome_id::river);
+    let has_rivers = seed_info.biomes.contains_key(&river);
+    let has_rivers_quarter_scale = seed_info.biomes_quarter_scale.contains_key(&river);
+    let has_slime_chunks =
+        !seed_info.positive.slime_chunks.is_empty() || !seed_info.negative.slime_chunks.is_empty();
+    let has_slime_evidence = !seed_info.slime_evidence.is_empty();
+    let has_buried_treasures = !seed_info.positive.buried_treasures.is_empty()
+        || !seed_info.negative.buried_treasures.is_empty();
+    let has_end_pillars = !seed_info.end_pillars.is_empty() && version >= MinecraftVersion::Java1_9;
+    let has_dungeons =
+        !seed_info.dungeons.is_empty() && population::dungeon_finder_supports(version);
+    let has_dungeons_64 = !seed_info.dungeons.is_empty() && version >= MinecraftVersion::Java1_18;
+    let has_seed_hash = seed_info.world_seed_hash.is_some();
+    let has_biomes = !seed_info.biomes.is_empty() || !seed_info.item_maps.is_empty();
+    let has_dimension_biomes =
+        !seed_info.nether_biomes.is_empty() || !seed_info.end_biomes.is_empty();
+
+    if seed_info.edition == Edition::Bedrock {
+        // Slime chunks and structures do not use the Java rng, and the seed is
+        // not generated using nextLong, so only the biomes can be used to
+        // filter 64-bit candidates
+        let mut steps = vec![];
+        if !seed_info.biomes.is_empty() {
+            steps.push((Stage::Biomes64, 64, 64));
+        }
+        return Ok(steps);
+    }
+
+    let mut steps = vec![];
+    let treasure_map_bits = Stage::TreasureMap.output_bits(version) as u8;
+
+    if has_rivers_quarter_scale {
+        steps.push((Stage::RiversQuarterScale26, 0, 26));
+    }
+    if !seed_info.treasure_maps.is_empty() && version >= MinecraftVersion::Java1_13 {
+        steps.push((Stage::TreasureMap, 0, treasure_map_bits));
+    }
+    if has_end_pillars {
+        steps.push((Stage::EndPillars32, 0, 32));
+        steps.push((Stage::EndPillars32, 48, 48));
+    }
+    if has_slime_chunks {
+        steps.push((Stage::SlimeChunks48, 0, 48));
+        steps.push((Stage::SlimeChunks48, 26, 48));
+        if treasure_map_bits != 26 {
+            steps.push((Stage::SlimeChunks48, treasure_map_bits, 48));
+        }
+        if has_end_pillars {
+            steps.push((Stage::SlimeChunks48, 32, 48));
+        }
+        steps.push((Stage::SlimeChunks48, 48, 48));
+    }
+    if has_slime_evidence {
+        steps.push((Stage::SlimeEvidence48, 0, 48));
+        steps.push((Stage::SlimeEvidence48, 26, 48));
+        if treasure_map_bits != 26 {
+            steps.push((Stage::SlimeEvidence48, treasure_map_bits, 48));
+        }
+        if has_end_pillars {
+            steps.push((Stage::SlimeEvidence48, 32, 48));
+        }
+        steps.push((Stage::SlimeEvidence48, 48, 48));
+        steps.push((Stage::SlimeEvidence48, 64, 64));
+    }
+    if has_buried_treasures {
+        steps.push((Stage::BuriedTreasures48, 48, 48));
+    }
+    let structures = StructureChunks::new(version, &seed_info.positive, &seed_info.negative);
+    if !structures.is_empty() {
+        if structures.has_positive() {
+            steps.push((Stage::Structures48, 0, 48));
+        }
+        steps.push((Stage::Structures48, 48, 48));
+    }
+    if has_dungeons {
+        steps.push((Stage::Dungeons48, 0, 48));
+        steps.push((Stage::Dungeons48, 48, 48));
+        steps.push((Stage::Dungeons48, 64, 64));
+    }
+    if has_rivers && version < MinecraftVersion::Java1_15 {
+        // The river seed finder already checks the other biomes
+        steps.push((Stage::Rivers64, 0, 64));
+    }
+    if java {
+        steps.push((Stage::Extend48, 48, 64));
+    } else if has_seed_hash || has_biomes || has_dimension_biomes {
+        // Without a 64-bit filter this would just return 2^16 seeds per candidate
+        steps.push((Stage::Upper16, 48, 64));
+    }
+    if has_seed_hash {
+        steps.push((Stage::SeedHash64, 26, 64));
+        steps.push((Stage::SeedHash64, 64, 64));
+    }
+    if has_dungeons_64 {
+        steps.push((Stage::Dungeons64, 48, 64));
+        steps.push((Stage::Dungeons64, 64, 64));
+    }
+    if has_biomes {
+        steps.push((Stage::Biomes64, 64, 64));
+    }
+    if has_dimension_biomes {
+        steps.push((Stage::DimensionBiomes64, 64, 64));
+    }
+
+    Ok(steps)
+}
+
+/// Measure the cost of each available step by running it for a short time,
+/// and find the fastest plan using the bruteforce graph.
+pub fn plan(
+    seed_info: &SeedInfo,
+    candidates: Option<&LowBitsCandidates>,
+    num_threads: usize,
+) -> Result<Plan<Stage>, FindError> {
+    let version = seed_info.version().map_err(FindError::InvalidVersion)?;
+    let steps: Vec<_> = available_steps(seed_info)?
+        .into_iter()
+        .map(|(stage, input_bits, output_bits)| {
+            let mut step = estimate_step(seed_info, version, stage, input_bits, output_bits);
+            // All the slow steps are multithreaded
+            step.cost /= std::cmp::max(num_threads, 1) as f64;
+            debug!("{:?}", step);
+            step
+        })
+        .collect();
+
+    let plan = match candidates {
+        Some(c) => find_shortest_path(&steps, c.num_known_bits, c.len() as f64),
+        None => find_shortest_path(&steps, 0, 1.0),
+    };
+
+    if plan.output_bits() == 0 {
+        return Err(FindError::NotEnoughInformation);
+    }
+
+    Ok(plan)
+}
+
+/// Find all the seeds that match the SeedInfo.
+///
+/// `candidates` are optional initial candidates, so instead of bruteforcing
+/// all the possible seeds only these ones are tried.
+/// If not all the 64 bits can be found, returns `FindError::Incomplete` with
+/// the partial results.
+pub fn find_seeds(
+    seed_info: &SeedInfo,
+    candidates: Option<LowBitsCandidates>,
+    num_threads: usize,
+) -> Result<Vec<i64>, FindError> {
+    let plan = plan(seed_info, candidates.as_ref(), num_threads)?;
+    info!("Bruteforce plan:\n{}", plan);
+
+    run_plan(seed_info, &plan, candidates, num_threads)
+}
+
+/// Run all the steps of the plan, passing the candidates from one step to the
+/// next one.
+pub fn run_plan(
+    seed_info: &SeedInfo,
+    plan: &Plan<Stage>,
+    candidates: Option<LowBitsCandidates>,
+    num_threads: usize,
+) -> Result<Vec<i64>, FindError> {
+    let mut ckpt = Checkpointer::in_memory(String::new());
+    run_plan_with_checkpoint(seed_info, plan, candidates, num_threads, &mut ckpt)
+}
+
+/// The steps of a plan, as stored in the checkpoint
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedPlan {
+    initial_bits: u8,
+    steps: Vec<(Stage, u8, u8)>,
+}
+
+impl SavedPlan {
+    fn new(plan: &Plan<Stage>) -> Self {
+        Self {
+            initial_bits: plan.initial_bits,
+            steps: plan
+                .steps
+                .iter()
+                .map(|s| (s.step.kind, s.step.input_bits, s.step.output_bits))
+                .collect(),
+        }
+    }
+}
+
+/// Checkpoint id of a search, see `checkpoint::task_id`
+pub fn task_id(seed_info: &SeedInfo, candidates: Option<&LowBitsCandidates>) -> String {
+    let candidates = candidates.map(|c| (c.num_known_bits, &c.patterns, c.add_similar_seed));
+    crate::checkpoint::task_id("find", &(seed_info, candidates))
+}
+
+/// The plan stored in the checkpoint, if the search has already started. The
+/// cost estimations are not stored, so they are set to 0.
+pub fn resumed_plan(ckpt: &Checkpointer) -> Option<Plan<Stage>> {
+    let saved: SavedPlan = serde_json::from_value(ckpt.state.plan.clone()).ok()?;
+
+    Some(Plan {
+        initial_bits: saved.initial_bits,
+        steps: saved
+            .steps
+            .into_iter()
+            .map(|(kind, input_bits, output_bits)| PlannedStep {
+                step: BruteforceStep {
+                    kind,
+                    input_bits,
+                    output_bits,
+                    cost: 0.0,
+                    output_ratio: 0.0,
+                },
+                input_candidates: 0.0,
+                estimated_seconds: 0.0,
+            })
+            .collect(),
+    })
+}
+
+/// Same as `run_plan`, but saving the progress to `ckpt`. If the checkpoint
+/// was already used, the completed steps are skipped and `candidates` is
+/// ignored. Returns `FindError::Interrupted` if the search was interrupted
+/// using `checkpoint::interrupt`.
+pub fn run_plan_with_checkpoint(
+    seed_info: &SeedInfo,
+    plan: &Plan<Stage>,
+    candidates: Option<LowBitsCandidates>,
+    num_threads: usize,
+    ckpt: &mut Checkpointer,
+) -> Result<Vec<i64>, FindError> {
+    let version = seed_info.version().map_err(FindError::InvalidVersion)?;
+    warn_ignored_clues(seed_info, plan);
+
+    let saved_plan = serde_json::to_value(SavedPlan::new(plan)).expect("Serialization fail");
+    if ckpt.state.plan.is_null() {
+        ckpt.state.plan = saved_plan;
+        ckpt.state.input = candidates.map(|c| c.into_seeds());
+    } else if ckpt.state.plan != saved_plan {
+        return Err(FindError::CheckpointMismatch);
+    }
+
+    for (i, s) in plan.steps.iter().enumerate().skip(ckpt.state.step) {
+        let stage = s.step.kind;
+        let input_bits = if i == 0 {
+            plan.initial_bits
+        } else {
+            plan.steps[i - 1].step.output_bits
+        };
+        let current = ckpt
+            .state
+            .input
+            .clone()
+            .map(|seeds| LowBitsCandidates::new(input_bits, seeds));
+        info!("Running stage {:?}", stage);
+        let seeds = run_stage(stage, seed_info, version, current, num_threads, ckpt)?;
+        info!("Found {} candidates", seeds.len());
+        ckpt.next_step(seeds);
+    }
+
+    let current = ckpt
+        .state
+        .input
+        .clone()
+        .map(|seeds| LowBitsCandidates::new(plan.output_bits(), seeds));
+    match current {
+        Some(c) if c.num_known_bits == 64 => {
+            Ok(c.into_seeds().into_iter().map(|s| s as i64).collect())
+        }
+        Some(c) => Err(FindError::Incomplete(c)),
+        None => Err(FindError::NotEnoughInformation),
+    }
+}
+
+/// Estimate the cost of one step by running a small part of it
+fn estimate_step(
+    seed_info: &SeedInfo,
+    version: MinecraftVersion,
+    stage: Stage,
+    input_bits: u8,
+    output_bits: u8,
+) -> BruteforceStep<Stage> {
+    let river = BiomeId(biome_id::river);
+    let java = !seed_info.options.not_from_java_next_long;
+    // Seeds used to measure the cost of the filters
+    let bench_seeds: Vec<u64> = (0..1024u64)
+        .map(|i| i.wrapping_mul(0x5DEECE66D) & ((1 << 48) - 1))
+        .collect();
+
+    let (cost, output_ratio) = match stage {
+        Stage::RiversQuarterScale26 => {
+            let rivers = &seed_info.biomes_quarter_scale[&river];
+            let t = time_per_iteration(64, || {
+                biome_layers::river_seed_finder_26_range(rivers, 0, 64);
+            });
+            (t * (1u64 << 24) as f64, EXPECTED_CANDIDATES_26)
+        }
+        Stage::TreasureMap => {
+            let pmap = seed_info.treasure_maps[0].to_map();
+            let t = time_per_iteration(64, || {
+                biome_layers::treasure_map_river_seed_finder(&pmap, version, 0, 64);
+            });
+            let expected = if output_bits == 26 {
+                EXPECTED_CANDIDATES_26
+            } else {
+                EXPECTED_CANDIDATES_34
+            };
+            (t * (1u64 << 24) as f64, expected)
+        }
+        Stage::EndPillars32 => {
+            if input_bits == 0 {
+                let t = time_per_iteration(1, || {
+                    end_pillars::pillar_seeds_from_heights(&seed_info.end_pillars);
+                });
+                // Usually there is only one pillar seed
+                (t, (1u64 << 16) as f64)
+            } else {
+                let t = time_per_iteration(bench_seeds.len(), || {
+                    for &s in &bench_seeds {
+                        end_pillars::pillar_seed(s as i64);
+                    }
+                });
+                (t, 1.0 / (1u64 << 16) as f64)
+            }
+        }
+        Stage::SlimeChunks48 => {
+            let sc = slime_chunks(seed_info);
+            let p = slime_chunks_false_positive_rate(seed_info);
+            let remaining = 2f64.powi(48 - i32::from(input_bits));
+            if input_bits == 0 {
+                // Units of 2^18 seeds
+                let t = time_per_iteration(16, || {
+                    sc.find_seed_range(0, 16);
+                });
+                (t * (1u64 << 30) as f64, remaining * p)
+            } else if let Some(&low_18) = sc.low_18_candidates().first() {
+                // Probability that the lower 18 bits of a candidate are valid
+                let p18 = sc.num_low_18_candidates() as f64 / (1u64 << 18) as f64;
+                let t = time_per_iteration(256, || {
+                    sc.find_seed_with_low_bits(u64::from(low_18), 40);
+                });
+                (t * remaining * p18, remaining * p)
+            } else {
+                (0.0, 0.0)
+            }
+        }
+        Stage::SlimeEvidence48 => {
+            let sc = slime_evidence(seed_info);
+            let p = sc.false_positive_rate();
+            let remaining = 2f64.powi(48 - i32::from(input_bits.min(48)));
+            if input_bits == 0 {
+                // Units of 2^18 seeds
+                let t = time_per_iteration(16, || {
+                    sc.find_seed_range(0, 16);
+                });
+                (t * (1u64 << 30) as f64, remaining * p)
+            } else if let Some(&low_18) = sc.low_18_candidates().first() {
+                // Probability that the lower 18 bits of a candidate are valid
+                let p18 = sc.num_low_18_candidates() as f64 / (1u64 << 18) as f64;
+                let t = time_per_iteration(256, || {
+                    sc.find_seed_with_low_bits(u64::from(low_18), 40);
+                });
+                (t * remaining * p18, remaining * p)
+            } else {
+                (0.0, 0.0)
+            }
+        }
+        Stage::BuriedTreasures48 => {
+            let positive = &seed_info.positive.buried_treasures;
+            let negative = &seed_info.negative.buried_treasures;
+            let t = time_per_iteration(bench_seeds.len(), || {
+                for &s in &bench_seeds {
+                    let _ = positive
+                        .iter()
+                        .chain(negative.iter())
+                        .filter(|c| structures::is_treasure_chunk(s as i64, c))
+                        .count();
+                }
+            });
+            let p = 0.01f64.powi(positive.len() as i32) * 0.99f64.powi(negative.len() as i32);
+            (t, p)
+        }
+        Stage::Structures48 => {
+            let sc = StructureChunks::new(version, &seed_info.positive, &seed_info.negative);
+            let p = sc.probability();
+            if input_bits == 0 {
+                // Units of 2^num_low_bits seeds
+                let t = time_per_iteration(64, || {
+                    sc.find_seed_range(0, 64);
+                });
+                let units = 2f64.powi(48 - i32::from(sc.num_low_bits()));
+                (t * units, 2f64.powi(48) * p)
+            } else {
+                let t = time_per_iteration(bench_seeds.len(), || {
+                    for &s in &bench_seeds {
+                        sc.try_seed(s as i64);
+                    }
+                });
+                (t, p)
+            }
+        }
+        Stage::Dungeons48 => {
+            let dungeons = dungeons(seed_info);
+            let n = dungeons.len();
+            let p = binomial_at_least(n, n - n / 4, DUNGEON_FALSE_POSITIVE_RATE);
+            let t = time_per_iteration(bench_seeds.len(), || {
+                for &s in &bench_seeds {
+                    let _ = population::dungeons_match(&dungeons, version, s as i64);
+                }
+            });
+            if input_bits == 0 {
+                (t * 2f64.powi(48), 2f64.powi(48) * p)
+            } else {
+                (t, p)
+            }
+        }
+        Stage::Rivers64 => {
+            let rivers = &seed_info.biomes[&river];
+            let extra_biomes = extra_biomes(seed_info);
+            let t = time_per_iteration(16, || {
+                biome_layers::river_seed_finder_range(rivers, &extra_biomes, version, 0, 16);
+            });
+            (t * (1u64 << 24) as f64, 1.0)
+        }
+        Stage::Extend48 => {
+            let t = time_per_iteration(bench_seeds.len(), || {
+                for &s in &bench_seeds {
+                    JavaRng::extend_long_48(s);
+                }
+            });
+            // On average there is one 64-bit seed for each 48-bit seed
+            (t, 1.0)
+        }
+        Stage::Upper16 => {
+            // Only the extension itself, the cost of checking the 2^16 seeds
+            // of each candidate is added by the next step of the plan
+            let seeds = &bench_seeds[..16];
+            let t = time_per_iteration(seeds.len(), || {
+                std::hint::black_box(upper_16(seeds));
+            });
+            (t, (1u64 << 16) as f64)
+        }
+        Stage::SeedHash64 if input_bits == 64 => {
+            let t = time_per_iteration(bench_seeds.len(), || {
+                for &s in &bench_seeds {
+                    biome_layers::sha256_long_to_long(s as i64);
+                }
+            });
+            (t, 0.5f64.powi(64))
+        }
+        Stage::SeedHash64 => {
+            let seed_hash = seed_info.world_seed_hash.unwrap();
+            // Each iteration tries 64 seeds
+            let t = time_per_iteration(4, || {
+                if java {
+                    biome_layers::seed_hash_bruteforce_26_java_range(seed_hash, &[0], 0, 3);
+                } else {
+                    biome_layers::seed_hash_bruteforce_26_range(seed_hash, &[0], 0, 3);
+                }
+            });
+            // At most one seed will be found
+            (t * (1u64 << 32) as f64, 1.0)
+        }
+        Stage::Dungeons64 => {
+            let dungeons = dungeon_floors(seed_info);
+            let n = dungeons.len();
+            let p = dungeons
+                .iter()
+                .map(|(_, floor)| {
+                    DUNGEON_FALSE_POSITIVE_RATE * floor.as_ref().map_or(1.0, |f| f.probability())
+                })
+                .fold(0.0, f64::max);
+            let t = time_per_iteration(bench_seeds.len(), || {
+                for &s in &bench_seeds {
+                    population::dungeons_match_1_18(&dungeons, s as i64);
+                }
+            });
+            let p = binomial_at_least(n, n - n / 4, p);
+            if input_bits == 48 {
+                (t * (1u64 << 16) as f64, p * (1u64 << 16) as f64)
+            } else {
+                (t, p)
+            }
+        }
+        Stage::Biomes64 => {
+            let extra_biomes = extra_biomes(seed_info);
+            let land_and_water = item_map_land_and_water(seed_info);
+            let seeds: Vec<i64> = bench_seeds[..4].iter().map(|&s| s as i64).collect();
+            let t = time_per_iteration(seeds.len(), || {
+                filter_seeds_using_biomes(
+                    seed_info.edition,
+                    &seeds,
+                    &extra_biomes,
+                    &land_and_water,
+                    version,
+                );
+            });
+            // Very rough estimate, assume that each biome has a 50% chance of
+            // matching
+            let num_biomes = extra_biomes.len() + land_and_water.len();
+            (t, 0.5f64.powi(num_biomes.min(64) as i32))
+        }
+        Stage::DimensionBiomes64 => {
+            let clues = dimension_biome_clues(seed_info);
+            let seeds: Vec<i64> = bench_seeds[..4].iter().map(|&s| s as i64).collect();
+            let t = time_per_iteration(seeds.len(), || {
+                for (dimension, biomes) in &clues {
+                    dimension_biomes::filter_seeds_using_dimension_biomes(
+                        &seeds, biomes, *dimension, version,
+                    );
+                }
+            });
+            let num_biomes: usize = clues.iter().map(|(_, biomes)| biomes.len()).sum();
+            (t, 0.5f64.powi(num_biomes.min(64) as i32))
+        }
+    };
+
+    BruteforceStep {
+        kind: stage,
+        input_bits,
+        output_bits,
+        cost,
+        output_ratio,
+    }
+}
+
+/// All the 64-bit seeds with the same lower 48 bits as the candidates
+fn upper_16(candidates: &[u64]) -> Vec<u64> {
+    candidates
+        .iter()
+        .flat_map(|&s| (0..(1u64 << 16)).map(move |hi| (hi << 48) | s))
+        .collect()
+}
+
+/// Run `f` and return the elapsed time in seconds divided by `iterations`
+fn time_per_iteration<F: FnOnce()>(iterations: usize, f: F) -> f64 {
+    let start = Instant::now();
+    f();
+
+    start.elapsed().as_secs_f64() / iterations as f64
+}
+
+/// The slime chunk clues of the SeedInfo, with their error margins
+pub fn slime_chunks(seed_info: &SeedInfo) -> SlimeChunks {
+    SlimeChunks::new(
+        &seed_info.positive.slime_chunks,
+        seed_info.options.error_margin_slime_chunks as usize,
+        &seed_info.negative.slime_chunks,
+        seed_info.options.error_margin_slime_chunks_negative as usize,
+    )
+}
+
+/// The slime chunk clues of the SeedInfo, with coordinates relative to an
+/// unknown offset inside `window`
+pub fn slime_chunks_pattern(
+    seed_info: &SeedInfo,
+    window: Area,
+    transforms: Vec<ChunkTransform>,
+) -> SlimeChunksPattern {
+    SlimeChunksPattern::new(
+        &seed_info.positive.slime_chunks,
+        seed_info.options.error_margin_slime_chunks as usize,
+        &seed_info.negative.slime_chunks,
+        seed_info.options.error_margin_slime_chunks_negative as usize,
+        window,
+        transforms,
+    )
+}
+
+/// The weighted slime chunk clues of the SeedInfo
+pub fn slime_evidence(seed_info: &SeedInfo) -> SlimeEvidenceScore {
+    let margin = seed_info
+        .options
+        .slime_evidence_margin
+        .unwrap_or(slime::DEFAULT_SLIME_EVIDENCE_MARGIN);
+    SlimeEvidenceScore::new(&seed_info.slime_evidence, margin)
+}
+
+/// Sort the seeds by the log-likelihood of the weighted slime chunk clues,
+/// best first. Only the lower 48 bits of the seeds are used.
+pub fn rank_by_slime_evidence(seed_info: &SeedInfo, seeds: &[i64]) -> Vec<(i64, f64)> {
+    let sc = slime_evidence(seed_info);
+    let mut v: Vec<(i64, f64)> = seeds
+        .iter()
+        .map(|&s| (s, sc.score(s as u64 & ((1 << 48) - 1))))
+        .collect();
+    v.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    v
+}
+
+/// Probability that a random 48-bit seed matches all the slime chunks, taking
+/// into account the error margins
+fn slime_chunks_false_positive_rate(seed_info: &SeedInfo) -> f64 {
+    let n = seed_info.positive.slime_chunks.len();
+    let e = seed_info.options.error_margin_slime_chunks as usize;
+    let nn = seed_info.negative.slime_chunks.len();
+    let ne = seed_info.options.error_margin_slime_chunks_negative as usize;
+
+    binomial_at_least(n, n.saturating_sub(e), 0.1)
+        * binomial_at_least(nn, nn.saturating_sub(ne), 0.9)
+}
+
+/// Approximate probability that a random seed generates a dungeon at some
+/// given coordinates: 8 attempts with 1/2^16 chance, and a few possible
+/// population orders depending on the biome and the lakes
+const DUNGEON_FALSE_POSITIVE_RATE: f64 = 1.0 / 2048.0;
+
+/// Spawner coordinates of the dungeons
+fn dungeons(seed_info: &SeedInfo) -> Vec<(i32, i32, i32)> {
+    seed_info
+        .dungeons
+        .iter()
+        .map(|d| (d.x as i32, d.y as i32, d.z as i32))
+        .collect()
+}
+
+/// Spawner coordinates and floor of the dungeons. Invalid floors are ignored
+fn dungeon_floors(seed_info: &SeedInfo) -> Vec<DungeonClue> {
+    seed_info
+        .dungeons
+        .iter()
+        .map(|d| {
+            let floor = if d.floor.is_empty() {
+                None
+            } else {
+                match MossyFloor::parse(&d.floor) {
+                    Ok(floor) => Some(floor),
+                    Err(e) => {
+                        warn!("Ignoring floor of dungeon at {:?}: {}", (d.x, d.y, d.z), e);
+                        None
+                    }
+                }
+            };
+            ((d.x as i32, d.y as i32, d.z as i32), floor)
+        })
+        .collect()
+}
+
+/// Probability of at least `k` successes out of `n` trials
+fn binomial_at_least(n: usize, k: usize, p: f64) -> f64 {
+    let mut total = 0.0;
+    let mut n_choose_i = 1.0;
+    for i in 0..=n {
+        if i > 0 {
+            n_choose_i = n_choose_i * (n + 1 - i) as f64 / i as f64;
+        }
+        if i >= k {
+            total += n_choose_i * p.powi(i as i32) * (1.0 - p).powi((n - i) as i32);
+        }
+    }
+
+    total
+}
+
+fn run_stage(
+    stage: Stage,
+    seed_info: &SeedInfo,
+    version: MinecraftVersion,
+    current: Option<LowBitsCandidates>,
+    num_threads: usize,
+    ckpt: &mut Checkpointer,
+) -> Result<Vec<u64>, Interrupted> {
+    let river = BiomeId(biome_id::river);
+    let num_known_bits = current.as_ref().map(|c| c.num_known_bits).unwrap_or(0);
+    let candidates = current.map(|c| c.into_seeds()).unwrap_or_default();
+    let candidates = &candidates[..];
+
+    let seeds = match stage {
+        Stage::RiversQuarterScale26 => {
+            let rivers = &seed_info.biomes_quarter_scale[&river];
+            ckpt.run_range(num_threads, 1 << 24, block_size(1 << 24), |lo, hi| {
+                biome_layers::river_seed_finder_26_range(rivers, lo as u32, hi as u32)
+                    .into_iter()
+                    .map(|s| s as u64)
+                    .collect()
+            })?
+        }
+        Stage::TreasureMap => {
+            if seed_info.treasure_maps.len() > 1 {
+                warn!("Only the first treasure map will be used");
+            }
+            let pmap = seed_info.treasure_maps[0].to_map();
+            ckpt.run_range(num_threads, 1 << 24, block_size(1 << 24), |lo, hi| {
+                biome_layers::treasure_map_river_seed_finder(&pmap, version, lo as u32, hi as u32)
+                    .into_iter()
+                    .map(|s| s as u64)
+                    .collect()
+            })?
+        }
+        Stage::EndPillars32 => {
+            if num_known_bits == 0 {
+                end_pillars::low_bits_candidates_from_heights(&seed_info.end_pillars).into_seeds()
+            } else {
+                let pillar_seeds = end_pillars::pillar_seeds_from_heights(&seed_info.end_pillars);
+                candidates
+                    .iter()
+                    .cloned()
+                    .filter(|&s| pillar_seeds.contains(&end_pillars::pillar_seed(s as i64)))
+                    .collect()
+            }
+        }
+        Stage::SlimeChunks48 => {
+            let sc = slime_chunks(seed_info);
+            info!("Found {} 18-bit candidates", sc.num_low_18_candidates());
+            if num_known_bits == 0 {
+                let total = 1 << (48 - 18);
+                ckpt.run_range(num_threads, total, block_size(total), |lo, hi| {
+                    sc.find_seed_range(lo as u32, (hi - lo) as u32)
+                })?
+            } else {
+                ckpt.run_slice(num_threads, candidates, SLICE_BLOCK, |seeds| {
+                    seeds
+                        .iter()
+                        .flat_map(|&s| sc.find_seed_with_low_bits(s, u32::from(num_known_bits)))
+                        .collect()
+                })?
+            }
+        }
+        Stage::SlimeEvidence48 => {
+            let sc = slime_evidence(seed_info);
+            info!("Found {} 18-bit candidates", sc.num_low_18_candidates());
+            if num_known_bits == 0 {
+                let total = 1 << (48 - 18);
+                ckpt.run_range(num_threads, total, block_size(total), |lo, hi| {
+                    sc.find_seed_range(lo as u32, (hi - lo) as u32)
+                })?
+            } else if num_known_bits >= 48 {
+                ckpt.run_slice(num_threads, candidates, SLICE_BLOCK, |seeds| {
+                    seeds
+                        .iter()
+                        .cloned()
+                        .filter(|&s| sc.try_seed(s & ((1 << 48) - 1)))
+                        .collect()
+                })?
+            } else {
+                ckpt.run_slice(num_threads, candidates, SLICE_BLOCK, |seeds| {
+                    seeds
+                        .iter()
+                        .flat_map(|&s| sc.find_seed_with_low_bits(s, u32::from(num_known_bits)))
+                        .collect()
+                })?
+            }
+        }
+        Stage::BuriedTreasures48 => {
+            let positive = &seed_info.positive.buried_treasures;
+            let negative = &seed_info.negative.buried_treasures;
+            candidates
+                .iter()
+                .cloned()
+                .filter(|&s| {
+                    positive
+                        .iter()
+                        .all(|c| structures::is_treasure_chunk(s as i64, c))
+                        && !negative
+                            .iter()
+                            .any(|c| structures::is_treasure_chunk(s as i64, c))
+                })
+                .collect()
+        }
+        Stage::Structures48 => {
+            let sc = StructureChunks::new(version, &seed_info.positive, &seed_info.negative);
+            if num_known_bits == 0 {
+                info!(
+                    "Found {} {}-bit candidates",
+                    sc.low_candidates().len(),
+                    sc.num_low_bits()
+                );
+                let total = 1 << (48 - sc.num_low_bits());
+                ckpt.run_range(num_threads, total, block_size(total), |lo, hi| {
+                    sc.find_seed_range(lo, hi)
+                        .into_iter()
+                        .map(|s| s as u64)
+                        .collect()
+                })?
+            } else {
+                ckpt.run_slice(num_threads, candidates, SLICE_BLOCK, |seeds| {
+                    seeds
+                        .iter()
+                        .cloned()
+                        .filter(|&s| sc.try_seed(s as i64))
+                        .collect()
+                })?
+            }
+        }
+        Stage::Dungeons48 => {
+            // Only used if `population::dungeon_finder_supports(version)`, so
+            // dungeons_match never returns an error
+            let dungeons = dungeons(seed_info);
+            if num_known_bits == 0 {
+                // Units of 2^16 seeds
+                let total = 1 << 32;
+                ckpt.run_range(num_threads, total, block_size(total), |lo, hi| {
+                    ((lo << 16)..(hi << 16))
+                        .filter(|&s| {
+                            population::dungeons_match(&dungeons, version, s as i64) == Ok(true)
+                        })
+                        .collect()
+                })?
+            } else {
+                ckpt.run_slice(num_threads, candidates, SLICE_BLOCK, |seeds| {
+                    seeds
+                        .iter()
+                        .cloned()
+                        .filter(|&s| {
+                            population::dungeons_match(
+                                &dungeons,
+                                version,
+                                (s & ((1 << 48) - 1)) as i64,
+                            ) == Ok(true)
+                        })
+                        .collect()
+                })?
+            }
+        }
+        Stage::Rivers64 => {
+            let rivers = &seed_info.biomes[&river];
+            let extra_biomes: Vec<_> = extra_biomes(seed_info)
+                .into_iter()
+                .filter(|(id, _)| *id != river)
+                .collect();
+            ckpt.run_range(num_threads, 1 << 24, block_size(1 << 24), |lo, hi| {
+                biome_layers::river_seed_finder_range(
+                    rivers,
+                    &extra_biomes,
+                    version,
+                    lo as u32,
+                    hi as u32,
+                )
+                .into_iter()
+                .map(|s| s as u64)
+                .collect()
+            })?
+        }
+        Stage::Extend48 => candidates
+            .iter()
+            .flat_map(|&s| JavaRng::extend_long_48(s))
+            .collect(),
+        Stage::Upper16 => upper_16(candidates),
+        Stage::SeedHash64 if num_known_bits == 64 => {
+            let seed_hash = seed_info.world_seed_hash.unwrap();
+            ckpt.run_slice(num_threads, candidates, SLICE_BLOCK, |seeds| {
+                seeds
+                    .iter()
+                    .cloned()
+                    .filter(|&s| biome_layers::sha256_long_to_long(s as i64) == seed_hash)
+                    .collect()
+            })?
+        }
+        Stage::SeedHash64 => {
+            let seed_hash = seed_info.world_seed_hash.unwrap();
+            let java = !seed_info.options.not_from_java_next_long;
+            // Use small blocks to be able to stop all the threads as soon as
+            // the seed is found
+            ckpt.run_range_until_found(num_threads, 1 << 32, 1 << 12, |lo, hi| {
+                let r = if java {
+                    biome_layers::seed_hash_bruteforce_26_java_range(
+                        seed_hash,
+                        candidates,
+                        lo as u32,
+                        (hi - 1) as u32,
+                    )
+                } else {
+                    biome_layers::seed_hash_bruteforce_26_range(
+                        seed_hash,
+                        candidates,
+                        lo as u32,
+                        (hi - 1) as u32,
+                    )
+                };
+                r.into_iter().map(|seed| seed as u64).collect()
+            })?
+        }
+        Stage::Dungeons64 if num_known_bits == 48 => {
+            let dungeons = dungeon_floors(seed_info);
+            // Each block tries all the upper 16 bits of one candidate
+            ckpt.run_range(num_threads, candidates.len() as u64, 1, |lo, hi| {
+                candidates[lo as usize..hi as usize]
+                    .iter()
+                    .flat_map(|&s| (0..(1u64 << 16)).map(move |hi| (hi << 48) | s))
+                    .filter(|&s| population::dungeons_match_1_18(&dungeons, s as i64))
+                    .collect()
+            })?
+        }
+        Stage::Dungeons64 => {
+            let dungeons = dungeon_floors(seed_info);
+            ckpt.run_slice(num_threads, candidates, SLICE_BLOCK, |seeds| {
+                seeds
+                    .iter()
+                    .cloned()
+                    .filter(|&s| population::dungeons_match_1_18(&dungeons, s as i64))
+                    .collect()
+            })?
+        }
+        Stage::Biomes64 => {
+            let extra_biomes = extra_biomes(seed_info);
+            let land_and_water = item_map_land_and_water(seed_info);
+            ckpt.run_slice(num_threads, candidates, SLICE_BLOCK, |seeds| {
+                let seeds: Vec<i64> = seeds.iter().map(|&s| s as i64).collect();
+                filter_seeds_using_biomes(
+                    seed_info.edition,
+                    &seeds,
+                    &extra_biomes,
+                    &land_and_water,
+                    version,
+                )
+                .into_iter()
+                .map(|s| s as u64)
+                .collect()
+            })?
+        }
+        Stage::DimensionBiomes64 => {
+            let clues = dimension_biome_clues(seed_info);
+            ckpt.run_slice(num_threads, candidates, SLICE_BLOCK, |seeds| {
+                let mut seeds: Vec<i64> = seeds.iter().map(|&s| s as i64).collect();
+                for (dimension, biomes) in &clues {
+                    seeds = dimension_biomes::filter_seeds_using_dimension_biomes(
+                        &seeds, biomes, *dimension, version,
+                    );
+                }
+                seeds.into_iter().map(|s| s as u64).collect()
+            })?
+        }
+    };
+
+    Ok(seeds)
+}
+
+/// Number of candidates per block when filtering a list of candidates
+const SLICE_BLOCK: u64 = 1 << 10;
+
+/// Split a range into 2^16 blocks, so the checkpoint is updated often
+fn block_size(total: u64) -> u64 {
+    std::cmp::max(total >> 16, 1)
+}
+
+/// Check the biomes, and then the land and water from the map items. The
+/// map colors are only known for Java Edition, so `land_and_water` must be
+/// empty in Bedrock Edition.
+fn filter_seeds_using_biomes(
+    edition: Edition,
+    candidates: &[i64],
+    extra_biomes: &[(BiomeId, Point)],
+    land_and_water: &[(BiomeId, Point)],
+    version: MinecraftVersion,
+) -> Vec<i64> {
+    let seeds = match edition {
+        Edition::Java => biome_layers::filter_seeds_using_biomes(candidates, extra_biomes, version),
+        Edition::Bedrock => bedrock::filter_seeds_using_biomes(candidates, extra_biomes, version),
+    };
+    if land_and_water.is_empty() {
+        return seeds;
+    }
+
+    biome_layers::filter_seeds_using_land_and_water(&seeds, land_and_water, version)
+}
+
+fn extra_biomes(seed_info: &SeedInfo) -> Vec<(BiomeId, Point)> {
+    flatten_biomes(&seed_info.biomes)
+}
+
+/// Only one pixel out of 8x8 is checked, so each map item has at most 256
+/// observations
+const ITEM_MAP_STEP: usize = 8;
+
+/// Land and water observations from all the map items of the SeedInfo
+fn item_map_land_and_water(seed_info: &SeedInfo) -> Vec<(BiomeId, Point)> {
+    if seed_info.edition == Edition::Bedrock {
+        return vec![];
+    }
+
+    seed_info
+        .item_maps
+        .iter()
+        .flat_map(|m| m.land_and_water(ITEM_MAP_STEP))
+        .collect()
+}
+
+/// The nether and End biomes of the SeedInfo, skipping empty dimensions
+fn dimension_biome_clues(seed_info: &SeedInfo) -> Vec<(Dimension, Vec<(BiomeId, Point)>)> {
+    [
+        (Dimension::Nether, &seed_info.nether_biomes),
+        (Dimension::End, &seed_info.end_biomes),
+    ]
+    .into_iter()
+    .filter(|(_, biomes)| !biomes.is_empty())
+    .map(|(dimension, biomes)| (dimension, flatten_biomes(biomes)))
+    .collect()
+}
+
+fn flatten_biomes(biomes: &HashMap<BiomeId, Vec<Point>>) -> Vec<(BiomeId, Point)> {
+    biomes
+        .iter()
+        .flat_map(|(id, vec_xz)| vec_xz.iter().map(move |p| (*id, *p)))
+        .collect()
+}
+
+/// Warn about the clues that are present in the SeedInfo but will not be used
+/// to find the seed.
+fn warn_ignored_clues(seed_info: &SeedInfo, plan: &Plan<Stage>) {
+    if seed_info.edition == Edition::Bedrock {
+        let mut other_clues = seed_info.clone();
+        other_clues.version.clear();
+        other_clues.edition = Edition::Java;
+        other_clues.world_seed = None;
+        other_clues.description.clear();
+        other_clues.options = Default::default();
+        other_clues.biomes.clear();
+        if !other_clues.item_maps.is_empty() {
+            // The land and water of the map items is only implemented for Java
+            warn!("Map items are not supported in Bedrock Edition, ignoring them");
+            other_clues.item_maps.clear();
+        }
+        if other_clues != SeedInfo::default() {
+            warn!("Only the biomes can be used in Bedrock Edition, ignoring the other clues");
+        }
+        return;
+    }
+    let river = BiomeId(biome_id::river);
+    if !seed_info.end_pillars.is_empty() {
+        if let Ok(version) = seed_info.version() {
+            if version < MinecraftVersion::Java1_9 {
+                warn!("End pillars are random before 1.9, ignoring them");
+            }
+        }
+    }
+    if seed_info.biomes_quarter_scale.keys().any(|id| *id != river) {
+        warn!("Quarter-scale biomes other than rivers are ignored");
+    }
+    let uses_treasure_map = plan.steps.iter().any(|s| s.step.kind == Stage::TreasureMap);
+    if !uses_treasure_map && !seed_info.treasure_maps.is_empty() {
+        warn!("Treasure maps are ignored");
+    }
+    if !seed_info.and.is_empty() {
+        warn!("SeedInfo.and is not supported yet, ignoring it");
+    }
+    if !seed_info.dungeons.is_empty() {
+        if let Ok(version) = seed_info.version() {
+            if !population::dungeon_finder_supports(version) && version < MinecraftVersion::Java1_18
+            {
+                warn!("Dungeons are not supported in {:?}, ignoring them", version);
+            }
+        }
+    }
+    for (name, positive, negative) in [
+        (
+            "mineshafts",
+            &seed_info.positive.mineshafts,
+            &seed_info.negative.mineshafts,
+        ),
+        (
+            "strongholds",
+            &seed_info.positive.strongholds,
+            &seed_info.negative.strongholds,
+        ),
+    ] {
+        if !positive.is_empty() || !negative.is_empty() {
+            warn!("The {} are not used by the search, ignoring them", name);
+        }
+    }
+    let uses_structures = plan
+        .steps
+        .iter()
+        .any(|s| s.step.kind == Stage::Structures48);
+    if let Ok(version) = seed_info.version() {
+        let structures = StructureChunks::new(version, &seed_info.positive, &seed_info.negative);
+        if !uses_structures && !structures.is_empty() {
+            warn!("Structures are ignored");
+        }
+        for kind in StructureKind::all() {
+            let used = !kind.chunks(&seed_info.positive).is_empty()
+                || !kind.chunks(&seed_info.negative).is_empty();
+            if used && StructurePlacement::new(*kind, version).is_none() {
+                warn!(
+                    "{:?} does not exist in version {:?}, ignoring it",
+                    kind, version
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::seed_info::Dungeon;
+    use crate::seed_info::ItemMap;
+    use crate::seed_info::SlimeEvidence;
+    use crate::slime::generate_slime_chunks_and_not;
+
+    fn plan_kinds(seed_info: &SeedInfo) -> Vec<Stage> {
+        plan(seed_info, None, 1)
+            .unwrap()
+            .steps
+            .into_iter()
+            .map(|s| s.step.kind)
+            .collect()
+    }
+
+    #[test]
+    fn plan_slime_chunks() {
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.7".to_string();
+        seed_info.positive.slime_chunks = vec![Chunk::new(1, 2)];
+        assert_eq!(
+            available_steps(&seed_info).unwrap(),
+            vec![
+                (Stage::SlimeChunks48, 0, 48),
+                (Stage::SlimeChunks48, 26, 48),
+                (Stage::SlimeChunks48, 34, 48),
+                (Stage::SlimeChunks48, 48, 48),
+                (Stage::Extend48, 48, 64),
+            ]
+        );
+        assert_eq!(
+            plan_kinds(&seed_info),
+            vec![Stage::SlimeChunks48, Stage::Extend48]
+        );
+
+        seed_info.options.not_from_java_next_long = true;
+        assert_eq!(plan_kinds(&seed_info), vec![Stage::SlimeChunks48]);
+    }
+
+    #[test]
+    fn plan_slime_evidence() {
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.7".to_string();
+        seed_info.slime_evidence = vec![SlimeEvidence {
+            x: 1,
+            z: 2,
+            slimes: 3,
+            minutes: 5.0,
+        }];
+        assert_eq!(
+            plan_kinds(&seed_info),
+            vec![Stage::SlimeEvidence48, Stage::Extend48]
+        );
+
+        let ranked = rank_by_slime_evidence(&seed_info, &[1, 2]);
+        assert_eq!(ranked.len(), 2);
+        assert!(ranked[0].1 >= ranked[1].1);
+    }
+
+    #[test]
+    fn plan_filters_before_upper_16() {
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.7".to_string();
+        seed_info.options.not_from_java_next_long = true;
+        seed_info.world_seed_hash = Some(1234);
+        let (c, nc) = generate_slime_chunks_and_not(1234, 20, 20);
+        seed_info.positive.slime_chunks = c;
+        seed_info.negative.slime_chunks = nc;
+        let candidates = LowBitsCandidates::new(48, (0..1000).collect());
+        let plan = plan(&seed_info, Some(&candidates), 1).unwrap();
+        let kinds: Vec<_> = plan.steps.iter().map(|s| s.step.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![Stage::SlimeChunks48, Stage::Upper16, Stage::SeedHash64]
+        );
+        assert!(plan.steps[1].step.cost > 0.0);
+    }
+
+    #[test]
+    fn plan_rivers_and_hash() {
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.15".to_string();
+        seed_info
+            .biomes_quarter_scale
+            .insert(BiomeId(biome_id::river), vec![]);
+        seed_info.world_seed_hash = Some(1234);
+        assert_eq!(
+            plan_kinds(&seed_info),
+            vec![Stage::RiversQuarterScale26, Stage::SeedHash64]
+        );
+    }
+
+    #[test]
+    fn plan_empty() {
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.7".to_string();
+        assert!(matches!(
+            plan(&seed_info, None, 1),
+            Err(FindError::NotEnoughInformation)
+        ));
+    }
+
+    #[test]
+    fn binomial() {
+        assert!((binomial_at_least(3, 0, 0.1) - 1.0).abs() < 1e-9);
+        assert!((binomial_at_least(3, 3, 0.1) - 0.001).abs() < 1e-9);
+        assert!((binomial_at_least(2, 1, 0.5) - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn find_slime_and_buried_treasure_with_candidates() {
+        let seed = 1234;
+        let (c, nc) = generate_slime_chunks_and_not(seed, 40, 40);
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.13".to_string();
+        seed_info.positive.slime_chunks = c;
+        seed_info.negative.slime_chunks = nc;
+        seed_info.options.not_from_java_next_long = true;
+        seed_info.positive.buried_treasures = vec![Chunk::new(-47, -28)];
+        let candidates = vec![seed as u64, 5678, seed as u64 + (1 << 40)];
+        let candidates = LowBitsCandidates::new(48, candidates);
+        match find_seeds(&seed_info, Some(candidates), 2) {
+            Err(FindError::Incomplete(c)) => {
+                assert_eq!(c.num_known_bits, 48);
+                assert_eq!(c.patterns, vec![seed as u64]);
+            }
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn resume_skips_completed_steps() {
+        let seed = 1234;
+        let (c, nc) = generate_slime_chunks_and_not(seed, 40, 40);
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.13".to_string();
+        seed_info.positive.slime_chunks = c;
+        seed_info.negative.slime_chunks = nc;
+        seed_info.options.not_from_java_next_long = true;
+        let candidates = LowBitsCandidates::new(48, vec![seed as u64, 5678]);
+        let plan = plan(&seed_info, Some(&candidates), 1).unwrap();
+        let task = task_id(&seed_info, Some(&candidates));
+
+        let mut ckpt = Checkpointer::in_memory(task.clone());
+        let r = run_plan_with_checkpoint(&seed_info, &plan, Some(candidates.clone()), 1, &mut ckpt);
+        assert!(matches!(r, Err(FindError::Incomplete(_))));
+        assert_eq!(ckpt.state.step, plan.steps.len());
+        assert_eq!(
+            resumed_plan(&ckpt).map(|p| SavedPlan::new(&p)),
+            Some(SavedPlan::new(&plan))
+        );
+
+        // All the steps are done, so the stored output is returned as is
+        ckpt.state.input = Some(vec![42]);
+        match run_plan_with_checkpoint(&seed_info, &plan, Some(candidates), 1, &mut ckpt) {
+            Err(FindError::Incomplete(c)) => assert_eq!(c.patterns, vec![42]),
+            x => panic!("{:?}", x),
+        }
+
+        let mut other_plan = plan.clone();
+        other_plan.steps.clear();
+        assert!(matches!(
+            run_plan_with_checkpoint(&seed_info, &other_plan, None, 1, &mut ckpt),
+            Err(FindError::CheckpointMismatch)
+        ));
+    }
+
+    #[test]
+    fn dungeons_filter_candidates() {
+        let world_seed: i64 = 4969430222601064613;
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.14".to_string();
+        seed_info.dungeons = vec![Dungeon {
+            x: -272,
+            y: 24,
+            z: -17,
+            floor: String::new(),
+        }];
+        let low_48 = world_seed as u64 & ((1 << 48) - 1);
+        let candidates = LowBitsCandidates::new(48, vec![low_48, low_48 ^ 1]);
+        let mut ckpt = Checkpointer::in_memory(String::new());
+        let seeds = run_stage(
+            Stage::Dungeons48,
+            &seed_info,
+            MinecraftVersion::Java1_14,
+            Some(candidates),
+            1,
+            &mut ckpt,
+        )
+        .unwrap();
+        assert_eq!(seeds, vec![low_48]);
+    }
+
+    #[test]
+    fn dungeons_1_18_filter_64_bit_seeds() {
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.18".to_string();
+        seed_info.positive.slime_chunks = vec![Chunk::new(1, 2)];
+        seed_info.dungeons = vec![Dungeon {
+            x: 159,
+            y: 23,
+            z: -290,
+            floor: "MMMMCMM;M?????M;C?????M;M?????M;C?????M;M?????C;CMMMMMM;".to_string(),
+        }];
+        let steps = available_steps(&seed_info).unwrap();
+        assert!(steps.contains(&(Stage::Dungeons64, 64, 64)));
+        assert!(!steps.iter().any(|s| s.0 == Stage::Dungeons48));
+    }
+
+    #[test]
+    fn dungeons_1_18_upper_16_bits() {
+        // Same dungeons as population::tests::dungeons_1_18_vanilla
+        let world_seed: i64 = -4172144997902289642;
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.18".to_string();
+        seed_info.options.not_from_java_next_long = true;
+        seed_info.dungeons = vec![
+            Dungeon {
+                x: -106,
+                y: 233,
+                z: 203,
+                floor: "MCMMMCMMC;MMMMMMMMC;MMMMMMMCM;MCCMMMCMM;MMMMMMMMM;MCMMCMCMM;MMMMMMMMC;"
+                    .to_string(),
+            },
+            Dungeon {
+                x: -649,
+                y: 174,
+                z: -139,
+                floor: "MCMMMMC;MMMMCMC;MMMMMMM;MMMMCCM;MMCMMMM;CMCMMMM;MMCMMCM;MMCMMCC;MMMMCMM;"
+                    .to_string(),
+            },
+        ];
+        let low_48 = world_seed as u64 & ((1 << 48) - 1);
+        let candidates = LowBitsCandidates::new(48, vec![low_48, low_48 ^ 1]);
+        let plan = plan(&seed_info, Some(&candidates), 1).unwrap();
+        let stages: Vec<_> = plan.steps.iter().map(|s| s.step.kind).collect();
+        assert_eq!(stages, vec![Stage::Dungeons64]);
+        let seeds = run_plan(&seed_info, &plan, Some(candidates), 1).unwrap();
+        assert_eq!(seeds, vec![world_seed]);
+    }
+
+    #[test]
+    fn plan_end_pillars_and_slime_chunks() {
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.13".to_string();
+        seed_info.end_pillars = vec![94, 103, 100, 85, 91, 88, 76, 97, 79, 82];
+        seed_info.positive.slime_chunks = vec![Chunk::new(1, 2)];
+        let steps = available_steps(&seed_info).unwrap();
+        assert!(steps.contains(&(Stage::EndPillars32, 0, 32)));
+        assert!(steps.contains(&(Stage::SlimeChunks48, 32, 48)));
+
+        seed_info.version = "1.7".to_string();
+        let steps = available_steps(&seed_info).unwrap();
+        assert!(!steps.iter().any(|s| s.0 == Stage::EndPillars32));
+    }
+
+    #[test]
+    fn item_maps_filter_64_bit_seeds() {
+        let world_seed = 1234;
+        let version = MinecraftVersion::Java1_18;
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.18".to_string();
+        seed_info.options.not_from_java_next_long = true;
+        // Draw a map with scale 4 using the generated biomes: deep water in
+        // oceans and rivers, grass everywhere else
+        let heightmap = crate::heightmap::OffsetHeightmap118::new(world_seed, version);
+        let mut colors = vec![0; 128 * 128];
+        for (i, c) in colors.iter_mut().enumerate() {
+            let (px, pz) = ((i % 128) as i64, (i / 128) as i64);
+            if px % ITEM_MAP_STEP as i64 != 0 || pz % ITEM_MAP_STEP as i64 != 0 {
+                // Unexplored
+                continue;
+            }
+            let (x, z) = ((px - 64) * 16 + 8, (pz - 64) * 16 + 8);
+            let area = biome_layers::Area { x, z, w: 1, h: 1 };
+            let y_offset = heightmap.surface_y_offset(x, z);
+            let m = biome_layers::generate_up_to_layer(
+                version,
+                area,
+                world_seed,
+                version.num_layers(),
+                y_offset,
+            );
+            let biome = m.a[(0, 0)];
+            *c = if biome_layers::is_oceanic(biome) || biome == biome_id::river {
+                12 * 4
+            } else {
+                4 + 1
+            };
+        }
+        assert!(colors.contains(&(12 * 4)) && colors.contains(&(4 + 1)));
+        seed_info.item_maps = vec![ItemMap {
+            x_center: 0,
+            z_center: 0,
+            scale: 4,
+            colors,
+        }];
+        assert!(available_steps(&seed_info)
+            .unwrap()
+            .contains(&(Stage::Biomes64, 64, 64)));
+
+        let candidates: Vec<u64> = (0..8).map(|i| world_seed as u64 + (i << 50)).collect();
+        let candidates = LowBitsCandidates::new(64, candidates);
+        let mut ckpt = Checkpointer::in_memory(String::new());
+        let seeds = run_stage(
+            Stage::Biomes64,
+            &seed_info,
+            version,
+            Some(candidates),
+            1,
+            &mut ckpt,
+        )
+        .unwrap();
+        assert_eq!(seeds, vec![world_seed as u64]);
+    }
+
+    #[test]
+    fn plan_bedrock_only_uses_biomes() {
+        let mut seed_info = SeedInfo::default();
+        seed_info.version = "1.20.80".to_string();
+        seed_info.edition = Edition::Bedrock;
+        seed_info.positive.slime_chunks = vec![Chunk::new(1, 2)];
+        assert_eq!(available_steps(&seed_info).unwrap(), vec![]);
+        // Map items are only supported in Java Edition
+        seed_info.item_maps = vec![ItemMap::default()];
+        assert_eq!(available_steps(&seed_info).unwrap(), vec![]);
+
+        seed_info
+            .biomes
+            .insert(BiomeId(biome_id::plains), vec![Point { x: 0, z: 0 }]);
+        assert_eq!(
+            available_steps(&seed_info).unwrap(),
+            vec![(Stage::Biomes64, 64, 64)]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Offset-only approximate terrain height for Minecraft 1.18 and later.
+//!
+//! Since 1.18 the shape of the terrain is controlled by splines that depend on the
+//! continentalness, erosion and weirdness climate parameters. Only the offset spline, which
+//! moves the surface up and down, is implemented here. The factor and jaggedness splines and the
+//! 3D noise are not, so the surface height is the height where the terrain density would be 0
+//! without them. This is close to the real surface in plains and oceans, usually a few blocks
+//! away, but mountain peaks, cliffs and overhangs are not generated: there the real surface can
+//! be tens of blocks higher or lower.
+
+use crate::biome_layers::Area;
+use crate::biome_layers::Map;
+use crate::biome_layers::MapGenBiomeNoise3D118;
+use crate::seed_info::MinecraftVersion;
+use crate::spline::Spline;
+use lazy_static::lazy_static;
+use ndarray::Array2;
+use std::rc::Rc;
+
+/// The highest y coordinate of the water in oceans is `SEA_LEVEL - 1`
+pub const SEA_LEVEL: i32 = 63;
+/// Lowest y coordinate of the overworld
+pub const MIN_Y: i32 = -64;
+/// Highest y coordinate of the overworld, exclusive
+pub const MAX_Y: i32 = 320;
+
+lazy_static! {
+    static ref OFFSET_SPLINE: Spline = Spline::new_continental();
+}
+
+/// Terrain parameters of one column
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TerrainShape {
+    /// Offset added to the depth. An offset of 0 means that the surface is at around y = 62
+    pub offset: f32,
+}
+
+impl TerrainShape {
+    /// Height in blocks where the depth is 0. This is the surface of the terrain if the 3D noise
+    /// is ignored.
+    pub fn surface_height(&self) -> f64 {
+        // depth(y) = 1 - y / 128 - 83 / 160 + offset
+        let h = 128.0 * (1.0 - 83.0 / 160.0 + f64::from(self.offset));
+
+        h.clamp(f64::from(MIN_Y), f64::from(MAX_Y - 1))
+    }
+}
+
+/// Approximate terrain height generator for 1.18 and later, using only the offset spline. The
+/// terrain uses the same climate noises as the biomes, so it can share them with a biome
+/// generator of the same seed.
+pub struct OffsetHeightmap118 {
+    climate: Rc<MapGenBiomeNoise3D118>,
+}
+
+impl OffsetHeightmap118 {
+    pub fn new(world_seed: i64, version: MinecraftVersion) -> Self {
+        let climate = MapGenBiomeNoise3D118::new(world_seed, version);
+
+        Self::from_biome_generator(Rc::new(climate))
+    }
+
+    /// Use the climate noises of this biome generator, instead of initializing them again
+    pub fn from_biome_generator(climate: Rc<MapGenBiomeNoise3D118>) -> Self {
+        Self { climate }
+    }
+
+    /// Terrain parameters at this position, using 1:4 coordinates, like the biomes
+    pub fn terrain_shape_4(&self, x: i64, z: i64) -> TerrainShape {
+        let np_param = self.climate.spline_params(x, z);
+
+        TerrainShape {
+            offset: OFFSET_SPLINE.get_spline(&np_param) + 0.015,
+        }
+    }
+
+    /// Surface height at this block, interpolated from the 4 closest points with 1:4 resolution
+    pub fn surface_height(&self, x: i64, z: i64) -> f64 {
+        let (qx, qz) = (x.div_euclid(4), z.div_euclid(4));
+        let h = |dx, dz| self.terrain_shape_4(qx + dx, qz + dz).surface_height();
+
+        bilinear(
+            [[h(0, 0), h(0, 1)], [h(1, 0), h(1, 1)]],
+            x.rem_euclid(4),
+            z.rem_euclid(4),
+        )
+    }
+
+    /// The `y_offset` argument of `biome_layers::generate_up_to_layer` at the surface of this
+    /// block, or at sea level in oceans. Since 1.18 the biomes are 3D, and this is the biome that
+    /// the player sees when looking at a map.
+    pub fn surface_y_offset(&self, x: i64, z: i64) -> u32 {
+        let y = std::cmp::max(self.surface_height(x, z).floor() as i32, SEA_LEVEL);
+
+        ((y - MIN_Y) >> 2) as u32
+    }
+
+    /// Surface height of all the blocks in this area, rounded down
+    pub fn get_map(&self, area: Area) -> Map {
+        // Sample the 1:4 points only once, and interpolate the blocks between them
+        let qx = area.x.div_euclid(4);
+        let qz = area.z.div_euclid(4);
+        let qw = (area.x + area.w as i64 - 1).div_euclid(4) - qx + 2;
+        let qh = (area.z + area.h as i64 - 1).div_euclid(4) - qz + 2;
+        let hq = Array2::from_shape_fn((qw as usize, qh as usize), |(i, j)| {
+            self.terrain_shape_4(qx + i as i64, qz + j as i64)
+                .surface_height()
+        });
+
+        Map::from_area_fn(area, |(i, j)| {
+            let x = area.x + i as i64;
+            let z = area.z + j as i64;
+            let i = (x.div_euclid(4) - qx) as usize;
+            let j = (z.div_euclid(4) - qz) as usize;
+            let corners = [
+                [hq[(i, j)], hq[(i, j + 1)]],
+                [hq[(i + 1, j)], hq[(i + 1, j + 1)]],
+            ];
+
+            bilinear(corners, x.rem_euclid(4), z.rem_euclid(4)).floor() as i32
+        })
+    }
+}
+
+/// Interpolate between the values at the corners of a 4x4 cell
+fn bilinear(corners: [[f64; 2]; 2], dx: i64, dz: i64) -> f64 {
+    let fx = dx as f64 / 4.0;
+    let fz = dz as f64 / 4.0;
+    let h0 = corners[0][0] + (corners[1][0] - corners[0][0]) * fx;
+    let h1 = corners[0][1] + (corners[1][1] - corners[0][1]) * fx;
+
+    h0 + (h1 - h0) * fz
+}
+
+/// Draw a heightmap returned by `OffsetHeightmap118::get_map`. The land is drawn in grayscale, from
+/// dark gray to white, and the water is drawn in blue, darker when deeper.
+pub fn draw_heightmap_image(map: &Map) -> Vec<u8> {
+    let (w, h) = map.a.dim();
+    let mut v = vec![0; w * h * 4];
+    for x in 0..w {
+        for z in 0..h {
+            let y = map.a[(x, z)];
+            let color = if y < SEA_LEVEL - 1 {
+                let depth = (SEA_LEVEL - 1 - y) as f64 / (SEA_LEVEL - MIN_Y) as f64;
+                let b = (255.0 - depth * 191.0) as u8;
+                [0, b / 4, b, 0xFF]
+            } else {
+                let gray = (64 + (y - SEA_LEVEL + 1) * 191 / (MAX_Y - SEA_LEVEL)) as u8;
+                [gray, gray, gray, 0xFF]
+            };
+            let i = (z * w + x) * 4;
+            v[i..i + 4].copy_from_slice(&color);
+        }
+    }
+
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The offset spline is flat at these continentalness values, so the height does not depend
+    // on the erosion and weirdness. The vanilla offsets are 0.044 in mushroom fields, -0.2222 in
+    // deep oceans and -0.12 in oceans, which put the surface a few blocks above sea level and the
+    // ocean floors at y = 35 and y = 48.
+    #[test]
+    fn surface_height_of_flat_areas() {
+        let height = |c: f32| {
+            let offset = OFFSET_SPLINE.get_spline(&[c, 0.3, -0.5, 0.2]) + 0.015;
+            TerrainShape { offset }.surface_height().floor() as i32
+        };
+
+        assert_eq!(height(-1.1), 69);
+        assert_eq!(height(-0.8), 35);
+        assert_eq!(height(-0.3), 48);
+    }
+
+    #[test]
+    fn heightmap_interpolation() {
+        let world_seed = 1234;
+        let g = OffsetHeightmap118::new(world_seed, MinecraftVersion::Java1_18);
+        let area = Area {
+            x: -6,
+            z: 9,
+            w: 13,
+            h: 7,
+        };
+        let map = g.get_map(area);
+        for x in area.x..area.x + area.w as i64 {
+            for z in area.z..area.z + area.h as i64 {
+                assert_eq!(map.get(x, z), g.surface_height(x, z).floor() as i32);
+            }
+        }
+        // Points with 1:4 resolution are not interpolated
+        assert_eq!(
+            g.surface_height(8, 12),
+            g.terrain_shape_4(2, 3).surface_height()
+        );
+    }
+}
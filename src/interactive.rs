@@ -0,0 +1,582 @@
+//! Interactive shell used to build a `SeedInfo` one clue at a time.
+//!
+//! Every change is saved back to the SeedInfo JSON file, so the file can be
+//! used later with the other subcommands. The candidates found by the `find`
+//! command are saved next to it in `<name>.candidates.json`, and the next
+//! search resumes from them, also after restarting the shell. While `find` is
+//! running its progress is saved to `<name>.checkpoint.json`, so a search
+//! stopped with Ctrl-C continues where it left off.
+use crate::biome_info::biome_id;
+use crate::candidates::format_duration;
+use crate::candidates::LowBitsCandidates;
+use crate::checkpoint;
+use crate::checkpoint::Checkpointer;
+use crate::chunk::Chunk;
+use crate::chunk::Point;
+use crate::end_pillars::NUM_PILLARS;
+use crate::find;
+use crate::find::FindError;
+use crate::population::MossyFloor;
+use crate::seed_info::BiomeId;
+use crate::seed_info::Dungeon;
+use crate::seed_info::MinecraftVersion;
+use crate::seed_info::SeedInfo;
+use crate::structures::StructureKind;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// After each change, the candidates are filtered again automatically if the
+/// estimated time of the search is lower than this, in seconds.
+const AUTO_REFILTER_SECONDS: f64 = 1.0;
+
+const HELP: &str = "\
+Commands:
+    help                            Show this help
+    show                            Print the SeedInfo
+    version <version>               Set the Minecraft version, e.g. 1.16
+    seed-hash <hash>                Set the hashed world seed
+    java <yes|no>                   The seed was generated using Java nextLong
+    slime <chunk_x> <chunk_z>       Add a slime chunk
+    not-slime <chunk_x> <chunk_z>   Add a chunk that is not a slime chunk
+    biome <id> <x> <z>              Add a biome at these block coordinates
+    river <x> <z>                   Add a river at these block coordinates
+    pillars <h1> ... <h10>          Set the height of the end pillars
+    dungeon <x> <y> <z> <floor>     Add a dungeon, see the dungeon-seed subcommand
+    structure <kind> <cx> <cz>      Add a structure at these chunk coordinates
+    not-structure <kind> <cx> <cz>  Add a chunk that does not have this structure
+    find                            Start or resume the search
+    candidates                      Show the current candidates
+    reset                           Forget the current candidates and the
+                                    progress of the interrupted search
+    quit                            Exit";
+
+/// Candidates stored in `<name>.candidates.json`
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedCandidates {
+    num_known_bits: u8,
+    seeds: Vec<u64>,
+}
+
+pub struct Repl {
+    pub seed_info: SeedInfo,
+    /// File where the SeedInfo is saved after each change. The candidates and
+    /// the checkpoint are saved in the same directory.
+    path: Option<PathBuf>,
+    pub candidates: Option<LowBitsCandidates>,
+    num_threads: usize,
+}
+
+impl Repl {
+    /// If `path` is set, the SeedInfo will be saved to that file after each
+    /// change, and the candidates saved by a previous session are loaded.
+    pub fn new(seed_info: SeedInfo, path: Option<PathBuf>, num_threads: usize) -> io::Result<Self> {
+        let mut repl = Self {
+            seed_info,
+            path,
+            candidates: None,
+            num_threads,
+        };
+        if let Some(path) = repl.candidates_path().filter(|p| p.exists()) {
+            let file = fs::File::open(path)?;
+            let saved: SavedCandidates = serde_json::from_reader(io::BufReader::new(file))?;
+            repl.candidates = Some(LowBitsCandidates::new(saved.num_known_bits, saved.seeds));
+        }
+
+        Ok(repl)
+    }
+
+    /// Read commands from `input` until the end of the input or until the quit
+    /// command.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, out: &mut W) -> io::Result<()> {
+        writeln!(out, "Type \"help\" to see the list of commands")?;
+        self.print_status(out)?;
+        write!(out, "> ")?;
+        out.flush()?;
+        for line in input.lines() {
+            if !self.execute(&line?, out)? {
+                break;
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Execute one command. Returns false if the shell should exit.
+    pub fn execute<W: Write>(&mut self, line: &str, out: &mut W) -> io::Result<bool> {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match args.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok(true),
+        };
+
+        let changed = match command {
+            "help" => {
+                writeln!(out, "{}", HELP)?;
+                Ok(false)
+            }
+            "show" => {
+                let s = serde_json::to_string_pretty(&self.seed_info).unwrap();
+                writeln!(out, "{}", s)?;
+                self.print_status(out)?;
+                Ok(false)
+            }
+            "quit" | "exit" => return Ok(false),
+            "find" => {
+                self.find(out)?;
+                Ok(false)
+            }
+            "candidates" => {
+                self.print_candidates(out)?;
+                Ok(false)
+            }
+            "reset" => {
+                self.candidates = None;
+                self.save_candidates()?;
+                self.remove_checkpoint()?;
+                self.print_status(out)?;
+                Ok(false)
+            }
+            _ => self.edit(command, args),
+        };
+
+        match changed {
+            Ok(true) => {
+                self.save()?;
+                self.refilter(out)?;
+            }
+            Ok(false) => {}
+            Err(e) => writeln!(out, "Error: {}", e)?,
+        }
+
+        Ok(true)
+    }
+
+    /// Commands that modify the SeedInfo. Returns true if the SeedInfo has
+    /// changed.
+    fn edit(&mut self, command: &str, args: &[&str]) -> Result<bool, String> {
+        let s = &mut self.seed_info;
+        match command {
+            "version" => {
+                let [v] = expect_args(args)?;
+                v.parse::<MinecraftVersion>()?;
+                s.version = v.to_string();
+            }
+            "seed-hash" => {
+                let [h] = expect_args(args)?;
+                s.world_seed_hash = Some(parse(h)?);
+            }
+            "java" => {
+                let [yes] = expect_args(args)?;
+                s.options.not_from_java_next_long = match yes {
+                    "yes" => false,
+                    "no" => true,
+                    _ => return Err(format!("Expected yes or no, got {:?}", yes)),
+                };
+            }
+            "slime" | "not-slime" => {
+                let [x, z] = expect_args(args)?;
+                let chunk = Chunk::new(parse(x)?, parse(z)?);
+                let structures = if command == "slime" {
+                    &mut s.positive
+                } else {
+                    &mut s.negative
+                };
+                push_unique(&mut structures.slime_chunks, chunk);
+            }
+            "biome" | "river" => {
+                let (id, x, z) = if command == "river" {
+                    let [x, z] = expect_args(args)?;
+                    (BiomeId(biome_id::river), x, z)
+                } else {
+                    let [id, x, z] = expect_args(args)?;
+                    (BiomeId(parse(id)?), x, z)
+                };
+                let p = Point {
+                    x: parse(x)?,
+                    z: parse(z)?,
+                };
+                push_unique(s.biomes.entry(id).or_default(), p);
+            }
+            "pillars" => {
+                if args.len() != NUM_PILLARS {
+                    return Err(format!("Expected {} heights", NUM_PILLARS));
+                }
+                s.end_pillars = args.iter().map(|h| parse(h)).collect::<Result<_, _>>()?;
+            }
+            "dungeon" => {
+                let [x, y, z, floor] = expect_args(args)?;
+                MossyFloor::parse(floor)?;
+                s.dungeons.push(Dungeon {
+                    x: parse(x)?,
+                    y: parse(y)?,
+                    z: parse(z)?,
+                    floor: floor.to_string(),
+                });
+            }
+            "structure" | "not-structure" => {
+                let [kind, x, z] = expect_args(args)?;
+                let chunk = Chunk::new(parse(x)?, parse(z)?);
+                let structures = if command == "structure" {
+                    &mut s.positive
+                } else {
+                    &mut s.negative
+                };
+                // Buried treasures are not a region-based structure, but they
+                // are also used by the search
+                let chunks = if kind == "buried-treasure" {
+                    &mut structures.buried_treasures
+                } else {
+                    StructureKind::from_name(kind)
+                        .ok_or_else(|| format!("Unknown structure {:?}", kind))?
+                        .chunks_mut(structures)
+                };
+                push_unique(chunks, chunk);
+            }
+            _ => return Err(format!("Unknown command {:?}, try \"help\"", command)),
+        }
+
+        Ok(true)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        if let Some(path) = &self.path {
+            let s = serde_json::to_string_pretty(&self.seed_info).unwrap();
+            std::fs::write(path, s)?;
+        }
+
+        Ok(())
+    }
+
+    /// `<name>.candidates.json`, next to the SeedInfo file
+    fn candidates_path(&self) -> Option<PathBuf> {
+        self.path
+            .as_deref()
+            .map(|p| p.with_extension("candidates.json"))
+    }
+
+    /// `<name>.checkpoint.json`, next to the SeedInfo file
+    fn checkpoint_path(&self) -> Option<PathBuf> {
+        self.path
+            .as_deref()
+            .map(|p| p.with_extension("checkpoint.json"))
+    }
+
+    /// Save the current candidates, or remove the file if there are none. The
+    /// file is replaced atomically, so it is never left half-written.
+    fn save_candidates(&self) -> io::Result<()> {
+        let path = match self.candidates_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        match &self.candidates {
+            Some(c) => {
+                let saved = SavedCandidates {
+                    num_known_bits: c.num_known_bits,
+                    seeds: c.clone().into_seeds(),
+                };
+                let tmp = path.with_extension("tmp");
+                fs::write(&tmp, serde_json::to_vec(&saved)?)?;
+                fs::rename(&tmp, path)
+            }
+            None => remove_if_exists(&path),
+        }
+    }
+
+    fn remove_checkpoint(&self) -> io::Result<()> {
+        match self.checkpoint_path() {
+            Some(path) => remove_if_exists(&path),
+            None => Ok(()),
+        }
+    }
+
+    /// Continue the interrupted search if it used the same SeedInfo and
+    /// candidates, otherwise start a new one. The checkpoint of a search with
+    /// other clues can never be resumed from this shell, so it is replaced.
+    fn open_checkpoint<W: Write>(&self, out: &mut W) -> io::Result<Checkpointer> {
+        let task = find::task_id(&self.seed_info, self.candidates.as_ref());
+        let path = match self.checkpoint_path() {
+            Some(path) => path,
+            None => return Ok(Checkpointer::in_memory(task)),
+        };
+        match Checkpointer::resume(path.clone(), task.clone()) {
+            Ok(ckpt) => {
+                if !ckpt.state.plan.is_null() {
+                    writeln!(out, "Resuming the interrupted search")?;
+                }
+                Ok(ckpt)
+            }
+            Err(_) => {
+                writeln!(
+                    out,
+                    "The clues have changed since the last search was interrupted, starting a new one"
+                )?;
+                Ok(Checkpointer::create(path, task))
+            }
+        }
+    }
+
+    /// Run the search, starting from the current candidates if any. The
+    /// progress is saved to the checkpoint, so after an interruption the next
+    /// `find` continues the same search.
+    fn find<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        checkpoint::clear_interrupted();
+        let mut ckpt = self.open_checkpoint(out)?;
+        let plan = match find::resumed_plan(&ckpt) {
+            Some(plan) => plan,
+            None => match find::plan(&self.seed_info, self.candidates.as_ref(), self.num_threads) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    writeln!(out, "Error: {}", e)?;
+                    return Ok(());
+                }
+            },
+        };
+        writeln!(out, "Bruteforce plan:\n{}", plan)?;
+        out.flush()?;
+        let r = find::run_plan_with_checkpoint(
+            &self.seed_info,
+            &plan,
+            self.candidates.clone(),
+            self.num_threads,
+            &mut ckpt,
+        );
+        if let Err(FindError::Interrupted) = r {
+            ckpt.save()?;
+            writeln!(
+                out,
+                "Interrupted, the progress has been saved. Type \"find\" to continue the search"
+            )?;
+            return self.print_status(out);
+        }
+        self.remove_checkpoint()?;
+        self.store_result(r, out)?;
+        self.print_candidates(out)
+    }
+
+    /// Filter the current candidates using the new information, if that can
+    /// be done quickly. Slower searches are only started by `find`, because
+    /// they can be interrupted and resumed.
+    fn refilter<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if self.candidates.is_none() {
+            return self.print_status(out);
+        }
+        let plan = match find::plan(&self.seed_info, self.candidates.as_ref(), self.num_threads) {
+            Ok(plan) => plan,
+            Err(_) => return self.print_status(out),
+        };
+        let seconds = plan.estimated_seconds();
+        if seconds >= AUTO_REFILTER_SECONDS {
+            writeln!(
+                out,
+                "Filtering the candidates would take about {}, type \"find\" to start the search",
+                format_duration(seconds)
+            )?;
+            return self.print_status(out);
+        }
+        checkpoint::clear_interrupted();
+        let r = find::run_plan(
+            &self.seed_info,
+            &plan,
+            self.candidates.clone(),
+            self.num_threads,
+        );
+        self.store_result(r, out)?;
+        self.print_status(out)
+    }
+
+    /// Replace the candidates with the result of a search, and save them. On
+    /// error the current candidates are kept.
+    fn store_result<W: Write>(
+        &mut self,
+        r: Result<Vec<i64>, FindError>,
+        out: &mut W,
+    ) -> io::Result<()> {
+        match r {
+            Ok(seeds) => {
+                let seeds = seeds.into_iter().map(|s| s as u64).collect();
+                self.candidates = Some(LowBitsCandidates::new(64, seeds));
+            }
+            Err(FindError::Incomplete(c)) => {
+                self.candidates = Some(c);
+            }
+            Err(e) => {
+                writeln!(out, "Error: {}", e)?;
+                return Ok(());
+            }
+        }
+
+        self.save_candidates()
+    }
+
+    fn print_status<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        match &self.candidates {
+            Some(c) => writeln!(
+                out,
+                "{} candidates with {} known bits",
+                c.len(),
+                c.num_known_bits
+            ),
+            None => writeln!(out, "No search has been started yet"),
+        }
+    }
+
+    fn print_candidates<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        self.print_status(out)?;
+        if let Some(c) = &self.candidates {
+            const MAX_SHOWN: usize = 10;
+            for s in c.patterns.iter().take(MAX_SHOWN) {
+                if c.num_known_bits == 64 {
+                    writeln!(out, "{}", *s as i64)?;
+                } else {
+                    writeln!(out, "{:X}", s)?;
+                }
+            }
+            if c.len() > MAX_SHOWN {
+                writeln!(out, "...")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn expect_args<'a, const N: usize>(args: &[&'a str]) -> Result<[&'a str; N], String> {
+    args.try_into()
+        .map_err(|_| format!("Expected {} arguments, got {}", N, args.len()))
+}
+
+fn parse<T: std::str::FromStr>(x: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    x.parse()
+        .map_err(|e| format!("Invalid number {:?}: {}", x, e))
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn push_unique<T: PartialEq>(v: &mut Vec<T>, x: T) {
+    if !v.contains(&x) {
+        v.push(x);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(repl: &mut Repl, commands: &[&str]) -> String {
+        let mut out = vec![];
+        for c in commands {
+            repl.execute(c, &mut out).unwrap();
+        }
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn edit_seed_info() {
+        let mut repl = Repl::new(SeedInfo::default(), None, 1).unwrap();
+        let out = run(
+            &mut repl,
+            &[
+                "version 1.13",
+                "slime 1 2",
+                "slime 1 2",
+                "not-slime -3 4",
+                "river 100 -20",
+                "structure village 5 6",
+                "not-structure buried-treasure 7 8",
+                "java no",
+            ],
+        );
+        assert!(!out.contains("Error"), "{}", out);
+        let s = &repl.seed_info;
+        assert_eq!(s.version, "1.13");
+        assert_eq!(s.positive.slime_chunks, vec![Chunk::new(1, 2)]);
+        assert_eq!(s.negative.slime_chunks, vec![Chunk::new(-3, 4)]);
+        assert_eq!(
+            s.biomes[&BiomeId(biome_id::river)],
+            vec![Point { x: 100, z: -20 }]
+        );
+        assert_eq!(s.positive.villages, vec![Chunk::new(5, 6)]);
+        assert_eq!(s.negative.buried_treasures, vec![Chunk::new(7, 8)]);
+        assert!(s.options.not_from_java_next_long);
+    }
+
+    #[test]
+    fn invalid_commands() {
+        let mut repl = Repl::new(SeedInfo::default(), None, 1).unwrap();
+        let out = run(
+            &mut repl,
+            &["version 9.99", "slime 1", "structure castle 1 2", "jump"],
+        );
+        assert_eq!(out.matches("Error").count(), 4, "{}", out);
+        assert_eq!(repl.seed_info, SeedInfo::default());
+        let mut out = vec![];
+        assert!(!repl.execute("quit", &mut out).unwrap());
+    }
+
+    #[test]
+    fn refilter_candidates() {
+        let mut repl = Repl::new(SeedInfo::default(), None, 1).unwrap();
+        run(&mut repl, &["version 1.7", "java no"]);
+        repl.candidates = Some(LowBitsCandidates::new(48, (0..1000).collect()));
+        // Chunk (0, 0) is a slime chunk for 10% of the seeds
+        let out = run(&mut repl, &["slime 0 0"]);
+        let n = repl.candidates.as_ref().unwrap().len();
+        assert!(n > 0 && n < 1000, "{}", out);
+        assert!(out.contains(&format!("{} candidates with 48 known bits", n)));
+    }
+
+    #[test]
+    fn slow_refilter_needs_find() {
+        let mut repl = Repl::new(SeedInfo::default(), None, 1).unwrap();
+        run(&mut repl, &["version 1.7", "java no"]);
+        let candidates = LowBitsCandidates::new(48, (0..1 << 20).collect());
+        repl.candidates = Some(candidates.clone());
+        // Checking the biomes of 2^36 seeds is too slow
+        let out = run(&mut repl, &["biome 1 0 0"]);
+        assert!(out.contains("type \"find\""), "{}", out);
+        assert_eq!(repl.candidates, Some(candidates));
+    }
+
+    #[test]
+    fn candidates_are_saved() {
+        let dir = std::env::temp_dir().join(format!(
+            "slime_seed_finder_interactive_test_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("seedinfo.json");
+        let candidates_path = dir.join("seedinfo.candidates.json");
+        let mut repl = Repl::new(SeedInfo::default(), Some(path.clone()), 1).unwrap();
+        run(&mut repl, &["version 1.7", "java no"]);
+        repl.candidates = Some(LowBitsCandidates::new(48, (0..1000).collect()));
+        run(&mut repl, &["slime 0 0"]);
+        assert!(candidates_path.exists());
+
+        // Restart the shell
+        let seed_info = SeedInfo::read(&path).unwrap();
+        let mut restarted = Repl::new(seed_info, Some(path), 1).unwrap();
+        assert_eq!(restarted.seed_info, repl.seed_info);
+        assert_eq!(restarted.candidates, repl.candidates);
+        run(&mut restarted, &["reset"]);
+        assert!(!candidates_path.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -5,6 +5,8 @@ extern crate ndarray;
 
 #[rustfmt::skip]
 pub mod anvil;
+pub mod bedrock;
+pub mod biome_diff;
 #[allow(non_snake_case)]
 #[allow(non_upper_case_globals)]
 #[rustfmt::skip]
@@ -15,13 +17,21 @@ pub mod biome_info_118;
 #[rustfmt::skip]
 pub mod biome_layers;
 pub mod candidates;
+pub mod checkpoint;
 pub mod chunk;
 pub mod climate;
+pub mod dimension_biomes;
+pub mod distributed;
+pub mod end_pillars;
 pub mod fastanvil_ext;
+pub mod find;
 pub mod gen_pairs3;
+pub mod heightmap;
+pub mod interactive;
 pub mod java_rng;
 #[rustfmt::skip]
 pub mod mc_rng;
+pub mod mt19937;
 pub mod multi_spawners;
 pub mod noise_generator;
 pub mod patterns;
@@ -32,6 +42,7 @@ pub mod slime;
 pub mod spline;
 pub mod strict_parse_int;
 pub mod structures;
+pub mod tile_cache;
 pub mod voronoi;
 pub mod weak_alloc;
 pub mod xoroshiro128plusplus;
@@ -6,31 +6,33 @@ use slime_seed_finder::anvil::ZipChunkProvider;
 use slime_seed_finder::biome_info::biome_id;
 use slime_seed_finder::biome_layers;
 use slime_seed_finder::biome_layers::Area;
-use slime_seed_finder::biome_layers::Area3D;
-use slime_seed_finder::biome_layers::Map;
-use slime_seed_finder::biome_layers::Map3D;
+use slime_seed_finder::candidates::LowBitsCandidates;
+use slime_seed_finder::checkpoint::Checkpointer;
 use slime_seed_finder::chunk::Chunk;
 use slime_seed_finder::chunk::Point;
-use slime_seed_finder::chunk::Point3D4;
 use slime_seed_finder::java_rng::JavaRng;
 use slime_seed_finder::population::MossyFloor;
 use slime_seed_finder::seed_info::biomes_from_map;
 use slime_seed_finder::seed_info::BiomeId;
+use slime_seed_finder::seed_info::Dimension;
+use slime_seed_finder::seed_info::Edition;
 use slime_seed_finder::seed_info::MinecraftVersion;
 use slime_seed_finder::seed_info::SeedInfo;
 use slime_seed_finder::slime::generate_slime_chunks_and_not;
-use slime_seed_finder::slime::seed_from_slime_chunks;
-use slime_seed_finder::slime::seed_from_slime_chunks_and_candidates;
+use slime_seed_finder::tile_cache::TileCache;
 use slime_seed_finder::*;
+use std::collections::HashSet;
 use std::convert::{TryFrom, TryInto};
 use std::ffi::OsStr;
 use std::fs;
 use std::fs::File;
+use std::io;
 use std::io::Write;
-use std::ops::ControlFlow;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::thread;
 use std::time::Instant;
 
@@ -93,33 +95,54 @@ enum Opt {
         #[clap(long, default_value = "0")]
         biome_map_z: i64,
         /// Minecraft version to use (Java edition).
-        /// Supported values: from 1.3 to 1.16
+        /// Supported values: from 1.3 to 1.21
         #[clap(long)]
         mc_version: String,
     },
 
+    /// Interactive shell to add clues to a SeedInfo and search for the seed.
+    /// All the changes are saved to the SeedInfo file, and the candidates are
+    /// saved next to it, so the search continues after restarting the shell.
+    /// Press Ctrl-C to stop a search, and type find to continue it.
     #[clap(name = "interactive")]
     Interactive {
-        /// File containing the SeedInfo
+        /// File containing the SeedInfo, will be created if it does not exist.
+        /// By default, seedinfo.json
         #[clap(short = 'i', long, value_parser)]
         input_file: Option<PathBuf>,
+        /// Number of threads to use. By default, same as number of CPUs
+        #[clap(short = 'j', long, default_value = "0")]
+        threads: usize,
     },
 
-    /// Use slime chunks to find the seed. In the future this will use a
-    /// combination of all the methods, but not yet.
+    /// Find the seed using all the information from the SeedInfo: slime
+    /// chunks, rivers, treasure maps, structures, biomes and the seed hash.
+    /// The bruteforce stages are chained automatically.
     #[clap(name = "find")]
     Find {
         /// File containing the SeedInfo
         #[clap(short = 'i', long, value_parser)]
         input_file: PathBuf,
-        /// File containing a JSON array of all the candidate seeds: so instead
-        /// of bruteforcing all the possible seeds we only try the ones from
-        /// this file.
+        /// File containing a JSON array of candidate seeds: so instead of
+        /// bruteforcing all the possible seeds we only try the ones from this
+        /// file.
         #[clap(long, value_parser)]
         candidate_seeds: Option<PathBuf>,
+        /// Number of known lower bits of the candidate seeds, for example 26
+        /// for the candidates found using rivers, or 64 for full seeds.
+        #[clap(long, default_value = "48")]
+        candidate_bits: u8,
         /// Where to write the found seeds as a JSON array
         #[clap(short = 'o', long, value_parser)]
         output_file: Option<PathBuf>,
+        /// Number of threads to use. By default, same as number of CPUs
+        #[clap(short = 'j', long, default_value = "0")]
+        threads: usize,
+        /// Checkpoint file. If it exists, continue the search from there.
+        /// The progress is saved to this file periodically and when pressing
+        /// Ctrl-C.
+        #[clap(long, value_parser)]
+        resume: Option<PathBuf>,
     },
 
     /// Use rivers and biomes to find the seed
@@ -131,6 +154,61 @@ enum Opt {
         /// Where to write the found seeds as a JSON array
         #[clap(short = 'o', long, value_parser)]
         output_file: Option<PathBuf>,
+        /// Checkpoint file. If it exists, continue the search from there.
+        /// The progress is saved to this file periodically and when pressing
+        /// Ctrl-C.
+        #[clap(long, value_parser)]
+        resume: Option<PathBuf>,
+    },
+
+    /// Find the lower 48 bits of the seed using slime chunks with unknown
+    /// coordinates. The slime chunks of the SeedInfo are relative to the
+    /// pattern, which is tried at all the offsets at most --radius chunks away
+    /// from the center.
+    #[clap(name = "slime-pattern")]
+    SlimePattern {
+        /// File containing the SeedInfo
+        #[clap(short = 'i', long, value_parser)]
+        input_file: PathBuf,
+        /// Where to write the found seeds as a JSON array
+        #[clap(short = 'o', long, value_parser)]
+        output_file: Option<PathBuf>,
+        /// Chunk x coordinate of the most likely offset of the pattern
+        #[clap(long, default_value = "0", allow_hyphen_values = true)]
+        center_x: i64,
+        /// Chunk z coordinate of the most likely offset of the pattern
+        #[clap(long, default_value = "0", allow_hyphen_values = true)]
+        center_z: i64,
+        /// Maximum distance in chunks from the center to the offset of the
+        /// pattern. The search time is proportional to (2*radius+1)^2.
+        #[clap(long, default_value = "2")]
+        radius: u64,
+        /// Also try the pattern rotated by 90, 180 and 270 degrees
+        #[clap(long)]
+        rotations: bool,
+        /// Also try the mirror image of the pattern
+        #[clap(long)]
+        mirror: bool,
+        /// Number of threads to use. By default, same as number of CPUs
+        #[clap(short = 'j', long, default_value = "0")]
+        threads: usize,
+        /// Checkpoint file. If it exists, continue the search from there.
+        /// The progress is saved to this file periodically and when pressing
+        /// Ctrl-C.
+        #[clap(long, value_parser)]
+        resume: Option<PathBuf>,
+    },
+
+    /// Use the height of the end pillars to find the lower 32 bits of the
+    /// seed. Only works for Minecraft 1.9 and later.
+    #[clap(name = "end-pillars")]
+    EndPillars {
+        /// File containing the SeedInfo
+        #[clap(short = 'i', long, value_parser)]
+        input_file: PathBuf,
+        /// Where to write the 32-bit candidates, as an array of hex strings
+        #[clap(short = 'o', long, value_parser)]
+        output_file: Option<PathBuf>,
     },
 
     /// Use rivers from an unexplored treasure map to find the seed
@@ -142,6 +220,11 @@ enum Opt {
         /// Where to write the found seeds as a JSON array
         #[clap(short = 'o', long, value_parser)]
         output_file: Option<PathBuf>,
+        /// Checkpoint file. If it exists, continue the search from there.
+        /// The progress is saved to this file periodically and when pressing
+        /// Ctrl-C.
+        #[clap(long, value_parser)]
+        resume: Option<PathBuf>,
     },
 
     #[clap(name = "extend48")]
@@ -185,13 +268,71 @@ enum Opt {
         #[clap(short = 'o', long, value_parser)]
         output_file: Option<PathBuf>,
         /// Minecraft version to use (Java edition).
-        /// Supported values: from 1.3 to 1.16
+        /// Supported values: from 1.3 to 1.21
         #[clap(long)]
         mc_version: String,
         /// The last layer to generate. Defaults to the latest one (full
         /// resolution biome map).
         #[clap(long)]
         last_layer: Option<u32>,
+        /// Dimension to render: overworld, nether or end.
+        #[clap(long, default_value = "overworld")]
+        dimension: String,
+        /// Minecraft edition: java or bedrock. Bedrock is supported starting
+        /// from 1.18, and only in the overworld.
+        #[clap(long, default_value = "java")]
+        edition: String,
+        /// Directory used to cache the generated biomes, so rendering the
+        /// same area again is faster. Only used for the Java Edition
+        /// overworld.
+        #[clap(long, value_parser)]
+        tile_cache: Option<PathBuf>,
+        /// Maximum size of the tile cache, in MiB. The least recently used
+        /// tiles are removed when the cache is full.
+        #[clap(long, default_value = "1024")]
+        tile_cache_size: u64,
+        /// Number of threads to use. By default, same as number of CPUs
+        #[clap(short = 'j', long, default_value = "0")]
+        threads: usize,
+        /// Instead of a single image, write a zoomable tile pyramid to this
+        /// directory, as 256x256 tiles in <z>/<x>/<y>.png. The highest zoom
+        /// level has one pixel per block, and zoom level 0 fits the whole map
+        /// in one tile.
+        #[clap(long, value_parser)]
+        pyramid: Option<PathBuf>,
+    },
+
+    /// Generate a map of the approximate terrain height, using only the offset
+    /// spline. Only supports versions 1.18 and later.
+    #[clap(name = "render-heightmap")]
+    RenderHeightmap {
+        /// The seed for which to generate the heightmap.
+        /// To avoid problems with negative seeds, use the following syntax:
+        /// -s=-1234 or --seed=-1234
+        #[clap(short = 's', long)]
+        seed: i64,
+        /// x position of the top-left coordinate of the map.
+        /// To avoid problems with negative coordinates, use the following
+        /// syntax: -x=-2
+        #[clap(short = 'x', default_value = "0")]
+        x: i64,
+        /// z position of the top-left coordinate of the map
+        #[clap(short = 'z', default_value = "0")]
+        z: i64,
+        /// width
+        #[clap(short = 'w', long, default_value = "1024")]
+        width: u32,
+        /// height
+        #[clap(short = 'h', long, default_value = "640")]
+        height: u32,
+        /// Output filename. Defaults to heightmap_<seed>_x_z_wxh.png.
+        /// Supported image formats: jpeg, png, ico, pnm, bmp and tiff.
+        #[clap(short = 'o', long, value_parser)]
+        output_file: Option<PathBuf>,
+        /// Minecraft version to use (Java edition).
+        /// Supported values: from 1.18 to 1.21
+        #[clap(long)]
+        mc_version: String,
     },
 
     /// Generate an unexplored treasure map, but without the treasure marker.
@@ -218,7 +359,7 @@ enum Opt {
         #[clap(short = 'o', long, value_parser)]
         output_file: Option<PathBuf>,
         /// Minecraft version to use (Java edition).
-        /// Supported values: from 1.3 to 1.16
+        /// Supported values: from 1.3 to 1.21
         #[clap(long)]
         mc_version: String,
     },
@@ -266,9 +407,14 @@ enum Opt {
         #[clap(long, default_value = "0")]
         center_z: i64,
         /// Minecraft version to use (Java edition).
-        /// Supported values: from 1.3 to 1.16
+        /// Supported values: from 1.3 to 1.21
         #[clap(long)]
         mc_version: String,
+        /// File containing a JSON array of 64-bit candidate seeds. Required
+        /// since 1.18, because the biomes can only be used to filter
+        /// candidates, for example the ones found using the slime chunks.
+        #[clap(long, value_parser)]
+        candidate_seeds: Option<PathBuf>,
     },
 
     /// Bruteforce world seed hash
@@ -288,6 +434,11 @@ enum Opt {
         /// Path to file containing a list of 26-bit candidates
         #[clap(long)]
         candidates_file: Option<PathBuf>,
+        /// Checkpoint file. If it exists, continue the search from there.
+        /// The progress is saved to this file periodically and when pressing
+        /// Ctrl-C.
+        #[clap(long, value_parser)]
+        resume: Option<PathBuf>,
     },
 
     /// Given a seed, calculate the seed hash
@@ -335,9 +486,24 @@ enum Opt {
         ///
         #[clap(short = 'f', long)]
         floor: String,
+        /// Minecraft version used to generate the dungeon (Java edition). Since 1.18 the dungeon
+        /// seed has 64 bits and cannot be bruteforced using the floor alone, so
+        /// --candidate-seeds is also needed. By default, a version before 1.18.
+        #[clap(long)]
+        mc_version: Option<String>,
+        /// File containing a JSON array of candidates for the lower 48 bits of the world seed,
+        /// found for example using slime chunks. Only used since 1.18, where the upper 16 bits of
+        /// each candidate are bruteforced to find the dungeon seed.
+        #[clap(long, value_parser)]
+        candidate_seeds: Option<PathBuf>,
         /// Number of threads to use. By default, same as number of CPUs
         #[clap(short = 'j', long, default_value = "0")]
         threads: usize,
+        /// Checkpoint file. If it exists, continue the search from there.
+        /// The progress is saved to this file periodically and when pressing
+        /// Ctrl-C.
+        #[clap(long, value_parser)]
+        resume: Option<PathBuf>,
     },
 
     /// Given 3 dungeon seeds in the format "159,23,-290,982513219448", find the world seed.
@@ -355,9 +521,19 @@ enum Opt {
         /// --resume-l 128` to continue the bruteforce.
         #[clap(long = "resume-l")]
         resume_l: Option<u32>,
+        /// Minecraft version used to generate the dungeons (Java edition). Since 1.18 the dungeon
+        /// seeds are decoration seeds, which are reversed directly without a bruteforce. By
+        /// default, a version before 1.13.
+        #[clap(long)]
+        mc_version: Option<String>,
         /// Number of threads to use. By default, same as number of CPUs
         #[clap(short = 'j', long, default_value = "0")]
         threads: usize,
+        /// Checkpoint file. If it exists, continue the search from there.
+        /// The progress is saved to this file periodically and when pressing
+        /// Ctrl-C. When resuming, the limit of steps back can be increased.
+        #[clap(long, value_parser)]
+        resume: Option<PathBuf>,
         dungeon_seeds: Vec<String>,
     },
 
@@ -379,19 +555,24 @@ enum Opt {
     },
 
     /// Read a minecraft world, read its seed, generate biome map using the
-    /// same seed, and compare both worlds
+    /// same seed, and compare both worlds. Prints the list of mismatches
+    /// and exits with an error if any biome does not match.
     #[clap(name = "test-generation")]
     TestGeneration {
         /// Path to "minecraft_saved_world.zip"
         #[clap(short = 'i', long, value_parser)]
         input_zip: PathBuf,
         /// Minecraft version to use (Java edition).
-        /// Supported values: from 1.3 to 1.16
+        /// Supported values: from 1.3 to 1.21
         #[clap(long)]
         mc_version: String,
         /// Render biome map from the biomes according to the saved world
         #[clap(long)]
         draw_biome_map: bool,
+        /// Render biome map from the biomes according to the saved world, highlighting the
+        /// biomes that do not match the generated biomes
+        #[clap(long)]
+        draw_diff: bool,
     },
 
     /// Read a minecraft world and find all the already generated dungeons
@@ -406,6 +587,54 @@ enum Opt {
         mc_version: String,
     },
 
+    /// Read the map items of a minecraft world and add them to a SeedInfo file. Only the maps
+    /// from the overworld are used. The land and the deep water of each map will be compared with
+    /// the biomes when using the find command.
+    #[clap(name = "read-maps")]
+    ReadMaps {
+        /// Path to "minecraft_saved_world.zip", or to a folder containing the map_<id>.dat files,
+        /// usually "minecraft_saved_world/data"
+        #[clap(short = 'i', long, value_parser)]
+        input: PathBuf,
+        /// SeedInfo file. If it exists, the maps are added to it, otherwise it is created
+        #[clap(short = 'o', long, value_parser)]
+        output_file: PathBuf,
+        /// Minecraft version used to generate the world, only needed when creating a new SeedInfo
+        #[clap(long)]
+        mc_version: Option<String>,
+    },
+
+    /// Read a minecraft world, extract the floor of all the dungeons, and find the world seed
+    /// using the same steps as dungeon-seed and dungeon-seed-to-world-seed.
+    ///
+    /// The world must be saved using minecraft 1.13 or later, but the search only works if the
+    /// dungeons were generated before 1.13. For newer versions, the dungeons are printed in the
+    /// seedInfo format, to be used with the find command.
+    #[clap(name = "world-dungeon-seed")]
+    WorldDungeonSeed {
+        /// Path to "minecraft_saved_world.zip"
+        #[clap(short = 'i', long, value_parser)]
+        input_zip: PathBuf,
+        /// Minecraft version used to generate the dungeons (Java edition).
+        #[clap(long)]
+        mc_version: String,
+        /// Maximum number of dungeons to bruteforce. Each dungeon takes around 240 CPU-minutes,
+        /// and the search stops once 3 dungeon seeds have been found.
+        #[clap(long, default_value = "5")]
+        max_dungeons: usize,
+        /// Maximum number of calls to rng.previous(), see dungeon-seed-to-world-seed.
+        #[clap(short = 'l', long, default_value = "128")]
+        limit_steps_back: u32,
+        /// Number of threads to use. By default, same as number of CPUs
+        #[clap(short = 'j', long, default_value = "0")]
+        threads: usize,
+        /// Checkpoint file. If it exists, continue the search from there.
+        /// The progress is saved to this file periodically and when pressing
+        /// Ctrl-C.
+        #[clap(long, value_parser)]
+        resume: Option<PathBuf>,
+    },
+
     /// Read a list of candidate seeds from a file and a list of biomes from a seedInfo and write
     /// the matching seeds to a file
     #[clap(name = "filter-biomes")]
@@ -441,6 +670,91 @@ enum Opt {
         #[clap(short = 'i', long, value_parser)]
         input_zip: PathBuf,
     },
+
+    /// Split a bruteforce search into work units and hand them out to `work`
+    /// processes, which can run on other machines. Work units that are not
+    /// finished before the timeout are given to another worker.
+    #[clap(name = "serve")]
+    Serve {
+        /// Address to listen on. Use 0.0.0.0:7878 to accept workers from
+        /// other machines.
+        #[clap(long, default_value = "127.0.0.1:7878")]
+        bind: String,
+        /// Seconds after which an unfinished work unit is given to another
+        /// worker
+        #[clap(long, default_value = "600")]
+        timeout: u64,
+        /// Size of each work unit. By default, each work unit takes a few
+        /// seconds.
+        #[clap(long)]
+        block_size: Option<u64>,
+        /// Where to write the found seeds as a JSON array
+        #[clap(short = 'o', long, value_parser)]
+        output_file: Option<PathBuf>,
+        /// Checkpoint file. If it exists, continue the search from there.
+        /// The progress is saved to this file periodically and when pressing
+        /// Ctrl-C.
+        #[clap(long, value_parser)]
+        resume: Option<PathBuf>,
+        #[clap(subcommand)]
+        job: ServeJob,
+    },
+
+    /// Connect to a `serve` process and process work units until the search
+    /// is finished
+    #[clap(name = "work")]
+    Work {
+        /// Address of the `serve` process
+        #[clap(long, default_value = "127.0.0.1:7878")]
+        server: String,
+        /// Number of threads to use. By default, same as number of CPUs
+        #[clap(short = 'j', long, default_value = "0")]
+        threads: usize,
+    },
+}
+
+/// Bruteforce searches that can be split between many workers
+#[derive(clap::Subcommand, Debug)]
+#[clap(rename_all = "kebab-case")]
+enum ServeJob {
+    /// Find the lower 48 bits of the seed using slime chunks
+    #[clap(name = "slime")]
+    Slime {
+        /// File containing the SeedInfo
+        #[clap(short = 'i', long, value_parser)]
+        input_file: PathBuf,
+    },
+    /// Use rivers and biomes to find the seed
+    #[clap(name = "rivers")]
+    Rivers {
+        /// File containing the SeedInfo
+        #[clap(short = 'i', long, value_parser)]
+        input_file: PathBuf,
+    },
+    /// Bruteforce world seed hash
+    #[clap(name = "seed-hash")]
+    SeedHash {
+        /// Seed hash as 64-bit signed integer.
+        /// To avoid problems with negative seeds, use the following
+        /// syntax: --seed-hash=-2
+        #[clap(long)]
+        seed_hash: i64,
+        /// The seed was not generated using Java Random nextLong
+        #[clap(long)]
+        seed_not_from_java_next_long: bool,
+        /// Path to file containing a list of 26-bit candidates
+        #[clap(long)]
+        candidates_file: PathBuf,
+    },
+    /// Given 3 dungeon seeds in the format "159,23,-290,982513219448", find
+    /// the world seed
+    #[clap(name = "dungeon")]
+    Dungeon {
+        /// Maximum number of calls to rng.previous()
+        #[clap(short = 'l', long, default_value = "128")]
+        limit_steps_back: u32,
+        dungeon_seeds: Vec<String>,
+    },
 }
 
 fn main() {
@@ -508,67 +822,145 @@ fn main() {
                 write!(w, "{}", buf).unwrap();
             }
         }
-        Opt::Interactive { .. } => {
-            println!("Some day you will be able to specify all the options here interactively");
-            println!("But not today, sorry");
-            unimplemented!()
+        Opt::Interactive {
+            input_file,
+            threads,
+        } => {
+            let num_threads = if threads == 0 {
+                num_cpus::get()
+            } else {
+                threads
+            };
+            let input_file = input_file.unwrap_or_else(|| PathBuf::from("seedinfo.json"));
+            let seed_info = if input_file.exists() {
+                match SeedInfo::read(&input_file) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        error!("Error reading seed info: {:?}", e);
+                        return;
+                    }
+                }
+            } else {
+                println!("Creating new SeedInfo file {}", input_file.display());
+                SeedInfo::default()
+            };
+            let mut repl = match interactive::Repl::new(seed_info, Some(input_file), num_threads) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Error reading candidates: {}", e);
+                    return;
+                }
+            };
+            set_ctrlc_handler();
+            let stdin = std::io::stdin();
+            if let Err(e) = repl.run(stdin.lock(), &mut std::io::stdout()) {
+                error!("Error in interactive shell: {}", e);
+            }
         }
 
         Opt::Find {
             input_file,
             candidate_seeds,
+            candidate_bits,
             output_file,
+            threads,
+            resume,
         } => {
-            let seed_info = SeedInfo::read(input_file).expect("Error reading seed info");
-            let c = seed_info.positive.slime_chunks;
-            let nc = seed_info.negative.slime_chunks;
-            let false_c = seed_info.options.error_margin_slime_chunks as usize;
-            let false_nc = seed_info.options.error_margin_slime_chunks_negative as usize;
-
-            if c.is_empty() && nc.is_empty() {
-                // Can't find seed without slime chunks
-                println!("Not enough slime chunks");
+            if candidate_bits == 0 || candidate_bits > 64 {
+                error!(
+                    "Invalid --candidate-bits {}, expected 1 to 64",
+                    candidate_bits
+                );
                 return;
             }
+            let seed_info = match SeedInfo::read(input_file) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Error reading seed info: {:?}", e);
+                    return;
+                }
+            };
+            let candidates = match candidate_seeds.map(read_seeds_from_file).transpose() {
+                Ok(seeds) => seeds.map(|seeds| LowBitsCandidates::new(candidate_bits, seeds)),
+                Err(e) => {
+                    error!("Error reading candidates: {}", e);
+                    return;
+                }
+            };
+            let num_threads = if threads == 0 {
+                num_cpus::get()
+            } else {
+                threads
+            };
 
-            // All possible 48 bit seeds
-            let seeds = if let Some(path) = candidate_seeds {
-                let candidates = read_seeds_from_file(path).expect("Error reading candidates");
-                seed_from_slime_chunks_and_candidates(&c, false_c, &nc, false_nc, candidates)
+            let task = find::task_id(&seed_info, candidates.as_ref());
+            let keep_checkpoint = resume.is_some();
+            let mut ckpt = open_checkpoint(resume, "find", task);
+            let plan = if let Some(plan) = find::resumed_plan(&ckpt) {
+                plan
             } else {
-                seed_from_slime_chunks(&c, false_c, &nc, false_nc)
+                match find::plan(&seed_info, candidates.as_ref(), num_threads) {
+                    Ok(plan) => plan,
+                    Err(e) => {
+                        println!("{}", e);
+                        return;
+                    }
+                }
             };
+            println!("Bruteforce plan:\n{}", plan);
+
+            let r = find::run_plan_with_checkpoint(
+                &seed_info,
+                &plan,
+                candidates,
+                num_threads,
+                &mut ckpt,
+            );
+            if let Err(find::FindError::Interrupted) = r {
+                print_interrupted(ckpt.path());
+                return;
+            }
+            close_checkpoint(&mut ckpt, keep_checkpoint);
+            let seeds = match r {
+                Ok(seeds) => seeds,
+                Err(find::FindError::Incomplete(partial)) => {
+                    let num_known_bits = partial.num_known_bits;
+                    let seeds = partial.into_seeds();
+                    println!(
+                        "Found {} {}-bit seeds:\n{}",
+                        seeds.len(),
+                        num_known_bits,
+                        serde_json::to_string(&seeds).unwrap()
+                    );
+                    println!("Not enough information to find the remaining bits");
+                    let seeds: Vec<i64> = seeds.into_iter().map(|x| x as i64).collect();
+                    if !seed_info.slime_evidence.is_empty() {
+                        print_slime_evidence_ranking(&seed_info, &seeds);
+                    }
+                    if let Some(of) = output_file {
+                        if let Err(e) = write_seeds_to_file(&seeds, of) {
+                            error!("Error writing seeds to file: {}", e);
+                        }
+                    }
+                    return;
+                }
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+
             println!(
-                "Found {} 48-bit seeds:\n{}",
+                "Found {} 64-bit seeds:\n{}",
                 seeds.len(),
                 serde_json::to_string(&seeds).unwrap()
             );
-
-            let java = !seed_info.options.not_from_java_next_long;
-
-            if java {
-                // Display only 64 bit seeds that could be generated by java
-                // (when the seed box is left empty)
-                let mut java_seeds: Vec<i64> = seeds
-                    .iter()
-                    .flat_map(|&s| JavaRng::extend_long_48(s))
-                    .map(|s| s as i64)
-                    .collect();
-
-                java_seeds.sort_unstable();
-                println!("Java seeds: {}\n{:#?}", java_seeds.len(), java_seeds);
-                if let Some(of) = output_file {
-                    // TODO: proper error handling
-                    write_seeds_to_file(&java_seeds, of).expect("Error writing seeds to file");
-                }
-            } else {
-                if let Some(of) = output_file {
-                    // TODO: proper error handling
-                    write_seeds_to_file(
-                        &seeds.into_iter().map(|x| x as i64).collect::<Vec<_>>(),
-                        of,
-                    )
-                    .expect("Error writing seeds to file");
+            if !seed_info.slime_evidence.is_empty() {
+                print_slime_evidence_ranking(&seed_info, &seeds);
+            }
+            if let Some(of) = output_file {
+                if let Err(e) = write_seeds_to_file(&seeds, of) {
+                    error!("Error writing seeds to file: {}", e);
                 }
             }
         }
@@ -576,10 +968,10 @@ fn main() {
         Opt::Rivers {
             input_file,
             output_file,
+            resume,
         } => {
             let seed_info = SeedInfo::read(input_file).expect("Error reading seed info");
             let version = seed_info.version.parse().unwrap();
-            // TODO: integrate the river seed finder into the "find" subcommand
             let extra_biomes: Vec<_> = seed_info
                 .biomes
                 .iter()
@@ -594,7 +986,30 @@ fn main() {
 
             // All possible 64 bit seeds
             let seeds = if let Some(rivers) = seed_info.biomes.get(&BiomeId(biome_id::river)) {
-                biome_layers::river_seed_finder(rivers, &extra_biomes, version)
+                let task = checkpoint::task_id("rivers", &seed_info);
+                let keep_checkpoint = resume.is_some();
+                let mut ckpt = open_checkpoint(resume, "rivers", task);
+                let r = ckpt.run_range(1, 1 << 24, 1 << 8, |lo, hi| {
+                    biome_layers::river_seed_finder_range(
+                        rivers,
+                        &extra_biomes,
+                        version,
+                        lo as u32,
+                        hi as u32,
+                    )
+                    .into_iter()
+                    .map(|s| s as u64)
+                    .collect()
+                });
+                let seeds = match r {
+                    Ok(seeds) => seeds,
+                    Err(_) => {
+                        print_interrupted(ckpt.path());
+                        return;
+                    }
+                };
+                close_checkpoint(&mut ckpt, keep_checkpoint);
+                seeds.into_iter().map(|s| s as i64).collect()
             } else {
                 error!("No rivers in seedInfo");
                 vec![]
@@ -611,61 +1026,182 @@ fn main() {
             }
         }
 
-        Opt::TreasureRivers {
+        Opt::SlimePattern {
             input_file,
             output_file,
+            center_x,
+            center_z,
+            radius,
+            rotations,
+            mirror,
+            threads,
+            resume,
         } => {
-            let seed_info = SeedInfo::read(input_file).expect("Error reading seed info");
-            let version = seed_info.version.parse().unwrap();
-            // TODO: integrate the treasure map river seed finder into the "find" subcommand
-            let first_treasure_map = &seed_info.treasure_maps[0];
-
-            let mut pmap = Map::new(Area {
-                x: (-64 + 256 * first_treasure_map.fragment_x) / 2,
-                z: (-64 + 256 * first_treasure_map.fragment_z) / 2,
-                w: 128,
-                h: 128,
-            });
-            for (i, v) in first_treasure_map.map.iter().enumerate() {
-                let (x, z) = (i % 128, i / 128);
-                pmap.a[(x, z)] = match v {
-                    0 => biome_id::ocean,
-                    1 => biome_id::plains,
-                    2 => biome_id::river,
-                    // Unknown biome
-                    255 => 255,
-                    _ => panic!("Invalid id: {}", v),
-                };
-            }
-
-            // All possible 26 bit seeds
-            let seeds = biome_layers::treasure_map_river_seed_finder(&pmap, version, 0, 1 << 24);
+            let seed_info = match SeedInfo::read(input_file) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Error reading seed info: {:?}", e);
+                    return;
+                }
+            };
+            let num_threads = if threads == 0 {
+                num_cpus::get()
+            } else {
+                threads
+            };
+            let window = Area {
+                x: center_x - radius as i64,
+                z: center_z - radius as i64,
+                w: 2 * radius + 1,
+                h: 2 * radius + 1,
+            };
+            let transforms = slime::ChunkTransform::all(rotations, mirror);
+            let pattern = find::slime_chunks_pattern(&seed_info, window, transforms);
             println!(
-                "Found {} 26-bit seeds:\n{}",
-                seeds.len(),
-                serde_json::to_string(&seeds).unwrap()
+                "Trying {} positions, {} 18-bit candidates",
+                pattern.num_placements(),
+                pattern.num_low_18_candidates()
             );
 
+            let task =
+                checkpoint::task_id("slime-pattern", &(&seed_info, window, rotations, mirror));
+            let keep_checkpoint = resume.is_some();
+            let mut ckpt = open_checkpoint(resume, "slime-pattern", task);
+            let r = ckpt.run_range(num_threads, 1 << 30, 1 << 12, |lo, hi| {
+                pattern
+                    .find_seed_range(lo as u32, (hi - lo) as u32)
+                    .into_iter()
+                    .map(|m| m.seed)
+                    .collect()
+            });
+            let mut seeds = match r {
+                Ok(seeds) => seeds,
+                Err(_) => {
+                    print_interrupted(ckpt.path());
+                    return;
+                }
+            };
+            close_checkpoint(&mut ckpt, keep_checkpoint);
+            // The same seed can match at more than one position
+            seeds.dedup();
+
+            println!("Found {} 48-bit seeds:", seeds.len());
+            for &seed in &seeds {
+                for m in pattern.try_seed(seed) {
+                    println!(
+                        "{} at chunk offset ({}, {}), mirror: {}, rotation: {}",
+                        seed, m.offset.x, m.offset.z, m.transform.mirror, m.transform.rotation
+                    );
+                }
+            }
+
             if let Some(of) = output_file {
-                write_seeds_to_file(&seeds, of).expect("Error writing seeds to file");
+                let seeds: Vec<i64> = seeds.into_iter().map(|s| s as i64).collect();
+                if let Err(e) = write_seeds_to_file(&seeds, of) {
+                    error!("Error writing seeds to file: {}", e);
+                }
             }
         }
 
-        Opt::Extend48 {
+        Opt::EndPillars {
             input_file,
             output_file,
         } => {
-            let seeds = read_seeds_from_file(input_file).expect("Error reading input file");
-            let mut r = vec![];
-            for s in seeds {
-                if !(s < (1u64 << 48)) {
-                    panic!("Input must be lower than 2^48");
-                };
-
-                let rr = JavaRng::extend_long_48(s);
-                r.extend(rr.into_iter().map(|seed| seed as i64));
-            }
-
+            let seed_info = match SeedInfo::read(input_file) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Error reading seed info: {:?}", e);
+                    return;
+                }
+            };
+            let version: MinecraftVersion = match seed_info.version.parse() {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Invalid version {:?}", e);
+                    return;
+                }
+            };
+            if version < MinecraftVersion::Java1_9 {
+                error!("End pillars are random before 1.9");
+                return;
+            }
+
+            let pillar_seeds = end_pillars::pillar_seeds_from_heights(&seed_info.end_pillars);
+            println!(
+                "Found {} 16-bit pillar seeds:\n{}",
+                pillar_seeds.len(),
+                serde_json::to_string(&pillar_seeds).unwrap()
+            );
+
+            let seeds: Vec<String> =
+                end_pillars::low_bits_candidates_from_heights(&seed_info.end_pillars)
+                    .into_seeds()
+                    .into_iter()
+                    .map(|seed| format!("{:08X}", seed))
+                    .collect();
+            println!("Found {} 32-bit candidates", seeds.len());
+
+            if let Some(of) = output_file {
+                if let Err(e) = write_candidates_to_file(&seeds, of) {
+                    error!("Error writing seeds to file: {}", e);
+                }
+            }
+        }
+
+        Opt::TreasureRivers {
+            input_file,
+            output_file,
+            resume,
+        } => {
+            let seed_info = SeedInfo::read(input_file).expect("Error reading seed info");
+            let version = seed_info.version.parse().unwrap();
+            let pmap = seed_info.treasure_maps[0].to_map();
+
+            // All possible 26 bit seeds
+            let task = checkpoint::task_id("treasure-rivers", &seed_info);
+            let keep_checkpoint = resume.is_some();
+            let mut ckpt = open_checkpoint(resume, "treasure-rivers", task);
+            let r = ckpt.run_range(1, 1 << 24, 1 << 8, |lo, hi| {
+                biome_layers::treasure_map_river_seed_finder(&pmap, version, lo as u32, hi as u32)
+                    .into_iter()
+                    .map(|s| s as u64)
+                    .collect()
+            });
+            let seeds = match r {
+                Ok(seeds) => seeds,
+                Err(_) => {
+                    print_interrupted(ckpt.path());
+                    return;
+                }
+            };
+            close_checkpoint(&mut ckpt, keep_checkpoint);
+            let seeds: Vec<i64> = seeds.into_iter().map(|s| s as i64).collect();
+            println!(
+                "Found {} 26-bit seeds:\n{}",
+                seeds.len(),
+                serde_json::to_string(&seeds).unwrap()
+            );
+
+            if let Some(of) = output_file {
+                write_seeds_to_file(&seeds, of).expect("Error writing seeds to file");
+            }
+        }
+
+        Opt::Extend48 {
+            input_file,
+            output_file,
+        } => {
+            let seeds = read_seeds_from_file(input_file).expect("Error reading input file");
+            let mut r = vec![];
+            for s in seeds {
+                if !(s < (1u64 << 48)) {
+                    panic!("Input must be lower than 2^48");
+                };
+
+                let rr = JavaRng::extend_long_48(s);
+                r.extend(rr.into_iter().map(|seed| seed as i64));
+            }
+
             println!("{}", serde_json::to_string_pretty(&r).unwrap());
 
             if let Some(output_file) = output_file {
@@ -683,16 +1219,61 @@ fn main() {
             output_file,
             mc_version,
             last_layer,
+            dimension,
+            edition,
+            tile_cache,
+            tile_cache_size,
+            threads,
+            pyramid,
         } => {
+            let tile_cache = tile_cache.map(|dir| {
+                TileCache::on_disk(dir, tile_cache_size << 20).expect("Error opening tile cache")
+            });
+            let dimension: Dimension = match dimension.parse() {
+                Ok(x) => x,
+                Err(e) => {
+                    error!(
+                        "Invalid dimension {:?}, expected overworld, nether or end",
+                        e
+                    );
+                    return;
+                }
+            };
+            let edition: Edition = match edition.parse() {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Invalid edition {:?}, expected java or bedrock", e);
+                    return;
+                }
+            };
+            if edition == Edition::Bedrock && dimension != Dimension::Overworld {
+                error!("Only the overworld is supported in Bedrock Edition");
+                return;
+            }
             let output_file = output_file.unwrap_or_else(|| {
+                let prefix = match (edition, dimension) {
+                    (Edition::Bedrock, _) => "biome_map_bedrock",
+                    (_, Dimension::Overworld) => "biome_map",
+                    (_, Dimension::Nether) => "biome_map_nether",
+                    (_, Dimension::End) => "biome_map_end",
+                };
                 format!(
-                    "biome_map_{}_{}_{}_{}_{}_{}x{}.png",
-                    mc_version, seed, x, y, z, width, height
+                    "{}_{}_{}_{}_{}_{}_{}x{}.png",
+                    prefix, mc_version, seed, x, y, z, width, height
                 )
                 .into()
             });
-            let version: MinecraftVersion = mc_version.parse().unwrap();
-            let last_layer = last_layer.unwrap_or_else(|| version.num_layers());
+            let version = match MinecraftVersion::parse_edition(&mc_version, edition) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Invalid version {:?} for {:?} Edition", e, edition);
+                    return;
+                }
+            };
+            let last_layer = last_layer.unwrap_or_else(|| match dimension {
+                Dimension::Overworld => version.num_layers(),
+                _ => dimension_biomes::NUM_LAYERS,
+            });
             let area = Area {
                 x,
                 z,
@@ -701,17 +1282,119 @@ fn main() {
             };
             let y_offset = u32::try_from((64 + y) >> 2)
                 .expect("invalid y level. Valid values are from -64 to 319");
-            let vec_rgba =
-                biome_layers::generate_image_up_to_layer(version, area, seed, last_layer, y_offset);
-            assert_eq!(vec_rgba.len(), (width * height * 4) as usize);
-            image::save_buffer(
+            let num_threads = if threads == 0 {
+                num_cpus::get()
+            } else {
+                threads
+            };
+            let render = |area| match (edition, &tile_cache) {
+                (Edition::Java, Some(cache)) if dimension == Dimension::Overworld => {
+                    cache.generate_image_up_to_layer(version, area, seed, last_layer, y_offset)
+                }
+                (Edition::Java, _) => dimension_biomes::generate_image_up_to_layer_dimension(
+                    dimension, version, area, seed, last_layer, y_offset,
+                ),
+                (Edition::Bedrock, _) => {
+                    bedrock::generate_image_up_to_layer(version, area, seed, last_layer, y_offset)
+                }
+            };
+
+            if let Some(dir) = pyramid {
+                let mut p = TilePyramid::new(dir.clone(), width as usize, height as usize);
+                render_rows(area, num_threads, render, |row| p.push_row(row))
+                    .and_then(|()| p.finish())
+                    .expect("Error writing tile pyramid");
+                println!(
+                    "Saved {} zoom levels to {}",
+                    p.num_zoom_levels(),
+                    dir.display()
+                );
+                return;
+            }
+
+            let is_png = output_file
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("png"));
+            if is_png {
+                // Write the image row by row, to support images that do not fit
+                // in memory
+                let w = std::io::BufWriter::new(
+                    File::create(&output_file).expect("Error creating output file"),
+                );
+                let mut encoder = png::Encoder::new(w, width, height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                let mut writer = encoder
+                    .write_header()
+                    .and_then(|w| w.into_stream_writer())
+                    .expect("Error writing png");
+                render_rows(area, num_threads, render, |row| writer.write_all(row))
+                    .expect("Error writing png");
+                writer.finish().expect("Error writing png");
+            } else {
+                let mut vec_rgba = Vec::with_capacity((width * height * 4) as usize);
+                render_rows(area, num_threads, render, |row| {
+                    vec_rgba.extend_from_slice(row);
+                    Ok(())
+                })
+                .unwrap();
+                image::save_buffer(
+                    output_file.clone(),
+                    &vec_rgba,
+                    width,
+                    height,
+                    image::ColorType::Rgba8,
+                )
+                .unwrap();
+            }
+            println!("Saved image to {}", output_file.to_string_lossy());
+        }
+
+        Opt::RenderHeightmap {
+            seed,
+            x,
+            z,
+            width,
+            height,
+            output_file,
+            mc_version,
+        } => {
+            let version: MinecraftVersion = match mc_version.parse() {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Invalid version {:?}", e);
+                    return;
+                }
+            };
+            if version < MinecraftVersion::Java1_18 {
+                error!("The heightmap is only supported since 1.18");
+                return;
+            }
+            let output_file = output_file.unwrap_or_else(|| {
+                format!(
+                    "heightmap_{}_{}_{}_{}_{}x{}.png",
+                    mc_version, seed, x, z, width, height
+                )
+                .into()
+            });
+            let area = Area {
+                x,
+                z,
+                w: width as u64,
+                h: height as u64,
+            };
+            let map = heightmap::OffsetHeightmap118::new(seed, version).get_map(area);
+            let vec_rgba = heightmap::draw_heightmap_image(&map);
+            if let Err(e) = image::save_buffer(
                 output_file.clone(),
                 &vec_rgba,
                 width,
                 height,
                 image::ColorType::Rgba8,
-            )
-            .unwrap();
+            ) {
+                error!("Error saving image: {}", e);
+                return;
+            }
             println!("Saved image to {}", output_file.to_string_lossy());
         }
 
@@ -818,9 +1501,63 @@ fn main() {
             center_x,
             center_z,
             mc_version,
+            candidate_seeds,
         } => {
             let version = mc_version.parse().unwrap();
 
+            if version >= MinecraftVersion::Java1_18 {
+                let candidate_seeds = match candidate_seeds {
+                    Some(x) => x,
+                    None => {
+                        println!("Since 1.18, the biomes can only be used to filter candidates. Please provide a list of 64-bit seeds using --candidate-seeds");
+                        return;
+                    }
+                };
+                let candidates =
+                    read_seeds_from_file_i64(candidate_seeds).expect("Error reading candidates");
+                let biomes = anvil::get_some_biomes_zip_1_18(
+                    &input_zip,
+                    Point {
+                        x: center_x,
+                        z: center_z,
+                    },
+                );
+                println!("Using {} biomes to filter the candidates", biomes.len());
+                let num_threads = if threads == 0 {
+                    num_cpus::get()
+                } else {
+                    threads
+                };
+                let candidates = Arc::new(candidates);
+                let biomes = Arc::new(biomes);
+                let thread_range = candidates.len().div_ceil(num_threads);
+
+                let seeds: Vec<String> = run_threads(num_threads, move |thread_id| {
+                    let range_lo = std::cmp::min(thread_range * thread_id, candidates.len());
+                    let range_hi = std::cmp::min(range_lo + thread_range, candidates.len());
+                    biome_layers::filter_seeds_using_biomes_118(
+                        &candidates[range_lo..range_hi],
+                        &biomes,
+                        version,
+                    )
+                })
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .map(|seed| format!("{:016X}", seed as u64))
+                .collect();
+                println!(
+                    "Found {} 64-bit seeds:\n{}",
+                    seeds.len(),
+                    serde_json::to_string(&seeds).unwrap()
+                );
+
+                if let Some(of) = output_file {
+                    write_candidates_to_file(&seeds, of).expect("Error writing seeds to file");
+                }
+                return;
+            }
+
             if version >= MinecraftVersion::Java1_15 {
                 let (rivers, _extra_biomes) = anvil::get_rivers_and_some_extra_biomes_zip_1_15(
                     &input_zip,
@@ -974,6 +1711,7 @@ fn main() {
             seed_hash,
             seed_not_from_java_next_long,
             candidates_file,
+            resume,
         } => {
             fn print_progress_since(
                 start: &Instant,
@@ -996,89 +1734,89 @@ fn main() {
                 print!("{}\r", msg);
             }
 
-            if let Some(candidates_path) = candidates_file {
-                let candidates = read_candidates_from_file(candidates_path).unwrap();
-                let start = Instant::now();
-                let iter = 0..=u32::max_value();
-                let total_iter_len = iter.size_hint().1.unwrap_or(u64::max_value() as usize);
-                for range_lo in iter {
-                    //let found_seed = biome_layers::seed_hash_bruteforce_26_range(seed_hash, &candidates, range_lo, range_lo);
+            let candidates = candidates_file.map(|path| read_candidates_from_file(path).unwrap());
+            let task = checkpoint::task_id(
+                "bruteforce-seed-hash",
+                &(seed_hash, seed_not_from_java_next_long, &candidates),
+            );
+            let keep_checkpoint = resume.is_some();
+            let mut ckpt = open_checkpoint(resume, "bruteforce-seed-hash", task);
+            let start = Instant::now();
+
+            let r = if let Some(candidates) = candidates {
+                let total = 1 << 32;
+                let block = 1 << if seed_not_from_java_next_long { 10 } else { 18 };
+                let resumed_from = ckpt.state.remaining(total).first().map_or(total, |r| r.0);
+                ckpt.run_range_until_found(1, total, block, |lo, hi| {
                     let found_seed = if seed_not_from_java_next_long {
                         biome_layers::seed_hash_bruteforce_26_range(
                             seed_hash,
                             &candidates,
-                            range_lo,
-                            range_lo,
+                            lo as u32,
+                            (hi - 1) as u32,
                         )
                     } else {
                         biome_layers::seed_hash_bruteforce_26_java_range(
                             seed_hash,
                             &candidates,
-                            range_lo,
-                            range_lo,
+                            lo as u32,
+                            (hi - 1) as u32,
                         )
                     };
-                    if let Some(seed) = found_seed {
-                        println!("\nFound seed: {}", seed);
-                        return;
-                    }
-
-                    if (range_lo & ((1 << if seed_not_from_java_next_long { 10 } else { 18 }) - 1))
-                        == 0
-                    {
-                        let tried_seeds = (range_lo as u64) * (1 << 6) * (candidates.len() as u64);
-                        print_progress_since(
-                            &start,
-                            range_lo as u64,
-                            total_iter_len as u64,
-                            tried_seeds,
-                        );
-                    }
-                }
 
-                println!("\nZero seeds found");
+                    let tried_seeds = hi * (1 << 6) * (candidates.len() as u64);
+                    print_progress_since(
+                        &start,
+                        hi - resumed_from,
+                        total - resumed_from,
+                        tried_seeds,
+                    );
+                    found_seed.into_iter().map(|seed| seed as u64).collect()
+                })
             } else {
                 println!("Warning: trying to bruteforce seed from hash without candidates");
                 println!("This will take a few years...");
 
-                let start = Instant::now();
-                let iter = 0..=u64::max_value();
-                let total_iter_len = iter.size_hint().1.unwrap_or(u64::max_value() as usize);
-                for range_lo in iter {
-                    if !seed_not_from_java_next_long {
-                        // if seed_from_java_next_long
-                        if JavaRng::create_from_long(range_lo).is_none() {
-                            if (range_lo & ((1 << 28) - 1)) == 0 {
-                                print_progress_since(
-                                    &start,
-                                    range_lo as u64,
-                                    total_iter_len as u64,
-                                    range_lo as u64,
-                                );
+                // Each element of the range is a block of 2^20 seeds
+                const BLOCK_BITS: u32 = 20;
+                let total = 1 << (64 - BLOCK_BITS);
+                let block = 1 << if seed_not_from_java_next_long { 0 } else { 8 };
+                let resumed_from = ckpt.state.remaining(total).first().map_or(total, |r| r.0);
+                ckpt.run_range_until_found(1, total, block, |lo, hi| {
+                    // When hi == total this overflows to 0, and the last seed is u64::MAX
+                    let last_seed = (hi << BLOCK_BITS).wrapping_sub(1);
+                    for seed in (lo << BLOCK_BITS)..=last_seed {
+                        if !seed_not_from_java_next_long {
+                            // if seed_from_java_next_long
+                            if JavaRng::create_from_long(seed).is_none() {
+                                continue;
                             }
-                            continue;
                         }
-                    }
-                    let seed = range_lo as i64;
-                    if biome_layers::sha256_long_to_long(seed) == seed_hash {
-                        println!("\nFound seed: {}", seed);
-                        return;
+                        if biome_layers::sha256_long_to_long(seed as i64) == seed_hash {
+                            return vec![seed];
+                        }
                     }
 
-                    if (range_lo & ((1 << if seed_not_from_java_next_long { 20 } else { 28 }) - 1))
-                        == 0
-                    {
-                        print_progress_since(
-                            &start,
-                            range_lo as u64,
-                            total_iter_len as u64,
-                            range_lo as u64,
-                        );
+                    print_progress_since(
+                        &start,
+                        hi - resumed_from,
+                        total - resumed_from,
+                        hi << BLOCK_BITS,
+                    );
+                    vec![]
+                })
+            };
+
+            match r {
+                Ok(seeds) => {
+                    close_checkpoint(&mut ckpt, keep_checkpoint);
+                    match seeds.first() {
+                        Some(seed) => println!("\nFound seed: {}", *seed as i64),
+                        None => println!("\nZero seeds found"),
                     }
                 }
-
-                println!("\nZero seeds found");
-            };
+                Err(_) => print_interrupted(ckpt.path()),
+            }
         }
 
         Opt::SeedHash { seed } => {
@@ -1090,11 +1828,35 @@ fn main() {
             spawner_y,
             spawner_z,
             floor,
+            mc_version,
+            candidate_seeds,
             threads,
+            resume,
         } => {
+            let version = match mc_version.as_deref().map(str::parse::<MinecraftVersion>) {
+                Some(Ok(version)) => Some(version),
+                Some(Err(_)) => {
+                    println!(
+                        "Error: unsupported Minecraft version {:?}",
+                        mc_version.unwrap()
+                    );
+                    return;
+                }
+                None => None,
+            };
+            let is_1_18 = version.map_or(false, |v| v >= MinecraftVersion::Java1_18);
+            let candidate_seeds = match candidate_seeds {
+                Some(path) if is_1_18 => {
+                    read_seeds_from_file(path).expect("Error reading candidates")
+                }
+                _ if is_1_18 => {
+                    println!("Error: since 1.18 the dungeon seed has 64 bits, use --candidate-seeds with the lower 48 bits of the world seed");
+                    return;
+                }
+                _ => vec![],
+            };
             let floor = MossyFloor::parse(&floor).expect("error parsing floor");
             let (wx, wy, wz) = (spawner_x, spawner_y, spawner_z);
-            let (x, y, z) = population::spawner_coordinates_to_next_int(wx, wy, wz);
             let (top_left, top_right, bottom_left, bottom_right) = floor.corner_coords(wx, wy, wz);
 
             println!("Please double check that the entered data is correct:");
@@ -1117,46 +1879,49 @@ fn main() {
             } else {
                 threads
             };
-            println!(
-                "Started brutefroce using {} threads. Estimated time: around {} minutes",
-                num_threads,
-                240 / num_threads
+            let task = checkpoint::task_id(
+                "dungeon-seed",
+                &((wx, wy, wz), floor.to_pretty_string(), &candidate_seeds),
             );
-
-            let total_range = 1u64 << 40;
-            let thread_range = total_range / u64::try_from(num_threads).unwrap();
-            let floor = Arc::new(floor);
-            let seeds: Vec<String> = run_threads(num_threads, move |thread_id| {
-                let range_lo = thread_range * u64::try_from(thread_id).unwrap();
-                let range_hi = if thread_id + 1 == num_threads {
-                    total_range
-                } else {
-                    thread_range * u64::try_from(thread_id + 1).unwrap()
-                };
-                let range_hi = u64::try_from(range_hi).unwrap();
-                debug!(
-                    "Spawning thread {} from {:X} to {:X}",
-                    thread_id, range_lo, range_hi
+            let keep_checkpoint = resume.is_some();
+            let mut ckpt = open_checkpoint(resume, "dungeon-seed", task);
+            let r = if is_1_18 {
+                println!(
+                    "Started bruteforce of the upper 16 bits of {} candidates using {} threads",
+                    candidate_seeds.len(),
+                    num_threads
                 );
-                let dungeon_rngs =
-                    population::dungeon_rng_bruteforce_range((x, y, z), &floor, range_lo, range_hi);
-                debug!("Thread {} finished", thread_id);
-
-                dungeon_rngs
-            })
-            .unwrap()
-            .into_iter()
-            .flat_map(|x| x)
-            .map(|dungeon_rng| {
-                format!(
-                    "{},{},{},{}",
-                    spawner_x,
-                    spawner_y,
-                    spawner_z,
-                    dungeon_rng.get_seed()
-                )
-            })
-            .collect();
+                let dungeon = (wx as i32, wy as i32, wz as i32);
+                ckpt.run_range(num_threads, candidate_seeds.len() as u64, 1, |lo, hi| {
+                    candidate_seeds[lo as usize..hi as usize]
+                        .iter()
+                        .flat_map(|&s| {
+                            population::dungeon_decoration_seeds_1_18(dungeon, &floor, s)
+                        })
+                        .collect()
+                })
+            } else {
+                println!(
+                    "Started brutefroce using {} threads. Estimated time: around {} minutes",
+                    num_threads,
+                    240 / num_threads
+                );
+                dungeon_seed_bruteforce((wx, wy, wz), &floor, num_threads, &mut ckpt)
+            };
+            let seeds = match r {
+                Ok(seeds) => seeds,
+                Err(_) => {
+                    print_interrupted(ckpt.path());
+                    return;
+                }
+            };
+            close_checkpoint(&mut ckpt, keep_checkpoint);
+            let seeds: Vec<String> = seeds
+                .into_iter()
+                .map(|dungeon_seed| {
+                    format!("{},{},{},{}", spawner_x, spawner_y, spawner_z, dungeon_seed)
+                })
+                .collect();
 
             let num_candidates = seeds.len();
 
@@ -1176,8 +1941,60 @@ fn main() {
         Opt::DungeonSeedToWorldSeed {
             limit_steps_back,
             resume_l,
+            mc_version,
             threads,
+            resume,
             dungeon_seeds,
+        } => {
+            let version = match mc_version.as_deref().map(str::parse::<MinecraftVersion>) {
+                Some(Ok(version)) => version,
+                Some(Err(_)) => {
+                    println!(
+                        "Error: unsupported Minecraft version {:?}",
+                        mc_version.unwrap()
+                    );
+                    return;
+                }
+                None => MinecraftVersion::Java1_11,
+            };
+            let dungeon_seeds = parse_dungeon_seeds(dungeon_seeds, limit_steps_back, version);
+            if dungeon_seeds.len() < 3 {
+                println!("Need at least 3 dungeon seeds");
+                return;
+            }
+            println!("{:?}", dungeon_seeds);
+
+            if version >= MinecraftVersion::Java1_18 {
+                let [i1, i2, i3] = [dungeon_seeds[0], dungeon_seeds[1], dungeon_seeds[2]]
+                    .map(|(seed, chunk_x, chunk_z, _l)| (seed, chunk_x, chunk_z));
+                let world_seeds = population::decoration_seed_to_world_seed_1_18(i1, i2, i3);
+                println!("Found {} world seeds:", world_seeds.len());
+                println!("{:?}", world_seeds);
+                return;
+            }
+
+            let num_threads = if threads == 0 {
+                num_cpus::get()
+            } else {
+                threads
+            };
+            let world_seeds = match dungeon_seeds_to_world_seeds(
+                [dungeon_seeds[0], dungeon_seeds[1], dungeon_seeds[2]],
+                num_threads,
+                resume_l,
+                resume,
+            ) {
+                Some(world_seeds) => world_seeds,
+                None => return,
+            };
+            println!("Found {} world seeds:", world_seeds.len());
+            println!("{:?}", world_seeds);
+        }
+
+        Opt::DungeonSeedCheck {
+            limit_steps_back,
+            dungeon_seed,
+            world_seed,
         } => {
             /// Parse the string output of the dungeon-seed command into (dungeon_seed, chunk_x,
             /// chunk_z, limit_steps_back).
@@ -1188,102 +2005,8 @@ fn main() {
             // a 5 element tuple here
             fn parse_dungeon_seed(
                 s: &str,
-                default_l: u32,
-            ) -> Result<(i64, i64, i64, u64, u32), ()> {
-                let mut parts = s.split(',');
-                let x = parts.next().ok_or(())?;
-                let y = parts.next().ok_or(())?;
-                let z = parts.next().ok_or(())?;
-                let seed = parts.next().ok_or(())?;
-                let l = parts.next();
-                if parts.next().is_some() {
-                    // Trailing ','
-                    return Err(());
-                }
-
-                let x = x.parse().map_err(|_| ())?;
-                let y = y.parse().map_err(|_| ())?;
-                let z = z.parse().map_err(|_| ())?;
-                let seed = seed.parse().map_err(|_| ())?;
-                let l = if let Some(l) = l {
-                    l.parse().map_err(|_| ())?
-                } else {
-                    default_l
-                };
-
-                Ok((x, y, z, seed, l))
-            }
-
-            let dungeon_seeds: Vec<_> = dungeon_seeds.into_iter().map(|d| {
-                parse_dungeon_seed(&d, limit_steps_back).map(|(x, _y, z, seed, l)| {
-                    let Chunk { x: chunk_x, z: chunk_z } = population::spawner_coordinates_to_chunk(x, z);
-                    (seed, chunk_x, chunk_z, l)
-                }).unwrap_or_else(|_| {
-                    panic!("Error parsing \"{}\": dungeon seed should follow the format \"{}\"`", d, "159,23,-290,982513219448");
-                })
-            }).collect();
-            if dungeon_seeds.len() < 3 {
-                println!("Need at least 3 dungeon seeds");
-                return;
-            }
-            println!("{:?}", dungeon_seeds);
-
-            let i1 = dungeon_seeds[0];
-            let i2 = dungeon_seeds[1];
-            let i3 = dungeon_seeds[2];
-
-            let num_threads = if threads == 0 {
-                num_cpus::get()
-            } else {
-                threads
-            };
-            let limit = std::cmp::max(i1.3, std::cmp::max(i2.3, i3.3));
-            let mut step = resume_l.unwrap_or(0);
-            let world_seeds = run_workers(
-                num_threads,
-                || {
-                    // Generate next work packet
-                    if step >= limit {
-                        return None;
-                    }
-                    let res = step;
-                    step += 1;
-                    Some(res)
-                },
-                move |step| {
-                    // Process work
-                    let world_seeds =
-                        population::dungeon_seed_to_world_seed_any_version_step(i1, i2, i3, step);
-                    if world_seeds.is_empty() {
-                        // Get more work
-                        ControlFlow::Continue(vec![])
-                    } else {
-                        // Found seed, done
-                        ControlFlow::Break(world_seeds)
-                    }
-                },
-            )
-            .unwrap_or_default();
-            println!("Found {} world seeds:", world_seeds.len());
-            println!("{:?}", world_seeds);
-        }
-
-        Opt::DungeonSeedCheck {
-            limit_steps_back,
-            dungeon_seed,
-            world_seed,
-        } => {
-            /// Parse the string output of the dungeon-seed command into (dungeon_seed, chunk_x,
-            /// chunk_z, limit_steps_back).
-            ///
-            /// Example: `"159,23,-290,982513219448"` is parsed into `(159, 23, -290, 982513219448, default_l)`
-            // Actually, l is only an argument because the syntax to convert a tuple with 4
-            // elements into a tuple of 5 elements is super ugly, so it looks better to just return
-            // a 5 element tuple here
-            fn parse_dungeon_seed(
-                s: &str,
-                default_l: u64,
-            ) -> Result<(i64, i64, i64, u64, u64), ()> {
+                default_l: u64,
+            ) -> Result<(i64, i64, i64, u64, u64), ()> {
                 let mut parts = s.split(',');
                 let x = parts.next().ok_or(())?;
                 let y = parts.next().ok_or(())?;
@@ -1343,234 +2066,52 @@ fn main() {
             input_zip,
             mc_version,
             draw_biome_map,
+            draw_diff,
         } => {
             let version: MinecraftVersion = mc_version.parse().unwrap();
-            match version {
-                MinecraftVersion::Java1_3
-                | MinecraftVersion::Java1_7
-                | MinecraftVersion::Java1_9
-                | MinecraftVersion::Java1_11
-                | MinecraftVersion::Java1_13
-                | MinecraftVersion::Java1_14 => {
-                    let world_seed =
-                        anvil::read_seed_from_level_dat_zip(&input_zip, Some(version)).unwrap();
-                    if JavaRng::create_from_long(world_seed as u64).is_none() {
-                        println!(
-                            "Warning: this seed cannot be generated with Java Random nextLong"
-                        );
-                    }
-                    println!("Seed from level.dat {}", world_seed);
-                    let mut chunk_provider = ZipChunkProvider::file(input_zip).unwrap();
-                    let biomes = anvil::get_all_biomes_1_14(&mut chunk_provider);
-                    println!("Got {} biomes", biomes.len());
-
-                    let points = biomes.iter().map(|(_biome_id, p)| Point { x: p.x, z: p.z });
-                    let area = Area::from_coords(points);
-                    println!("Area: {:?}", area);
-
-                    if draw_biome_map {
-                        println!("Drawing biome map");
-                        let mut map =
-                            Map::from_area_fn(area, |(_, _)| biome_info::UNKNOWN_BIOME_ID);
-                        for (expected_biome_id, p) in &biomes {
-                            map.set(p.x, p.z, expected_biome_id.0);
-                        }
-                        let map_image = biome_layers::draw_map_image(&map);
-                        let x = area.x;
-                        let z = area.z;
-                        let width = area.w.try_into().unwrap();
-                        let height = area.h.try_into().unwrap();
-                        let output_file = format!(
-                            "biome_map_mc_{}_{}_{}_{}_{}x{}.png",
-                            mc_version, world_seed, x, z, width, height
-                        );
-                        image::save_buffer(
-                            output_file.clone(),
-                            &map_image,
-                            width,
-                            height,
-                            image::ColorType::Rgba8,
-                        )
-                        .unwrap();
-                        println!("Saved image to {}", output_file);
-                    }
-
-                    // Generate area with 1:1 resolution
-                    let y_offset = 0;
-                    let map = biome_layers::generate(version, area, world_seed, y_offset);
-
-                    // Compare maps :D
-                    for (expected_biome_id, p) in biomes {
-                        let b = map.get(p.x, p.z);
-                        if b != expected_biome_id.0 {
-                            panic!(
-                                "Mismatch at ({}, {}): expected {} generated {}",
-                                p.x, p.z, expected_biome_id.0, b
-                            );
-                        }
-                    }
-
-                    println!("All biomes match");
-                }
-                MinecraftVersion::Java1_15
-                | MinecraftVersion::Java1_16_1
-                | MinecraftVersion::Java1_16
-                | MinecraftVersion::Java1_17 => {
-                    let world_seed =
-                        anvil::read_seed_from_level_dat_zip(&input_zip, Some(version)).unwrap();
-                    if JavaRng::create_from_long(world_seed as u64).is_none() {
-                        println!(
-                            "Warning: this seed cannot be generated with Java Random nextLong"
-                        );
-                    }
-                    println!("Seed from level.dat {}", world_seed);
-                    let mut chunk_provider = ZipChunkProvider::file(input_zip).unwrap();
-                    let biomes = anvil::get_all_biomes_1_15(&mut chunk_provider);
-                    println!("Got {} biomes", biomes.len());
-                    let points = biomes.iter().map(|(_biome_id, p)| Point { x: p.x, z: p.z });
-                    let area = Area::from_coords(points);
-                    println!("Area: {:?}", area);
-
-                    if draw_biome_map {
-                        println!("Drawing biome map");
-                        let mut map =
-                            Map::from_area_fn(area, |(_, _)| biome_info::UNKNOWN_BIOME_ID);
-                        for (expected_biome_id, p) in &biomes {
-                            map.set(p.x, p.z, expected_biome_id.0);
-                        }
-                        let map_image = biome_layers::draw_map_image(&map);
-                        let x = area.x;
-                        let z = area.z;
-                        let width = area.w.try_into().unwrap();
-                        let height = area.h.try_into().unwrap();
-                        let output_file = format!(
-                            "biome_map_mc_{}_{}_{}_{}_{}x{}.png",
-                            mc_version, world_seed, x, z, width, height
-                        );
-                        image::save_buffer(
-                            output_file.clone(),
-                            &map_image,
-                            width,
-                            height,
-                            image::ColorType::Rgba8,
-                        )
-                        .unwrap();
-                        println!("Saved image to {}", output_file);
-                    }
-
-                    // Generate area with 1:4 resolution
-                    let map = biome_layers::generate_up_to_layer_1_15(
-                        area,
-                        world_seed,
-                        version.num_layers() - 1,
-                        version,
-                    );
-
-                    // Compare maps :D
-                    for (expected_biome_id, p) in biomes {
-                        let b = map.get(p.x, p.z);
-                        if b != expected_biome_id.0 {
-                            panic!(
-                                "Mismatch at ({}, {}): expected {} generated {}",
-                                p.x, p.z, expected_biome_id.0, b
-                            );
-                        }
-                    }
-
-                    println!("All biomes match");
-                }
-                MinecraftVersion::Java1_18 => {
-                    let world_seed =
-                        anvil::read_seed_from_level_dat_zip(&input_zip, Some(version)).unwrap();
-                    if JavaRng::create_from_long(world_seed as u64).is_none() {
-                        println!(
-                            "Warning: this seed cannot be generated with Java Random nextLong"
-                        );
-                    }
-                    println!("Seed from level.dat {}", world_seed);
-                    let mut chunk_provider = ZipChunkProvider::file(input_zip).unwrap();
-                    let biomes = anvil::get_all_biomes_1_18(&mut chunk_provider);
-                    println!("Got {} biomes", biomes.len());
-                    let points = biomes.iter().map(|(_biome_id, p)| Point3D4 {
-                        x: p.x,
-                        y: p.y,
-                        z: p.z,
-                    });
-                    let area = Area3D::from_coords4(points);
-                    println!("Area: {:?}", area);
-
-                    if draw_biome_map {
-                        println!("Drawing biome map");
-                        let mut map =
-                            Map3D::from_area_fn(area, |(_, _, _)| biome_info::UNKNOWN_BIOME_ID);
-                        for (expected_biome_id, p) in &biomes {
-                            map.set(p.x, p.y, p.z, expected_biome_id.0);
-                        }
-                        let map_image = biome_layers::draw_map_image(&map.flatten_into_2d());
-                        let x = area.x;
-                        let z = area.z;
-                        let width = area.sx.try_into().unwrap();
-                        let height = area.sz.try_into().unwrap();
-                        let output_file = format!(
-                            "biome_map_mc_{}_{}_{}_{}_{}x{}.png",
-                            mc_version, world_seed, x, z, width, height
-                        );
-                        image::save_buffer(
-                            output_file.clone(),
-                            &map_image,
-                            width,
-                            height,
-                            image::ColorType::Rgba8,
-                        )
-                        .unwrap();
-                        println!("Saved image to {}", output_file);
-                    }
-
-                    // Generate area with 1:4 resolution
-                    let layer = version.num_layers() - 1;
-                    // Doesn't work because GetMap3D does not have partial_get_map_3d method
-                    //let generator = biome_layers::generator_up_to_layer_1_18(world_seed, layer, version);
-                    let generator = biome_layers::MapGenBiomeNoise3D118::new(world_seed);
-
-                    // Compare maps :D
-                    for (expected_biome_id, p) in biomes {
-                        if expected_biome_id.0 == 1 {
-                            // Bug: chunks that are not fully generated have biome id set to plains
-                            continue;
-                        }
-                        let a3 = Area3D {
-                            x: p.x,
-                            y: p.y,
-                            z: p.z,
-                            sx: 1,
-                            sy: 1,
-                            sz: 1,
-                        };
-                        let b = generator.partial_get_map_3d(a3, layer).a[(0, 0, 0)];
-                        if b != expected_biome_id.0 {
-                            let distance_to_second_biome_layer = 50;
-                            let distance_to_second_biome = generator
-                                .partial_get_map_3d(a3, distance_to_second_biome_layer)
-                                .a[(0, 0, 0)];
-
-                            if distance_to_second_biome < 1 {
-                                println!(
-                                    "Mismatch at ({}, {}, {}): expected {} generated {} (distance to 2nd biome: {})",
-                                    p.x, p.y, p.z, expected_biome_id.0, b, distance_to_second_biome
-                                );
-                            } else {
-                                panic!(
-                                    "Mismatch at ({}, {}, {}): expected {} generated {} (distance to 2nd biome: {})",
-                                    p.x, p.y, p.z, expected_biome_id.0, b, distance_to_second_biome
-                                );
-                            }
-                        }
-                    }
+            let world_seed =
+                anvil::read_seed_from_level_dat_zip(&input_zip, Some(version)).unwrap();
+            if JavaRng::create_from_long(world_seed as u64).is_none() {
+                println!("Warning: this seed cannot be generated with Java Random nextLong");
+            }
+            println!("Seed from level.dat {}", world_seed);
+            let mut chunk_provider = ZipChunkProvider::file(input_zip).unwrap();
+            let diff = biome_diff::compare_world_biomes(&mut chunk_provider, version, world_seed)
+                .unwrap_or_else(|e| panic!("{}", e));
+            let area = diff.expected.area();
+            println!("Area: {:?}", area);
+
+            let save_image = |name: &str, image: Vec<u8>| {
+                let output_file = format!(
+                    "{}_mc_{}_{}_{}_{}_{}x{}.png",
+                    name, mc_version, world_seed, area.x, area.z, area.w, area.h
+                );
+                image::save_buffer(
+                    output_file.clone(),
+                    &image,
+                    area.w.try_into().unwrap(),
+                    area.h.try_into().unwrap(),
+                    image::ColorType::Rgba8,
+                )
+                .unwrap();
+                println!("Saved image to {}", output_file);
+            };
+            if draw_biome_map {
+                println!("Drawing biome map");
+                save_image("biome_map", biome_layers::draw_map_image(&diff.expected));
+            }
+            if draw_diff {
+                println!("Drawing biome diff");
+                save_image("biome_diff", diff.draw_overlay());
+            }
 
-                    println!("All biomes match");
-                }
-                _ => {
-                    unimplemented!("Version {} is not supported", mc_version);
+            if diff.num_mismatches() == 0 {
+                println!("All {} biomes match", diff.total);
+            } else {
+                print!("{}", diff);
+                // Mismatches near the border of the second biome are expected
+                if diff.num_mismatches() > diff.near_border {
+                    std::process::exit(1);
                 }
             }
         }
@@ -1596,6 +2137,214 @@ fn main() {
             println!("{}", dungeons_json);
         }
 
+        Opt::ReadMaps {
+            input,
+            output_file,
+            mc_version,
+        } => {
+            if let Some(version) = &mc_version {
+                if version.parse::<MinecraftVersion>().is_err() {
+                    println!("Error: unsupported Minecraft version {:?}", version);
+                    return;
+                }
+            }
+            let item_maps = match anvil::read_item_maps(&input) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Error reading maps: {}", e);
+                    return;
+                }
+            };
+            println!("Found {} overworld maps", item_maps.len());
+            let mut seed_info = if output_file.exists() {
+                match SeedInfo::read(&output_file) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        error!("Error reading seed info: {:?}", e);
+                        return;
+                    }
+                }
+            } else {
+                let version = match mc_version {
+                    Some(version) => version,
+                    None => {
+                        println!("Error: --mc-version is needed to create a new SeedInfo");
+                        return;
+                    }
+                };
+                println!("Creating new SeedInfo file {}", output_file.display());
+                SeedInfo {
+                    version,
+                    ..Default::default()
+                }
+            };
+            let num_new = seed_info.add_item_maps(item_maps);
+            println!("Added {} new maps", num_new);
+            let s = serde_json::to_string_pretty(&seed_info).unwrap();
+            if let Err(e) = fs::write(&output_file, s) {
+                error!("Error writing seed info: {}", e);
+                return;
+            }
+            println!(
+                "Saved {} maps to {}",
+                seed_info.item_maps.len(),
+                output_file.display()
+            );
+        }
+
+        Opt::WorldDungeonSeed {
+            input_zip,
+            mc_version,
+            max_dungeons,
+            limit_steps_back,
+            threads,
+            resume,
+        } => {
+            let version: MinecraftVersion = match mc_version.parse() {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Invalid version {:?}", e);
+                    return;
+                }
+            };
+            let mut chunk_provider = match ZipChunkProvider::file(input_zip) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Error opening world: {:?}", e);
+                    return;
+                }
+            };
+            let dungeons = match anvil::find_dungeons(&mut chunk_provider) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Error reading dungeons: {}", e);
+                    return;
+                }
+            };
+            let mut dungeons: Vec<_> = dungeons
+                .into_iter()
+                .filter_map(|((x, y, z), _kind, floor)| {
+                    match MossyFloor::from_floor_blocks(&floor, version) {
+                        Ok(floor) => Some(((x, y, z), floor)),
+                        Err(e) => {
+                            warn!("Ignoring dungeon at {:?}: {}", (x, y, z), e);
+                            None
+                        }
+                    }
+                })
+                .collect();
+            println!("Found {} dungeons", dungeons.len());
+            let seed_info_dungeons: Vec<_> = dungeons
+                .iter()
+                .map(|((x, y, z), floor)| seed_info::Dungeon {
+                    x: *x,
+                    y: *y,
+                    z: *z,
+                    floor: floor.to_pretty_string().replace('\n', ";"),
+                })
+                .collect();
+            println!(
+                "Dungeons in seedInfo format:\n{}",
+                serde_json::to_string(&seed_info_dungeons).unwrap()
+            );
+            if version >= MinecraftVersion::Java1_13 {
+                println!("Help: dungeons generated in 1.13 or later cannot be reversed using the dungeon seed, use the find command with the list of dungeons instead");
+                return;
+            }
+
+            let num_threads = if threads == 0 {
+                num_cpus::get()
+            } else {
+                threads
+            };
+            // Each dungeon is one step of the checkpoint, and the world seed is
+            // the last step. The limit is not part of the task, so a search can
+            // be resumed with a higher limit
+            let task = checkpoint::task_id(
+                "world-dungeon-seed",
+                &(&seed_info_dungeons, &mc_version, max_dungeons),
+            );
+            let keep_checkpoint = resume.is_some();
+            let mut ckpt = open_checkpoint(resume, "world-dungeon-seed", task);
+            // The first dungeon seed of each finished dungeon, or NO_DUNGEON_SEED
+            const NO_DUNGEON_SEED: u64 = u64::MAX;
+            let mut finished = ckpt.state.input.clone().unwrap_or_default();
+            // Dungeons with more known tiles have less false positives
+            dungeons
+                .sort_by(|(_, a), (_, b)| a.probability().partial_cmp(&b.probability()).unwrap());
+            // Only one dungeon per chunk, dungeon-seed-to-world-seed needs 3 different chunks
+            let mut used_chunks = HashSet::new();
+            let mut dungeon_seeds = vec![];
+            let mut tried = 0;
+            for ((x, y, z), floor) in dungeons {
+                if dungeon_seeds.len() == 3 || tried == max_dungeons {
+                    break;
+                }
+                let chunk = population::spawner_coordinates_to_chunk_version(x, z, version);
+                if !used_chunks.insert(chunk) {
+                    continue;
+                }
+                tried += 1;
+                let seed = if let Some(&seed) = finished.get(tried - 1) {
+                    seed
+                } else {
+                    println!(
+                        "Finding dungeon seed of dungeon at {:?}. Estimated time: around {} minutes",
+                        (x, y, z),
+                        240 / num_threads
+                    );
+                    println!("    {}", floor.to_pretty_string().replace('\n', "\n    "));
+                    let seeds =
+                        match dungeon_seed_bruteforce((x, y, z), &floor, num_threads, &mut ckpt) {
+                            Ok(seeds) => seeds,
+                            Err(_) => {
+                                print_interrupted(ckpt.path());
+                                return;
+                            }
+                        };
+                    println!("Found {} dungeon seeds: {:?}", seeds.len(), seeds);
+                    // Use the first seed, if there are more candidates the user can try them manually
+                    let seed = seeds.first().copied().unwrap_or(NO_DUNGEON_SEED);
+                    finished.push(seed);
+                    ckpt.next_step(finished.clone());
+                    seed
+                };
+                if seed != NO_DUNGEON_SEED {
+                    dungeon_seeds.push(format!("{},{},{},{}", x, y, z, seed));
+                }
+            }
+
+            if dungeon_seeds.len() < 3 {
+                close_checkpoint(&mut ckpt, keep_checkpoint);
+                println!(
+                    "Found {} dungeon seeds, need at least 3: {:?}",
+                    dungeon_seeds.len(),
+                    dungeon_seeds
+                );
+                return;
+            }
+            println!(
+                "Finding world seed, equivalent to:\nslime_seed_finder dungeon-seed-to-world-seed -l {} -- {}",
+                limit_steps_back,
+                dungeon_seeds.join(" ")
+            );
+            let dungeon_seeds = parse_dungeon_seeds(dungeon_seeds, limit_steps_back, version);
+            let world_seeds = match dungeon_seeds_to_world_seeds_step(
+                [dungeon_seeds[0], dungeon_seeds[1], dungeon_seeds[2]],
+                num_threads,
+                &mut ckpt,
+            ) {
+                Ok(world_seeds) => world_seeds,
+                Err(_) => {
+                    print_interrupted(ckpt.path());
+                    return;
+                }
+            };
+            close_checkpoint(&mut ckpt, keep_checkpoint);
+            println!("Found {} world seeds:", world_seeds.len());
+            println!("{:?}", world_seeds);
+        }
+
         Opt::FilterBiomes {
             input_file,
             candidate_seeds,
@@ -1644,6 +2393,312 @@ fn main() {
             let multi_spawners_json = serde_json::to_string(&multi_spawners).unwrap();
             println!("{}", multi_spawners_json);
         }
+
+        Opt::Serve {
+            bind,
+            timeout,
+            block_size,
+            output_file,
+            resume,
+            job,
+        } => {
+            let job = match job {
+                ServeJob::Slime { input_file } => match SeedInfo::read(input_file) {
+                    Ok(seed_info) => distributed::Job::SlimeChunks { seed_info },
+                    Err(e) => {
+                        error!("Error reading seed info: {:?}", e);
+                        return;
+                    }
+                },
+                ServeJob::Rivers { input_file } => match SeedInfo::read(input_file) {
+                    Ok(seed_info) => distributed::Job::Rivers { seed_info },
+                    Err(e) => {
+                        error!("Error reading seed info: {:?}", e);
+                        return;
+                    }
+                },
+                ServeJob::SeedHash {
+                    seed_hash,
+                    seed_not_from_java_next_long,
+                    candidates_file,
+                } => match read_candidates_from_file(candidates_file) {
+                    Ok(candidates) => distributed::Job::SeedHash {
+                        seed_hash,
+                        not_from_java_next_long: seed_not_from_java_next_long,
+                        candidates,
+                    },
+                    Err(e) => {
+                        error!("Error reading candidates: {:?}", e);
+                        return;
+                    }
+                },
+                ServeJob::Dungeon {
+                    limit_steps_back,
+                    dungeon_seeds,
+                } => {
+                    // The distributed search only reverses dungeons generated before 1.13
+                    let dungeon_seeds = parse_dungeon_seeds(
+                        dungeon_seeds,
+                        limit_steps_back,
+                        MinecraftVersion::Java1_11,
+                    );
+                    if dungeon_seeds.len() < 3 {
+                        println!("Need at least 3 dungeon seeds");
+                        return;
+                    }
+                    distributed::Job::Dungeon {
+                        dungeon_seeds: [dungeon_seeds[0], dungeon_seeds[1], dungeon_seeds[2]],
+                    }
+                }
+            };
+            // Check that the job is valid before waiting for workers
+            if let Err(e) = job.range_fn() {
+                error!("{}", e);
+                return;
+            }
+
+            let task = checkpoint::task_id("serve", &job);
+            let keep_checkpoint = resume.is_some();
+            let ckpt = open_checkpoint(resume, "serve", task);
+            let block = block_size.unwrap_or_else(|| job.default_block());
+            let coordinator = Arc::new(Mutex::new(distributed::Coordinator::new(
+                job,
+                ckpt,
+                block,
+                std::time::Duration::from_secs(timeout),
+            )));
+            let listener = match std::net::TcpListener::bind(&bind) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Error binding address: {}", e);
+                    return;
+                }
+            };
+            println!("Waiting for workers on {}", bind);
+            let seeds = distributed::serve(listener, Arc::clone(&coordinator));
+            let mut coordinator = coordinator.lock().unwrap();
+            let seeds = match seeds {
+                Ok(seeds) => seeds,
+                Err(_) => {
+                    print_interrupted(coordinator.ckpt.path());
+                    return;
+                }
+            };
+            close_checkpoint(&mut coordinator.ckpt, keep_checkpoint);
+            let seeds: Vec<i64> = seeds.into_iter().map(|s| s as i64).collect();
+
+            println!(
+                "Found {} seeds:\n{}",
+                seeds.len(),
+                serde_json::to_string(&seeds).unwrap()
+            );
+            if let Some(of) = output_file {
+                if let Err(e) = write_seeds_to_file(&seeds, of) {
+                    error!("Error writing seeds to file: {}", e);
+                }
+            }
+        }
+
+        Opt::Work { server, threads } => {
+            let num_threads = if threads == 0 {
+                num_cpus::get()
+            } else {
+                threads
+            };
+            match distributed::work(&server, num_threads) {
+                Ok(units) => println!("No more work, processed {} work units", units),
+                Err(e) => error!("Connection with {} lost: {}", server, e),
+            }
+        }
+    }
+}
+
+/// Find all the dungeon seeds that generate a dungeon with this floor at this spawner
+/// coordinates, by bruteforcing the 2^40 possible values that can generate the coordinates.
+/// Returns `Err` if the search was interrupted.
+fn dungeon_seed_bruteforce(
+    (wx, wy, wz): (i64, i64, i64),
+    floor: &MossyFloor,
+    num_threads: usize,
+    ckpt: &mut Checkpointer,
+) -> Result<Vec<u64>, checkpoint::Interrupted> {
+    let (x, y, z) = population::spawner_coordinates_to_next_int(wx, wy, wz);
+    ckpt.run_range(num_threads, 1 << 40, 1 << 28, |lo, hi| {
+        population::dungeon_rng_bruteforce_range((x, y, z), floor, lo, hi)
+            .into_iter()
+            .map(|dungeon_rng| dungeon_rng.get_seed())
+            .collect()
+    })
+}
+
+/// Find the world seed given 3 dungeon seeds in the format returned by `parse_dungeon_seeds`.
+/// Returns `None` if the search was interrupted.
+fn dungeon_seeds_to_world_seeds(
+    dungeon_seeds: [(u64, i32, i32, u32); 3],
+    num_threads: usize,
+    resume_l: Option<u32>,
+    resume: Option<PathBuf>,
+) -> Option<Vec<i64>> {
+    // The limit is not part of the task, so a search can be resumed
+    // with a higher limit
+    let task = checkpoint::task_id(
+        "dungeon-seed-to-world-seed",
+        &dungeon_seeds.map(|(seed, x, z, _l)| (seed, x, z)),
+    );
+    let keep_checkpoint = resume.is_some();
+    let mut ckpt = open_checkpoint(resume, "dungeon-seed-to-world-seed", task);
+    if let Some(resume_l) = resume_l {
+        ckpt.state.add_done(0, u64::from(resume_l), vec![]);
+    }
+    match dungeon_seeds_to_world_seeds_step(dungeon_seeds, num_threads, &mut ckpt) {
+        Ok(world_seeds) => {
+            close_checkpoint(&mut ckpt, keep_checkpoint);
+            Some(world_seeds)
+        }
+        Err(_) => {
+            print_interrupted(ckpt.path());
+            None
+        }
+    }
+}
+
+/// Try all the number of steps back up to the limit, saving the progress to
+/// the current step of `ckpt`
+fn dungeon_seeds_to_world_seeds_step(
+    [i1, i2, i3]: [(u64, i32, i32, u32); 3],
+    num_threads: usize,
+    ckpt: &mut Checkpointer,
+) -> Result<Vec<i64>, checkpoint::Interrupted> {
+    let limit = std::cmp::max(i1.3, std::cmp::max(i2.3, i3.3));
+    let world_seeds = ckpt.run_range_until_found(num_threads, u64::from(limit), 1, |lo, _hi| {
+        population::dungeon_seed_to_world_seed_any_version_step(i1, i2, i3, lo as u32)
+            .into_iter()
+            .map(|s| s as u64)
+            .collect()
+    })?;
+
+    Ok(world_seeds.into_iter().map(|s| s as i64).collect())
+}
+
+/// Parse the dungeon seeds used by dungeon-seed-to-world-seed into
+/// `(dungeon_seed, chunk_x, chunk_z, limit_steps_back)`. Panics on invalid input.
+fn parse_dungeon_seeds(
+    dungeon_seeds: Vec<String>,
+    limit_steps_back: u32,
+    version: MinecraftVersion,
+) -> Vec<(u64, i32, i32, u32)> {
+    /// Parse the string output of the dungeon-seed command into (dungeon_seed, chunk_x,
+    /// chunk_z, limit_steps_back).
+    ///
+    /// Example: `"159,23,-290,982513219448"` is parsed into `(159, 23, -290, 982513219448, default_l)`
+    // Actually, l is only an argument because the syntax to convert a tuple with 4
+    // elements into a tuple of 5 elements is super ugly, so it looks better to just return
+    // a 5 element tuple here
+    fn parse_dungeon_seed(s: &str, default_l: u32) -> Result<(i64, i64, i64, u64, u32), ()> {
+        let mut parts = s.split(',');
+        let x = parts.next().ok_or(())?;
+        let y = parts.next().ok_or(())?;
+        let z = parts.next().ok_or(())?;
+        let seed = parts.next().ok_or(())?;
+        let l = parts.next();
+        if parts.next().is_some() {
+            // Trailing ','
+            return Err(());
+        }
+
+        let x = x.parse().map_err(|_| ())?;
+        let y = y.parse().map_err(|_| ())?;
+        let z = z.parse().map_err(|_| ())?;
+        let seed = seed.parse().map_err(|_| ())?;
+        let l = if let Some(l) = l {
+            l.parse().map_err(|_| ())?
+        } else {
+            default_l
+        };
+
+        Ok((x, y, z, seed, l))
+    }
+
+    dungeon_seeds
+        .into_iter()
+        .map(|d| {
+            parse_dungeon_seed(&d, limit_steps_back)
+                .map(|(x, _y, z, seed, l)| {
+                    let Chunk {
+                        x: chunk_x,
+                        z: chunk_z,
+                    } = population::spawner_coordinates_to_chunk_version(x, z, version);
+                    (seed, chunk_x, chunk_z, l)
+                })
+                .unwrap_or_else(|_| {
+                    panic!(
+                        "Error parsing \"{}\": dungeon seed should follow the format \"{}\"`",
+                        d, "159,23,-290,982513219448"
+                    );
+                })
+        })
+        .collect()
+}
+
+/// Open the checkpoint of a bruteforce subcommand and install the Ctrl-C
+/// handler. With `--resume`, continue the search saved in that file. Otherwise
+/// start a new search that is only saved if it is interrupted, to
+/// `<name>.checkpoint.json` or to a numbered file if that one belongs to
+/// another search.
+fn open_checkpoint(resume: Option<PathBuf>, name: &str, task: String) -> Checkpointer {
+    set_ctrlc_handler();
+
+    match resume {
+        Some(path) => Checkpointer::resume(path, task).expect("Error reading checkpoint"),
+        None => Checkpointer::create_on_interrupt(Path::new("."), name, task),
+    }
+}
+
+/// The first Ctrl-C stops the running search, which saves its progress to the
+/// checkpoint. The second one exits immediately.
+fn set_ctrlc_handler() {
+    #[cfg(feature = "ctrlc")]
+    ctrlc::set_handler(|| {
+        if checkpoint::interrupted() {
+            // Second Ctrl-C: exit without waiting
+            std::process::exit(130);
+        }
+        eprintln!("\nStopping, press Ctrl-C again to exit without saving the progress");
+        checkpoint::interrupt();
+    })
+    .expect("Error setting Ctrl-C handler");
+}
+
+/// Print the seeds sorted by the log-likelihood of the weighted slime chunk
+/// clues, best first. A score of 0 means a perfect match.
+fn print_slime_evidence_ranking(seed_info: &SeedInfo, seeds: &[i64]) {
+    println!("Ranked by slime evidence:");
+    for (seed, score) in find::rank_by_slime_evidence(seed_info, seeds) {
+        println!("{:>20} {:8.2}", seed, score);
+    }
+}
+
+fn print_interrupted(path: Option<&Path>) {
+    if let Some(path) = path {
+        println!(
+            "\nInterrupted, the progress has been saved. To continue the search use --resume {}",
+            path.display()
+        );
+    }
+}
+
+/// Save the finished search, or remove the checkpoint file if the user did
+/// not ask for it
+fn close_checkpoint(ckpt: &mut Checkpointer, keep: bool) {
+    let r = if keep {
+        ckpt.save()
+    } else if let Some(path) = ckpt.path().filter(|p| p.exists()) {
+        fs::remove_file(path)
+    } else {
+        Ok(())
+    };
+    if let Err(e) = r {
+        error!("Error saving checkpoint: {}", e);
     }
 }
 
@@ -1700,125 +2755,229 @@ fn write_candidates_to_file<P: AsRef<Path>>(s: &[String], path: P) -> Result<(),
     Ok(())
 }
 
-// Spawn n threads and wait for them to finish, returning a vector of the results
-// Optimization: when n is 1 do not spawn any threads and run the computation on the current thread
-fn run_threads<F, T>(num_threads: usize, f: F) -> Result<Vec<T>, Box<dyn std::any::Any + Send>>
+/// Width and height of the tiles used by `render_rows` and `TilePyramid`.
+/// Same as the tiles of the biome cache, so each tile uses one cached tile.
+const RENDER_TILE_SIZE: usize = tile_cache::TILE_SIZE as usize;
+
+/// Split `start..start+len` at the multiples of `RENDER_TILE_SIZE`, returning
+/// the start and length of each part
+fn split_at_tiles(start: i64, len: u64) -> Vec<(i64, u64)> {
+    let t = RENDER_TILE_SIZE as i64;
+    let end = start + len as i64;
+    let mut parts = vec![];
+    let mut x = start;
+    while x < end {
+        let next = ((x.div_euclid(t) + 1) * t).min(end);
+        parts.push((x, (next - x) as u64));
+        x = next;
+    }
+
+    parts
+}
+
+/// Render the area using `render(tile_area) -> rgba`, in tiles aligned to
+/// multiples of `RENDER_TILE_SIZE` using `num_threads` threads, and pass the
+/// rows of pixels to `write_row` from top to bottom. Only one row of tiles is
+/// kept in memory.
+fn render_rows<R, W>(area: Area, num_threads: usize, render: R, mut write_row: W) -> io::Result<()>
 where
-    F: FnOnce(usize) -> T,
-    F: Clone + Send + 'static,
-    T: Send + 'static,
+    R: Fn(Area) -> Vec<u8> + Sync,
+    W: FnMut(&[u8]) -> io::Result<()>,
 {
-    if num_threads == 1 {
-        return Ok(vec![f(0)]);
-    }
+    let width = area.w as usize;
+    let columns = split_at_tiles(area.x, area.w);
+    for (band_z, band_h) in split_at_tiles(area.z, area.h) {
+        let tiles: Vec<Area> = columns
+            .iter()
+            .map(|&(x, w)| Area {
+                x,
+                z: band_z,
+                w,
+                h: band_h,
+            })
+            .collect();
+        // Each thread takes the next tile until there are no tiles left
+        let next_tile = AtomicUsize::new(0);
+        let work = || {
+            let mut done = vec![];
+            loop {
+                let i = next_tile.fetch_add(1, Ordering::Relaxed);
+                if i >= tiles.len() {
+                    return done;
+                }
+                done.push((i, render(tiles[i])));
+            }
+        };
+        let mut rendered: Vec<Vec<u8>> = vec![vec![]; tiles.len()];
+        let results = if num_threads <= 1 {
+            vec![work()]
+        } else {
+            thread::scope(|s| {
+                let handles: Vec<_> = (0..num_threads.min(tiles.len()))
+                    .map(|_| s.spawn(work))
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            })
+        };
+        for (i, rgba) in results.into_iter().flatten() {
+            rendered[i] = rgba;
+        }
 
-    let mut threads = vec![];
-    for thread_id in 0..num_threads {
-        let ff = f.clone();
-        let handle = thread::spawn(move || ff(thread_id));
-        threads.push(handle);
+        let mut row = Vec::with_capacity(width * 4);
+        for z in 0..band_h as usize {
+            row.clear();
+            for (tile, rgba) in tiles.iter().zip(&rendered) {
+                let tile_w = tile.w as usize * 4;
+                row.extend_from_slice(&rgba[z * tile_w..(z + 1) * tile_w]);
+            }
+            write_row(&row)?;
+        }
     }
 
-    let mut r = vec![];
-    for h in threads {
-        r.push(h.join()?);
-    }
+    Ok(())
+}
 
-    Ok(r)
+/// Writes the rows of an image as a pyramid of `RENDER_TILE_SIZE` png tiles,
+/// in `<dir>/<z>/<x>/<y>.png`. Each zoom level is half the size of the next
+/// one, using the top-left pixel of each 2x2 square, to keep the colors of
+/// the biomes. The tiles at the right and bottom edges are padded with
+/// transparent pixels.
+struct TilePyramid {
+    dir: PathBuf,
+    /// The last level has the full resolution
+    levels: Vec<PyramidLevel>,
 }
 
-// Spawn n threads and wait for them to finish, returning a vector of the results
-// Optimization: when n is 1 do not spawn any threads and run the computation on the current thread
-fn run_workers<F1, F2, W, T>(
-    num_threads: usize,
-    mut get_work: F1,
-    mut process_work: F2,
-) -> Result<Vec<T>, Box<dyn std::any::Any + Send>>
-where
-    F1: FnMut() -> Option<W>,
-    F2: Clone + Send + 'static + FnMut(W) -> ControlFlow<Vec<T>, Vec<T>>,
-    W: Send + 'static,
-    T: Send + 'static,
-{
-    // Handle special case by not spawning threads
-    if num_threads == 1 {
-        let mut candidates = vec![];
+struct PyramidLevel {
+    width: usize,
+    /// Rows of the current row of tiles
+    band: Vec<u8>,
+    band_rows: usize,
+    /// Index of the current row of tiles
+    tile_y: usize,
+    /// Total number of rows received, used to skip the odd rows
+    rows: usize,
+}
 
-        while let Some(work) = get_work() {
-            let res = process_work(work);
-            match res {
-                ControlFlow::Continue(r) => {
-                    candidates.extend(r);
-                }
-                ControlFlow::Break(r) => {
-                    candidates.extend(r);
-                    return Ok(candidates);
-                }
+impl TilePyramid {
+    fn new(dir: PathBuf, width: usize, height: usize) -> Self {
+        let mut levels = vec![];
+        let (mut w, mut h) = (width.max(1), height.max(1));
+        loop {
+            levels.push(PyramidLevel {
+                width: w,
+                band: vec![],
+                band_rows: 0,
+                tile_y: 0,
+                rows: 0,
+            });
+            if w <= RENDER_TILE_SIZE && h <= RENDER_TILE_SIZE {
+                break;
             }
+            w = w.div_ceil(2);
+            h = h.div_ceil(2);
         }
+        levels.reverse();
 
-        return Ok(candidates);
+        Self { dir, levels }
     }
 
-    let (get_work_tx, get_work_rx) = std::sync::mpsc::channel::<WorkerCommand<T, W>>();
+    fn num_zoom_levels(&self) -> usize {
+        self.levels.len()
+    }
 
-    enum WorkerCommand<T, W> {
-        GetWork(std::sync::mpsc::Sender<Option<W>>),
-        Candidates(Vec<T>),
-        Done(Vec<T>),
+    /// Add the next row of the full resolution image
+    fn push_row(&mut self, row: &[u8]) -> io::Result<()> {
+        self.push_row_to_level(self.levels.len() - 1, row)
     }
 
-    let mut threads = vec![];
-    for _thread_id in 0..num_threads {
-        let mut ff = process_work.clone();
-        let get_work_tx = get_work_tx.clone();
-        let handle = thread::spawn(move || loop {
-            let (tx, rx) = std::sync::mpsc::channel();
-            get_work_tx.send(WorkerCommand::GetWork(tx)).unwrap();
-            let work = rx.recv().unwrap();
-            if work.is_none() {
-                return;
-            }
-            let res = ff(work.unwrap());
-            match res {
-                ControlFlow::Continue(res) => {
-                    get_work_tx.send(WorkerCommand::Candidates(res)).unwrap();
-                }
-                ControlFlow::Break(res) => {
-                    get_work_tx.send(WorkerCommand::Done(res)).unwrap();
-                    return;
-                }
-            }
-        });
-        threads.push(handle);
+    fn push_row_to_level(&mut self, z: usize, row: &[u8]) -> io::Result<()> {
+        let level = &mut self.levels[z];
+        assert_eq!(row.len(), level.width * 4);
+        level.band.extend_from_slice(row);
+        level.band_rows += 1;
+        level.rows += 1;
+        if level.band_rows == RENDER_TILE_SIZE {
+            self.write_band(z)?;
+        }
+        // Only the even rows are used in the previous zoom level
+        if z > 0 && self.levels[z].rows % 2 == 1 {
+            let half: Vec<u8> = row.chunks_exact(4).step_by(2).flatten().copied().collect();
+            self.push_row_to_level(z - 1, &half)?;
+        }
+
+        Ok(())
     }
 
-    let mut res = vec![];
-    while let Ok(command) = get_work_rx.recv() {
-        match command {
-            WorkerCommand::GetWork(tx) => {
-                let work = get_work();
-                tx.send(work).unwrap();
+    /// Write the current row of tiles of level `z` to disk
+    fn write_band(&mut self, z: usize) -> io::Result<()> {
+        let level = &mut self.levels[z];
+        let t = RENDER_TILE_SIZE;
+        let y_dir = level.tile_y;
+        for tile_x in 0..level.width.div_ceil(t) {
+            let x0 = tile_x * t;
+            let tile_w = t.min(level.width - x0);
+            // Transparent padding
+            let mut tile = vec![0; t * t * 4];
+            for r in 0..level.band_rows {
+                let src = (r * level.width + x0) * 4;
+                tile[r * t * 4..(r * t + tile_w) * 4]
+                    .copy_from_slice(&level.band[src..src + tile_w * 4]);
             }
-            WorkerCommand::Candidates(r) => {
-                res.extend(r);
-            }
-            WorkerCommand::Done(r) => {
-                res.extend(r);
-                break;
+            let dir = self.dir.join(z.to_string()).join(tile_x.to_string());
+            fs::create_dir_all(&dir)?;
+            image::save_buffer(
+                dir.join(format!("{}.png", y_dir)),
+                &tile,
+                t as u32,
+                t as u32,
+                image::ColorType::Rgba8,
+            )
+            .map_err(io::Error::other)?;
+        }
+        level.band.clear();
+        level.band_rows = 0;
+        level.tile_y += 1;
+
+        Ok(())
+    }
+
+    /// Write the tiles that are not complete. Must be called after the last
+    /// row.
+    fn finish(&mut self) -> io::Result<()> {
+        for z in 0..self.levels.len() {
+            if self.levels[z].band_rows > 0 {
+                self.write_band(z)?;
             }
         }
+
+        Ok(())
     }
+}
 
-    // Drop rx end of channel, so threads that call get_work_tx.send() will panic
-    drop(get_work_rx);
+// Spawn n threads and wait for them to finish, returning a vector of the results
+// Optimization: when n is 1 do not spawn any threads and run the computation on the current thread
+fn run_threads<F, T>(num_threads: usize, f: F) -> Result<Vec<T>, Box<dyn std::any::Any + Send>>
+where
+    F: FnOnce(usize) -> T,
+    F: Clone + Send + 'static,
+    T: Send + 'static,
+{
+    if num_threads == 1 {
+        return Ok(vec![f(0)]);
+    }
 
-    // Wait for threads to finish
-    // TODO: can we skip this step here? It would be nice to exit this function as soon as one
-    // thread sends the Done command
+    let mut threads = vec![];
+    for thread_id in 0..num_threads {
+        let ff = f.clone();
+        let handle = thread::spawn(move || ff(thread_id));
+        threads.push(handle);
+    }
+
+    let mut r = vec![];
     for h in threads {
-        h.join()?;
+        r.push(h.join()?);
     }
 
-    Ok(res)
+    Ok(r)
 }
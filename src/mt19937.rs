@@ -0,0 +1,102 @@
+//! 32-bit Mersenne Twister, used by Bedrock Edition.
+
+const N: usize = 624;
+const M: usize = 397;
+const MATRIX_A: u32 = 0x9908b0df;
+const UPPER_MASK: u32 = 0x80000000;
+const LOWER_MASK: u32 = 0x7fffffff;
+
+pub struct Mt19937 {
+    state: [u32; N],
+    index: usize,
+}
+
+impl Mt19937 {
+    pub fn new(seed: u32) -> Self {
+        let mut state = [0; N];
+        state[0] = seed;
+        for i in 1..N {
+            state[i] = init_step(state[i - 1], i as u32);
+        }
+
+        Self { state, index: N }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        if self.index >= N {
+            self.twist();
+        }
+
+        let y = self.state[self.index];
+        self.index += 1;
+
+        temper(y)
+    }
+
+    fn twist(&mut self) {
+        for i in 0..N {
+            let y = (self.state[i] & UPPER_MASK) | (self.state[(i + 1) % N] & LOWER_MASK);
+            self.state[i] = self.state[(i + M) % N] ^ mix(y);
+        }
+        self.index = 0;
+    }
+}
+
+/// Equivalent to `Mt19937::new(seed).next_u32()`, but only initializes the
+/// part of the state needed to calculate the first output.
+pub fn first_u32(seed: u32) -> u32 {
+    let mut s0 = seed;
+    let mut s1 = 0;
+    let mut s = seed;
+    for i in 1..=M {
+        s = init_step(s, i as u32);
+        if i == 1 {
+            s1 = s;
+        }
+    }
+    let y = (s0 & UPPER_MASK) | (s1 & LOWER_MASK);
+    s0 = s ^ mix(y);
+
+    temper(s0)
+}
+
+fn init_step(prev: u32, i: u32) -> u32 {
+    1812433253u32
+        .wrapping_mul(prev ^ (prev >> 30))
+        .wrapping_add(i)
+}
+
+fn mix(y: u32) -> u32 {
+    (y >> 1) ^ if y & 1 != 0 { MATRIX_A } else { 0 }
+}
+
+fn temper(mut y: u32) -> u32 {
+    y ^= y >> 11;
+    y ^= (y << 7) & 0x9d2c5680;
+    y ^= (y << 15) & 0xefc60000;
+    y ^= y >> 18;
+
+    y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_outputs() {
+        // Default seed of std::mt19937
+        let mut r = Mt19937::new(5489);
+        assert_eq!(r.next_u32(), 3499211612);
+        assert_eq!(r.next_u32(), 581869302);
+        let mut r = Mt19937::new(0);
+        assert_eq!(r.next_u32(), 2357136044);
+    }
+
+    #[test]
+    fn first_u32_matches_full_generator() {
+        for seed in [0, 1, 5489, 0x1f1f1f1f, u32::MAX] {
+            assert_eq!(first_u32(seed), Mt19937::new(seed).next_u32());
+        }
+    }
+}
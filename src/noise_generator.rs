@@ -14,6 +14,11 @@ pub struct NoiseGeneratorPerlin {
 impl NoiseGeneratorPerlin {
     pub fn new(seed: i64) -> Self {
         let mut r = JavaRng::with_seed(seed as u64);
+        Self::with_rng(&mut r)
+    }
+
+    /// Initialize the noise using an existing rng. This consumes 262 calls.
+    pub fn with_rng(r: &mut JavaRng) -> Self {
         let a = r.next_double() * 256.0;
         let b = r.next_double() * 256.0;
         let c = r.next_double() * 256.0;
@@ -78,6 +83,161 @@ impl NoiseGeneratorPerlin {
     }
 }
 
+/// Octave perlin noise generator, as used by the nether biomes.
+///
+/// Uses the JavaRng as seed, so it only has 48 bits of entropy.
+pub struct NoiseGeneratorOctave {
+    // (noise, amplitude, lacunarity)
+    octaves: Vec<(NoiseGeneratorPerlin, f64, f64)>,
+}
+
+impl NoiseGeneratorOctave {
+    pub fn new(r: &mut JavaRng, amplitudes: &[f64], omin: i32) -> Self {
+        let len = amplitudes.len();
+        // Index of the octave with frequency 1
+        let j = -omin;
+        assert!(j >= len as i32 - 1, "Positive octaves are not supported");
+
+        // The noise of octave 0 is always initialized first, even if it is not used.
+        // Then the octaves are initialized starting from the highest frequency.
+        let first = NoiseGeneratorPerlin::with_rng(r);
+        let mut levels: Vec<Option<NoiseGeneratorPerlin>> = (0..len).map(|_| None).collect();
+        if (j as usize) < len && amplitudes[j as usize] != 0.0 {
+            levels[j as usize] = Some(first);
+        }
+        for k in (0..j).rev() {
+            let k = k as usize;
+            if k < len && amplitudes[k] != 0.0 {
+                levels[k] = Some(NoiseGeneratorPerlin::with_rng(r));
+            } else {
+                r.next_n_calls(262);
+            }
+        }
+
+        let mut lacuna = 2.0_f64.powi(omin);
+        let mut persist = 2.0_f64.powi(len as i32 - 1) / (((1u64 << len) as f64) - 1.0);
+        let mut octaves = Vec::with_capacity(len);
+        for (level, amplitude) in levels.into_iter().zip(amplitudes) {
+            if let Some(noise) = level {
+                octaves.push((noise, amplitude * persist, lacuna));
+            }
+            lacuna *= 2.0;
+            persist *= 0.5;
+        }
+
+        Self { octaves }
+    }
+
+    pub fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let mut v = 0.0;
+
+        for (p, amplitude, lf) in &self.octaves {
+            v += amplitude * p.get_ocean_temp(x * lf, y * lf, z * lf);
+        }
+
+        v
+    }
+}
+
+/// Double perlin noise generator, as used by the nether biomes.
+///
+/// Uses the JavaRng as seed, so it only has 48 bits of entropy.
+pub struct NoiseGeneratorDoublePerlin {
+    amplitude: f64,
+    octave_a: NoiseGeneratorOctave,
+    octave_b: NoiseGeneratorOctave,
+}
+
+impl NoiseGeneratorDoublePerlin {
+    pub fn new(r: &mut JavaRng, amplitudes: &[f64], omin: i32) -> Self {
+        let octave_a = NoiseGeneratorOctave::new(r, amplitudes, omin);
+        let octave_b = NoiseGeneratorOctave::new(r, amplitudes, omin);
+
+        let len = len_without_start_zeros_and_end_zeros(amplitudes);
+        let amplitude = (10.0 / 6.0) * (len as f64) / ((len + 1) as f64);
+
+        Self {
+            amplitude,
+            octave_a,
+            octave_b,
+        }
+    }
+
+    pub fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let f: f64 = 337.0 / 331.0;
+        let mut v = 0.0;
+
+        v += self.octave_a.sample(x, y, z);
+        v += self.octave_b.sample(x * f, y * f, z * f);
+
+        v * self.amplitude
+    }
+}
+
+/// 2D simplex noise generator, used by the End islands.
+///
+/// Uses the JavaRng as seed, so it only has 48 bits of entropy.
+pub struct NoiseGeneratorSimplex {
+    d: [i32; 256],
+}
+
+impl NoiseGeneratorSimplex {
+    /// Initialize the noise using an existing rng. This consumes 262 calls.
+    pub fn with_rng(r: &mut JavaRng) -> Self {
+        // The initialization is the same as the perlin noise, but the 2D
+        // simplex noise does not use the coordinate offsets
+        let d = NoiseGeneratorPerlin::with_rng(r).d;
+
+        Self { d }
+    }
+
+    fn p(&self, i: i32) -> i32 {
+        self.d[(i & 0xFF) as usize]
+    }
+
+    pub fn sample_2d(&self, x: f64, y: f64) -> f64 {
+        let sqrt3 = 3.0_f64.sqrt();
+        let f2 = 0.5 * (sqrt3 - 1.0);
+        let g2 = (3.0 - sqrt3) / 6.0;
+
+        let d0 = (x + y) * f2;
+        let i = (x + d0).floor() as i32;
+        let j = (y + d0).floor() as i32;
+        let d1 = (i + j) as f64 * g2;
+        let x0 = x - (i as f64 - d1);
+        let y0 = y - (j as f64 - d1);
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let x1 = x0 - i1 as f64 + g2;
+        let y1 = y0 - j1 as f64 + g2;
+        let x2 = x0 - 1.0 + 2.0 * g2;
+        let y2 = y0 - 1.0 + 2.0 * g2;
+
+        let ii = i & 0xFF;
+        let jj = j & 0xFF;
+        let gi0 = self.p(ii + self.p(jj)) % 12;
+        let gi1 = self.p(ii + i1 + self.p(jj + j1)) % 12;
+        let gi2 = self.p(ii + 1 + self.p(jj + 1)) % 12;
+
+        let n0 = simplex_corner(gi0, x0, y0);
+        let n1 = simplex_corner(gi1, x1, y1);
+        let n2 = simplex_corner(gi2, x2, y2);
+
+        70.0 * (n0 + n1 + n2)
+    }
+}
+
+fn simplex_corner(idx: i32, x: f64, y: f64) -> f64 {
+    let t = 0.5 - x * x - y * y;
+    if t < 0.0 {
+        0.0
+    } else {
+        let t = t * t;
+        // The z coordinate is always 0, so the z component of the gradient
+        // is not needed
+        t * t * indexed_lerp(idx, x, y, 0.0)
+    }
+}
+
 // Split d1 between fractional part and integer part.
 // Return integer part, and mutate argument to always be between 0 and 1
 fn split_int(d1: &mut f64) -> i32 {
@@ -3,21 +3,30 @@ use crate::chunk::Point;
 use crate::gen_pairs3::GenPairs3L;
 use crate::java_rng::JavaRng;
 use crate::seed_info::MinecraftVersion;
+use crate::xoroshiro128plusplus::Xoroshiro128PlusPlus;
 
 /// Return all the 48-bit world seeds that can generate dungeons at the specified coordinates.
-pub fn dungeon_seed_finder(dungeons: &[(i32, i32, i32)], version: MinecraftVersion) -> Vec<u64> {
+pub fn dungeon_seed_finder(
+    dungeons: &[(i32, i32, i32)],
+    version: MinecraftVersion,
+) -> Result<Vec<u64>, String> {
     dungeon_seed_finder_range(dungeons, version, 0, 1 << 48)
 }
 
 /// Return all the 48-bit world seeds that can generate dungeons at the specified coordinates.
 /// Range: [0, 1 << 48)
+///
+/// Returns an error if the version is not supported, see `dungeon_finder_supports`.
 pub fn dungeon_seed_finder_range(
     dungeons: &[(i32, i32, i32)],
     version: MinecraftVersion,
     range_lo: u64,
     range_hi: u64,
-) -> Vec<u64> {
+) -> Result<Vec<u64>, String> {
     assert!(range_hi <= (1 << 48));
+    if !dungeon_finder_supports(version) {
+        return Err(unsupported_dungeon_version(version));
+    }
     let chunks: Vec<_> = dungeons
         .iter()
         .cloned()
@@ -25,20 +34,10 @@ pub fn dungeon_seed_finder_range(
             let Chunk {
                 x: chunk_x,
                 z: chunk_z,
-            } = spawner_coordinates_to_chunk(x as i64, z as i64);
+            } = spawner_coordinates_to_chunk_version(x as i64, z as i64, version);
             ((chunk_x, chunk_z), (x, y, z))
         })
         .collect();
-    let check_fn = |world_seed: u64, chunk_x, chunk_z, (x, y, z)| match version {
-        MinecraftVersion::JavaAlpha1_2_5 => Some(
-            populate_alpha_1_0_4_check_dungeon(world_seed as i64, chunk_x, chunk_z, (x, y, z))
-                .is_some(),
-        ),
-        MinecraftVersion::JavaBeta => {
-            populate_alpha_1_2_6_check_dungeon(world_seed as i64, chunk_x, chunk_z, (x, y, z))
-        }
-        _ => unimplemented!(),
-    };
     let mut r = vec![];
     'nextseed: for world_seed in range_lo..range_hi {
         //let world_seed = 536274160436487309 & ((1 << 48) - 1);
@@ -46,7 +45,7 @@ pub fn dungeon_seed_finder_range(
         let mut misses = 0;
         let expected_matches = chunks.len();
         for ((chunk_x, chunk_z), (x, y, z)) in chunks.iter().cloned() {
-            match check_fn(world_seed, chunk_x, chunk_z, (x, y, z)) {
+            match check_dungeon(world_seed as i64, version, chunk_x, chunk_z, (x, y, z))? {
                 None => {
                     // This chunk has a lava lake, so we don't know how to check for dungeons
                     // (we could check using bruteforce but it's probably not worth it)
@@ -71,7 +70,86 @@ pub fn dungeon_seed_finder_range(
         r.push(world_seed);
     }
 
-    r
+    Ok(r)
+}
+
+fn unsupported_dungeon_version(version: MinecraftVersion) -> String {
+    format!("Dungeons are not supported in version {:?}", version)
+}
+
+/// Returns true if `check_dungeon` is implemented for this version.
+pub fn dungeon_finder_supports(version: MinecraftVersion) -> bool {
+    match version {
+        MinecraftVersion::JavaAlpha1_2_5 | MinecraftVersion::JavaBeta => true,
+        MinecraftVersion::Java1_2 | MinecraftVersion::Java1_3 => false,
+        v => v <= MinecraftVersion::Java1_17,
+    }
+}
+
+/// Check if the chunk population of this world seed can generate a dungeon at the specified
+/// spawner coordinates. The chunk must be the one returned by
+/// `spawner_coordinates_to_chunk_version`.
+///
+/// None: maybe
+/// true: probably yes
+/// false: probably no
+///
+/// Returns an error if the version is not supported, see `dungeon_finder_supports`.
+pub fn check_dungeon(
+    world_seed: i64,
+    version: MinecraftVersion,
+    chunk_x: i32,
+    chunk_z: i32,
+    dungeon: (i32, i32, i32),
+) -> Result<Option<bool>, String> {
+    Ok(match version {
+        MinecraftVersion::JavaAlpha1_2_5 => Some(
+            populate_alpha_1_0_4_check_dungeon(world_seed, chunk_x, chunk_z, dungeon).is_some(),
+        ),
+        MinecraftVersion::JavaBeta => {
+            populate_alpha_1_2_6_check_dungeon(world_seed, chunk_x, chunk_z, dungeon)
+        }
+        MinecraftVersion::Java1_7 | MinecraftVersion::Java1_9 | MinecraftVersion::Java1_11 => {
+            Some(populate_1_7_check_dungeon(world_seed, chunk_x, chunk_z, dungeon).is_some())
+        }
+        MinecraftVersion::Java1_13
+        | MinecraftVersion::Java1_14
+        | MinecraftVersion::Java1_15
+        | MinecraftVersion::Java1_16_1
+        | MinecraftVersion::Java1_16
+        | MinecraftVersion::Java1_17 => Some(
+            populate_1_13_check_dungeon(world_seed, version, chunk_x, chunk_z, dungeon).is_some(),
+        ),
+        _ => return Err(unsupported_dungeon_version(version)),
+    })
+}
+
+/// Returns true if the world seed matches most of the dungeons: up to a quarter of them can be
+/// missing, because this chunk may have a structure or more than one dungeon, which breaks the
+/// simulation of the population rng.
+///
+/// Returns an error if the version is not supported, see `dungeon_finder_supports`.
+pub fn dungeons_match(
+    dungeons: &[(i32, i32, i32)],
+    version: MinecraftVersion,
+    world_seed: i64,
+) -> Result<bool, String> {
+    let max_misses = dungeons.len() / 4;
+    let mut misses = 0;
+    for &(x, y, z) in dungeons {
+        let Chunk {
+            x: chunk_x,
+            z: chunk_z,
+        } = spawner_coordinates_to_chunk_version(x as i64, z as i64, version);
+        if check_dungeon(world_seed, version, chunk_x, chunk_z, (x, y, z))? != Some(true) {
+            misses += 1;
+            if misses > max_misses {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
 }
 
 /// Mutate the RNG in the same way as the code that tries to generate water lakes.
@@ -323,12 +401,112 @@ impl MossyFloor {
         )
     }
 
+    /// Build the mossy floor from the 11x11 area of blocks below the spawner, as returned by
+    /// `anvil::find_dungeons`: `blocks[(dz + 5) * 11 + (dx + 5)]`.
+    ///
+    /// The size of the dungeon is guessed from the blocks around the floor: a 9-block wide floor
+    /// has cobblestone at distance 4 from the spawner, while a 7-block wide floor usually has
+    /// natural terrain there.
+    ///
+    /// Air is a floor tile that was not generated because there was no solid block below it, so
+    /// no `nextInt(4)` was consumed for it. Before 1.13 that air is "minecraft:air", and it is
+    /// still stored as "minecraft:air" after upgrading the world. Since 1.13 dungeons generate
+    /// "minecraft:cave_air", so in that case "minecraft:air" can only be placed by players.
+    /// Blocks that cannot have been generated as part of the floor are considered to have been
+    /// modified by players and are marked as unknown.
+    pub fn from_floor_blocks(blocks: &[String], version: MinecraftVersion) -> Result<Self, String> {
+        if blocks.len() != 11 * 11 {
+            return Err(format!(
+                "Invalid number of blocks {}, should be 11x11",
+                blocks.len()
+            ));
+        }
+        let block = |dx: i32, dz: i32| blocks[((dz + 5) * 11 + (dx + 5)) as usize].as_str();
+        let is_cobble =
+            |b: &str| b == "minecraft:cobblestone" || b == "minecraft:mossy_cobblestone";
+        // Count the cobblestone blocks in the 2 lines at distance 4 from the spawner. Only the
+        // blocks that would be part of the floor in the smallest dungeon are considered.
+        let is_9_wide = |swap_xz: bool| {
+            let cobbles = (-3..=3)
+                .flat_map(|i| [(-4, i), (4, i)])
+                .map(|(a, b)| if swap_xz { block(b, a) } else { block(a, b) })
+                .filter(|b| is_cobble(b))
+                .count();
+            cobbles > 7
+        };
+        let size = match (is_9_wide(false), is_9_wide(true)) {
+            (false, false) => DungeonSize::X7Z7,
+            (false, true) => DungeonSize::X7Z9,
+            (true, false) => DungeonSize::X9Z7,
+            (true, true) => DungeonSize::X9Z9,
+        };
+        let (a, b) = size.next_ints();
+        let (a, b) = (i32::from(a) + 3, i32::from(b) + 3);
+        let mut tiles = Vec::with_capacity(size.floor_area() as usize);
+        for dx in -a..=a {
+            for dz in -b..=b {
+                let t = match block(dx, dz) {
+                    "minecraft:cobblestone" => DungeonFloorTile::Cobble,
+                    "minecraft:mossy_cobblestone" => DungeonFloorTile::Mossy,
+                    "minecraft:cave_air" => DungeonFloorTile::Air,
+                    "minecraft:air" if version < MinecraftVersion::Java1_13 => {
+                        DungeonFloorTile::Air
+                    }
+                    _ => DungeonFloorTile::Unknown,
+                };
+                tiles.push(t);
+            }
+        }
+
+        Ok(Self { size, tiles })
+    }
+
     fn all_mossy(size: DungeonSize) -> Self {
         Self {
             size,
             tiles: vec![DungeonFloorTile::Mossy; size.floor_area() as usize],
         }
     }
+
+    /// Check if the calls to `next_int_n` generate this dungeon size and floor. The first call
+    /// must be the one that decides the size of the dungeon, right after the coordinates.
+    pub fn matches<F: FnMut(i32) -> i32>(&self, mut next_int_n: F) -> bool {
+        let (wx, wz) = self.size.next_ints();
+        if next_int_n(2) != wx as i32 || next_int_n(2) != wz as i32 {
+            return false;
+        }
+
+        for mt in &self.tiles {
+            match mt {
+                DungeonFloorTile::Mossy => {
+                    if next_int_n(4) == 0 {
+                        return false;
+                    }
+                }
+                DungeonFloorTile::Cobble => {
+                    if next_int_n(4) != 0 {
+                        return false;
+                    }
+                }
+                DungeonFloorTile::Unknown => {
+                    // Assume that the tile was not air, see dungeon_rng_bruteforce_range
+                    next_int_n(4);
+                }
+                DungeonFloorTile::Air => {}
+            }
+        }
+
+        true
+    }
+
+    /// Probability that a random rng generates this dungeon size and floor.
+    pub fn probability(&self) -> f64 {
+        self.tiles.iter().fold(0.25, |p, mt| match mt {
+            DungeonFloorTile::Mossy => p * 0.75,
+            DungeonFloorTile::Cobble => p * 0.25,
+            DungeonFloorTile::Unknown | DungeonFloorTile::Air => p,
+        })
+    }
 }
 
 /// Find a rng state that will generate the dungeon with the correct chunk offset coordinates,
@@ -667,6 +845,326 @@ pub fn populate_alpha_1_2_6_check_dungeon(
     Some(false)
 }
 
+/// Simulate the rng calls of `WorldGenLakes` that define the shape of the lake. Returns a 16x8x16
+/// mask of the blocks that will be filled with liquid, indexed by `(x * 16 + z) * 8 + y`.
+pub fn lake_shape(r: &mut JavaRng) -> [bool; 2048] {
+    let mut shape = [false; 2048];
+    let s = r.next_int_n(4) + 4;
+
+    for _ in 0..s {
+        let d0 = r.next_double() * 6.0 + 3.0;
+        let d1 = r.next_double() * 4.0 + 2.0;
+        let d2 = r.next_double() * 6.0 + 3.0;
+        let d3 = r.next_double() * (16.0 - d0 - 2.0) + 1.0 + d0 / 2.0;
+        let d4 = r.next_double() * (8.0 - d1 - 4.0) + 2.0 + d1 / 2.0;
+        let d5 = r.next_double() * (16.0 - d2 - 2.0) + 1.0 + d2 / 2.0;
+
+        for x in 1..15 {
+            for z in 1..15 {
+                for y in 1..7 {
+                    let d6 = (x as f64 - d3) / (d0 / 2.0);
+                    let d7 = (y as f64 - d4) / (d1 / 2.0);
+                    let d8 = (z as f64 - d5) / (d2 / 2.0);
+                    if d6 * d6 + d7 * d7 + d8 * d8 < 1.0 {
+                        shape[(x * 16 + z) * 8 + y] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    shape
+}
+
+/// Number of calls to nextInt(2) made by a lava lake with this shape when it generates: the upper
+/// half of the border of the lake is randomly replaced with stone.
+pub fn lava_lake_border_calls(shape: &[bool; 2048]) -> u64 {
+    let at = |x: usize, z: usize, y: usize| shape[(x * 16 + z) * 8 + y];
+    let mut calls = 0;
+    for x in 0..16 {
+        for z in 0..16 {
+            // For y < 4 the condition short-circuits before calling nextInt(2)
+            for y in 4..8 {
+                let border = !at(x, z, y)
+                    && ((x < 15 && at(x + 1, z, y))
+                        || (x > 0 && at(x - 1, z, y))
+                        || (z < 15 && at(x, z + 1, y))
+                        || (z > 0 && at(x, z - 1, y))
+                        || (y < 7 && at(x, z, y + 1))
+                        || at(x, z, y - 1));
+                if border {
+                    calls += 1;
+                }
+            }
+        }
+    }
+
+    calls
+}
+
+/// Try the 8 dungeon attempts of one chunk, starting with the rng `r`. Returns the rng state
+/// before the attempt that generates this dungeon.
+///
+/// Before 1.16.2 the coordinates are generated in (x, y, z) order, since 1.16.2 in (x, z, y)
+/// order. The failed attempts are assumed to not generate a dungeon.
+fn dungeon_attempts(
+    mut r: JavaRng,
+    (x_offset, z_offset): (i32, i32),
+    xzy: bool,
+    dungeon: (i32, i32, i32),
+) -> Option<JavaRng> {
+    for _ in 0..8 {
+        let r_clone = r;
+        let (x, y, z) = if xzy {
+            let x = r.next_int_n(16);
+            let z = r.next_int_n(16);
+            let y = r.next_int_n(256);
+            (x, y, z)
+        } else {
+            let x = r.next_int_n(16);
+            let y = r.next_int_n(256);
+            let z = r.next_int_n(16);
+            (x, y, z)
+        };
+        if (x_offset + x, y, z_offset + z) == dungeon {
+            return Some(r_clone);
+        }
+        // new WorldGenDungeons
+        // 2 calls to next_int_n(2) if there is no dungeon
+        r.next_int_n(2);
+        r.next_int_n(2);
+    }
+
+    None
+}
+
+/// Population order used from 1.7 to 1.12: water lake, lava lake, and 8 dungeon attempts using
+/// nextInt(256) for the y coordinate. Returns the rng state before the attempt that generates this
+/// dungeon, the same value as the dungeon seed.
+///
+/// The parts that depend on the terrain are bruteforced: the biome (there are no water lakes in
+/// deserts) and whether the lava lake actually generated. This assumes that no structure
+/// intersects this chunk, because structures are generated using the same rng.
+pub fn populate_1_7_check_dungeon(
+    world_seed: i64,
+    chunk_x: i32,
+    chunk_z: i32,
+    dungeon: (i32, i32, i32),
+) -> Option<JavaRng> {
+    let x_offset = chunk_x * 16 + 8;
+    let z_offset = chunk_z * 16 + 8;
+    let chunk_seed = world_seed_to_chunk_population_seed(world_seed, chunk_x, chunk_z);
+
+    for desert in [false, true] {
+        let mut r = JavaRng::with_seed(chunk_seed);
+
+        if !desert && r.next_int_n(4) == 0 {
+            let _x = r.next_int_n(16);
+            let y = r.next_int_n(256);
+            let _z = r.next_int_n(16);
+            // Lakes too close to the bottom of the world return before using the rng
+            if y > 4 {
+                lake_shape(&mut r);
+            }
+        }
+
+        let mut lava_lake_calls = 0;
+        if r.next_int_n(8) == 0 {
+            let _x = r.next_int_n(16);
+            let temp = r.next_int_n(248) + 8;
+            let y = r.next_int_n(temp);
+            let _z = r.next_int_n(16);
+            if (y < 63 || r.next_int_n(10) == 0) && y > 4 {
+                let shape = lake_shape(&mut r);
+                lava_lake_calls = lava_lake_border_calls(&shape);
+            }
+        }
+
+        // The lava lake only uses these calls if it actually generates, which depends on the
+        // terrain, so try both cases
+        for calls in [0, lava_lake_calls] {
+            let mut r = r;
+            r.next_n_calls(calls);
+            if let Some(r) = dungeon_attempts(r, (x_offset, z_offset), false, dungeon) {
+                return Some(r);
+            }
+            if lava_lake_calls == 0 {
+                break;
+            }
+        }
+    }
+
+    None
+}
+
+/// Values of `index + 10000 * step` that can be used to initialize the rng of the dungeon feature
+/// since 1.13. The index depends on the number of features and structures generated before
+/// dungeons in the same step, so it is slightly different in some biomes: deserts and swamps
+/// have fossils, and in 1.16.1 each biome has its own list of structures.
+fn dungeon_feature_salts(version: MinecraftVersion) -> &'static [u64] {
+    match version {
+        MinecraftVersion::Java1_13 | MinecraftVersion::Java1_14 | MinecraftVersion::Java1_15 => {
+            &[20003, 20004]
+        }
+        MinecraftVersion::Java1_16_1 => &[30001, 30002, 30003],
+        _ => &[30002, 30003],
+    }
+}
+
+/// Population order used from 1.13 to 1.17: each feature has its own rng, so the lakes do not
+/// affect the dungeons. Returns the rng state before the attempt that generates this dungeon.
+///
+/// The chunk must be the one returned by `spawner_coordinates_to_chunk_1_13`.
+pub fn populate_1_13_check_dungeon(
+    world_seed: i64,
+    version: MinecraftVersion,
+    chunk_x: i32,
+    chunk_z: i32,
+    dungeon: (i32, i32, i32),
+) -> Option<JavaRng> {
+    let x_offset = chunk_x * 16;
+    let z_offset = chunk_z * 16;
+    // The decoration seed is calculated using block coordinates
+    let decoration_seed = world_seed_to_chunk_population_seed_1_13(world_seed, x_offset, z_offset);
+    let xzy = version >= MinecraftVersion::Java1_16;
+
+    dungeon_feature_salts(version).iter().find_map(|salt| {
+        let r = JavaRng::with_seed(decoration_seed.wrapping_add(*salt));
+        dungeon_attempts(r, (x_offset, z_offset), xzy, dungeon)
+    })
+}
+
+/// The two dungeon features used since 1.18, as `(count, y_min, y_range)`: 10 attempts between
+/// y=0 and the top of the world, and 4 attempts between y=-58 and y=-1.
+const DUNGEON_FEATURES_1_18: [(u32, i32, u32); 2] = [(10, 0, 320), (4, -58, 58)];
+
+/// Since 1.18 the index of a feature is its position in the list of all the features of this step
+/// used by any biome. Dungeons are the first features of the underground structures step, but
+/// this may change with datapacks, so the next few indices are also tried.
+const DUNGEON_FEATURE_INDICES_1_18: std::ops::Range<u64> = 0..4;
+
+/// Given a world seed, calculate the decoration seed for the given chunk coordinates.
+/// Works for versions >= Minecraft Java 1.18, where the features use the Xoroshiro128PlusPlus rng.
+/// For older versions, use world_seed_to_chunk_population_seed_1_13 with block coordinates.
+///
+/// The rng is wrapped in a WorldgenRandom, so `nextLong` and `nextInt` use the Java algorithm on
+/// the upper bits of the Xoroshiro128PlusPlus output, see `next_long_j`.
+pub fn world_seed_to_decoration_seed_1_18(world_seed: i64, chunk_x: i32, chunk_z: i32) -> u64 {
+    let mut r = Xoroshiro128PlusPlus::with_u64_seed(world_seed as u64);
+
+    let m = r.next_long_j() | 1;
+    let n = r.next_long_j() | 1;
+
+    // (x * m + z * n) ^ world_seed, using block coordinates
+    (((chunk_x as i64 * 16)
+        .wrapping_mul(m)
+        .wrapping_add((chunk_z as i64 * 16).wrapping_mul(n)))
+        ^ world_seed) as u64
+}
+
+/// Population order used since 1.18: the dungeon features use their own Xoroshiro128PlusPlus rng,
+/// seeded with `decoration_seed + index + 10000 * step`. Since this rng has 64 bits of state, the
+/// dungeon position is not enough to check the seed, the floor is also checked if available.
+///
+/// Returns the decoration seed of the chunk if it can generate this dungeon. The chunk must be the
+/// one returned by `spawner_coordinates_to_chunk_1_13`.
+pub fn populate_1_18_check_dungeon(
+    world_seed: i64,
+    chunk_x: i32,
+    chunk_z: i32,
+    dungeon: (i32, i32, i32),
+    floor: Option<&MossyFloor>,
+) -> Option<u64> {
+    let decoration_seed = world_seed_to_decoration_seed_1_18(world_seed, chunk_x, chunk_z);
+    let (dungeon_x, dungeon_y, dungeon_z) = dungeon;
+    let (x, z) = (dungeon_x - chunk_x * 16, dungeon_z - chunk_z * 16);
+    // Deep dungeons are a different feature, with fewer attempts
+    let (count, y_min, y_range) = if dungeon_y < 0 {
+        DUNGEON_FEATURES_1_18[1]
+    } else {
+        DUNGEON_FEATURES_1_18[0]
+    };
+
+    for index in DUNGEON_FEATURE_INDICES_1_18 {
+        // Underground structures step
+        let feature_seed = decoration_seed.wrapping_add(index + 10000 * 3);
+        let mut r = Xoroshiro128PlusPlus::with_u64_seed(feature_seed);
+        for _ in 0..count {
+            let rx = r.next_int_n_j(16);
+            let rz = r.next_int_n_j(16);
+            let ry = r.next_int_n_j(y_range) + y_min;
+            if (rx, ry, rz) == (x, dungeon_y, z) {
+                let matches = match floor {
+                    Some(floor) => floor.matches(|n| r.next_int_n_j(n as u32)),
+                    None => true,
+                };
+                if matches {
+                    return Some(decoration_seed);
+                }
+                break;
+            }
+            // 2 calls to next_int_n(2) if there is no dungeon
+            r.next_int_n_j(2);
+            r.next_int_n_j(2);
+        }
+    }
+
+    None
+}
+
+/// Spawner coordinates and floor of a dungeon
+pub type DungeonClue = ((i32, i32, i32), Option<MossyFloor>);
+
+/// Find the decoration seeds of the chunk of this dungeon, for versions >= Minecraft Java 1.18.
+///
+/// Since 1.18 the dungeon rng is seeded with the 64-bit decoration seed, which is too large to
+/// bruteforce using the floor alone. Instead, this tries all the values of the upper 16 bits of
+/// a world seed with these lower 48 bits, which can be found using slime chunks or structures.
+/// The decoration seeds of 3 different chunks can be reversed into the world seed using
+/// `decoration_seed_to_world_seed_1_18`.
+pub fn dungeon_decoration_seeds_1_18(
+    dungeon: (i32, i32, i32),
+    floor: &MossyFloor,
+    lower_48: u64,
+) -> Vec<u64> {
+    let Chunk {
+        x: chunk_x,
+        z: chunk_z,
+    } = spawner_coordinates_to_chunk_1_13(i64::from(dungeon.0), i64::from(dungeon.2));
+    let lower_48 = lower_48 & ((1 << 48) - 1);
+
+    (0..(1u64 << 16))
+        .filter_map(|hi| {
+            let world_seed = ((hi << 48) | lower_48) as i64;
+            populate_1_18_check_dungeon(world_seed, chunk_x, chunk_z, dungeon, Some(floor))
+        })
+        .collect()
+}
+
+/// Returns true if the world seed matches most of the dungeons, same as `dungeons_match` but for
+/// versions >= Minecraft Java 1.18. The floors are optional, but without them the probability of
+/// false positives is much higher.
+pub fn dungeons_match_1_18(dungeons: &[DungeonClue], world_seed: i64) -> bool {
+    let max_misses = dungeons.len() / 4;
+    let mut misses = 0;
+    for ((x, y, z), floor) in dungeons {
+        let Chunk {
+            x: chunk_x,
+            z: chunk_z,
+        } = spawner_coordinates_to_chunk_1_13(*x as i64, *z as i64);
+        if populate_1_18_check_dungeon(world_seed, chunk_x, chunk_z, (*x, *y, *z), floor.as_ref())
+            .is_none()
+        {
+            misses += 1;
+            if misses > max_misses {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
 /// Convert the block coordinates of a dungeon spawner to the coordinates of the chunk that
 /// generated that dungeon.
 // chunk_x * 16 + random.nextInt(16) + 8
@@ -678,6 +1176,30 @@ pub fn spawner_coordinates_to_chunk(spawner_x: i64, spawner_z: i64) -> Chunk {
     })
 }
 
+/// Same as `spawner_coordinates_to_chunk`, but for versions >= Minecraft Java 1.13, where the
+/// dungeon coordinates no longer have the +8 offset.
+// chunk_x * 16 + random.nextInt(16)
+pub fn spawner_coordinates_to_chunk_1_13(spawner_x: i64, spawner_z: i64) -> Chunk {
+    Chunk::from_point(Point {
+        x: spawner_x,
+        z: spawner_z,
+    })
+}
+
+/// Convert the block coordinates of a dungeon spawner to the coordinates of the chunk that
+/// generated that dungeon, in any version.
+pub fn spawner_coordinates_to_chunk_version(
+    spawner_x: i64,
+    spawner_z: i64,
+    version: MinecraftVersion,
+) -> Chunk {
+    if version >= MinecraftVersion::Java1_13 {
+        spawner_coordinates_to_chunk_1_13(spawner_x, spawner_z)
+    } else {
+        spawner_coordinates_to_chunk(spawner_x, spawner_z)
+    }
+}
+
 /// Convert the block coordinates of a dungeon spawner to the number returned by the corresponding
 /// call to nextInt.
 // chunk_x * 16 + random.nextInt(16) + 8
@@ -1147,6 +1669,85 @@ pub fn chunk_population_seed_to_world_seed_64(
     ws
 }
 
+/// Given 3 different (decoration_seed, chunk_x, chunk_z), find the world seed. Works for versions
+/// >= Minecraft Java 1.18, see world_seed_to_decoration_seed_1_18.
+///
+/// Same algorithm as chunk_population_seed_to_world_seed, but since the decoration seed has 64
+/// bits, the full world seed can be recovered. The block coordinates are multiples of 16, so the
+/// lower 4 bits of the decoration seed are the lower 4 bits of the world seed, and the upper 4
+/// bits of M and N are not needed. The candidates are checked using the Xoroshiro128PlusPlus rng.
+pub fn decoration_seed_to_world_seed_1_18(
+    i1: (u64, i32, i32),
+    i2: (u64, i32, i32),
+    i3: (u64, i32, i32),
+) -> Vec<i64> {
+    let (p1, x1, z1) = i1;
+    let (p2, x2, z2) = i2;
+    let (p3, x3, z3) = i3;
+
+    if !all_unique(&[(x1, z1), (x2, z2), (x3, z3)]) {
+        panic!("Input chunks must be different, otherwise this function explodes quadratically. Found inputs: {:?}", (i1, i2, i3));
+    }
+
+    // p12 = (16 * (x1 * M + z1 * N)) ^ (16 * (x2 * M + z2 * N))
+    //     = 16 * ((x1 * M + z1 * N) ^ (x2 * M + z2 * N))
+    let p12 = p1 ^ p2;
+    let p13 = p1 ^ p3;
+    if p12 & 0xF != 0 || p13 & 0xF != 0 {
+        return vec![];
+    }
+    let (p12, p13) = (p12 >> 4, p13 >> 4);
+    let (x1, z1) = (x1 as u64, z1 as u64);
+    let (x2, z2) = (x2 as u64, z2 as u64);
+    let (x3, z3) = (x3 as u64, z3 as u64);
+    let e = |m: u64, n: u64| {
+        let a1 = x1.wrapping_mul(m).wrapping_add(z1.wrapping_mul(n));
+        let a2 = x2.wrapping_mul(m).wrapping_add(z2.wrapping_mul(n));
+        let a3 = x3.wrapping_mul(m).wrapping_add(z3.wrapping_mul(n));
+        (a1 ^ a2, a1 ^ a3)
+    };
+
+    // The constants must be odd
+    let mut cc = vec![(1u64, 1u64)];
+    // For each unknown bit, up to bit 60
+    for i in 1..60 {
+        let msk = (1u64 << (i + 1)) - 1;
+        let mut new_cc = Vec::with_capacity(cc.len() * 2);
+        for (m, n) in cc {
+            for (bm, bn) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                let (m, n) = (m | (bm << i), n | (bn << i));
+                let (e12, e13) = e(m, n);
+                if (e12 & msk) == (p12 & msk) && (e13 & msk) == (p13 & msk) {
+                    new_cc.push((m, n));
+                }
+            }
+        }
+        cc = new_cc;
+        if cc.is_empty() {
+            return vec![];
+        }
+        if cc.len() > 1048576 {
+            // See chunk_population_seed_to_world_seed
+            panic!(
+                "Too many candidates in decoration_seed_to_world_seed_1_18{:?}",
+                (i1, i2, i3)
+            );
+        }
+    }
+
+    let msk = (1u64 << 60) - 1;
+    let mut ws = vec![];
+    for (m, n) in cc {
+        let world_seed = p1 ^ (x1.wrapping_mul(m).wrapping_add(z1.wrapping_mul(n)) << 4);
+        let mut r = Xoroshiro128PlusPlus::with_u64_seed(world_seed);
+        if (r.next_long_j() | 1) as u64 & msk == m && (r.next_long_j() | 1) as u64 & msk == n {
+            ws.push(world_seed as i64);
+        }
+    }
+
+    ws
+}
+
 /// Given only one (population_seed, chunk_x, chunk_z), find world_seed:
 ///
 /// population_seed = (x * i1 + z * j1) ^ world_seed
@@ -1578,7 +2179,8 @@ mod tests {
             MinecraftVersion::JavaBeta,
             world_seed,
             world_seed + 1,
-        );
+        )
+        .unwrap();
         assert_eq!(candidates, vec![world_seed]);
     }
 
@@ -1596,7 +2198,8 @@ mod tests {
             MinecraftVersion::JavaBeta,
             world_seed,
             world_seed + 1,
-        );
+        )
+        .unwrap();
         assert_eq!(candidates, vec![world_seed]);
     }
 
@@ -2180,8 +2783,15 @@ mod tests {
         check_rng_dungeon(dungeon_rng, (wx as i32, wy as i32, wz as i32), &floor);
     }
 
+    // Helper: check the dungeon size, which is the same in all versions
+    fn check_rng_dungeon_size(mut r: JavaRng, floor: &MossyFloor) {
+        let (wx, wz) = floor.size.next_ints();
+        r.next_n_calls(3);
+        assert_eq!(r.next_int_n(2) as u8, wx);
+        assert_eq!(r.next_int_n(2) as u8, wz);
+    }
+
     #[test]
-    #[ignore] // TODO: implement 1.14 populate?
     fn test_case_1_14() {
         let world_seed: i64 = 5012404560470663646;
         let (wx, wy, wz) = (-72, 28, 218);
@@ -2197,14 +2807,22 @@ mod tests {
              MMMMMMC\n",
         )
         .unwrap();
-        panic!(
-            "Ignore unused variable warnings: {:?}",
-            (world_seed, (wx, wy, wz), floor)
-        );
+        let Chunk {
+            x: chunk_x,
+            z: chunk_z,
+        } = spawner_coordinates_to_chunk_1_13(wx, wz);
+        let dungeon_rng = populate_1_13_check_dungeon(
+            world_seed,
+            MinecraftVersion::Java1_14,
+            chunk_x,
+            chunk_z,
+            (wx as i32, wy as i32, wz as i32),
+        )
+        .unwrap();
+        check_rng_dungeon_size(dungeon_rng, &floor);
     }
 
     #[test]
-    #[ignore] // TODO: implement 1.14 populate?
     fn test_case2_1_14() {
         // slime_seed_finder dungeon-seed --spawner-x=-272 --spawner-y=24 --spawner-z=-17 --floor="CMMMMMC;MMCMMMM;CMCMMMC;MMMMMCM;MMCMMMM;CMMMMMM;CCMMMMM;"
         // "-272,24,-17,93895796360180"
@@ -2220,14 +2838,22 @@ mod tests {
              CCMMMMM\n",
         )
         .unwrap();
-        panic!(
-            "Ignore unused variable warnings: {:?}",
-            (world_seed, (wx, wy, wz), floor)
-        );
+        let Chunk {
+            x: chunk_x,
+            z: chunk_z,
+        } = spawner_coordinates_to_chunk_1_13(wx, wz);
+        let dungeon_rng = populate_1_13_check_dungeon(
+            world_seed,
+            MinecraftVersion::Java1_14,
+            chunk_x,
+            chunk_z,
+            (wx as i32, wy as i32, wz as i32),
+        )
+        .unwrap();
+        check_rng_dungeon_size(dungeon_rng, &floor);
     }
 
     #[test]
-    #[ignore] // TODO: implement 1.7 populate?
     fn test_case_1_7() {
         // slime_seed_finder dungeon-seed --spawner-x=-348 --spawner-y=16 --spawner-z=132 --floor="MMMMCMMMM;CMMMMMMMM;MCMMMMCCM;CMMCMMMCM;CCMMCMMMM;MMCCMMCMM;CMCCCCCMC;MMMMMMMCM;CMMCMMCCC;"
         // ["191650212348642,-23,7"]
@@ -2246,9 +2872,207 @@ mod tests {
              CMMCMMCCC\n",
         )
         .unwrap();
-        panic!(
-            "Ignore unused variable warnings: {:?}",
-            (world_seed, (wx, wy, wz), floor)
+        let Chunk {
+            x: chunk_x,
+            z: chunk_z,
+        } = spawner_coordinates_to_chunk(wx, wz);
+        let dungeon_rng = populate_1_7_check_dungeon(
+            world_seed,
+            chunk_x,
+            chunk_z,
+            (wx as i32, wy as i32, wz as i32),
+        )
+        .unwrap();
+        assert_eq!(dungeon_rng.get_seed(), 191650212348642);
+        check_rng_dungeon_size(dungeon_rng, &floor);
+        // The same dungeons, using the clue format of find
+        assert_eq!(
+            dungeons_match(
+                &[(wx as i32, wy as i32, wz as i32)],
+                MinecraftVersion::Java1_7,
+                world_seed
+            ),
+            Ok(true)
+        );
+        // Not supported yet, but it should not panic
+        assert!(dungeons_match(
+            &[(wx as i32, wy as i32, wz as i32)],
+            MinecraftVersion::Java1_18,
+            world_seed
+        )
+        .is_err());
+        assert!(dungeon_seed_finder_range(&[], MinecraftVersion::Java1_3, 0, 1).is_err());
+    }
+
+    #[test]
+    fn decoration_seed_reverse_1_18() {
+        let world_seed: i64 = -4172144997902289642;
+        let (x1, z1) = (3, 7);
+        let (x2, z2) = (-12, 5);
+        let (x3, z3) = (100, -33);
+        let p1 = world_seed_to_decoration_seed_1_18(world_seed, x1, z1);
+        let p2 = world_seed_to_decoration_seed_1_18(world_seed, x2, z2);
+        let p3 = world_seed_to_decoration_seed_1_18(world_seed, x3, z3);
+        let ws = decoration_seed_to_world_seed_1_18((p1, x1, z1), (p2, x2, z2), (p3, x3, z3));
+        assert_eq!(ws, vec![world_seed]);
+    }
+
+    #[test]
+    fn dungeon_floor_1_18() {
+        let world_seed: i64 = -4172144997902289642;
+        let (chunk_x, chunk_z) = (-7, 12);
+        // Generate the dungeon of the first attempt, assuming that the feature index is 0
+        let decoration_seed = world_seed_to_decoration_seed_1_18(world_seed, chunk_x, chunk_z);
+        let mut r = Xoroshiro128PlusPlus::with_u64_seed(decoration_seed.wrapping_add(30000));
+        let x = chunk_x * 16 + r.next_int_n_j(16);
+        let z = chunk_z * 16 + r.next_int_n_j(16);
+        let y = r.next_int_n_j(320);
+        let size = match (r.next_int_n_j(2), r.next_int_n_j(2)) {
+            (0, 0) => DungeonSize::X7Z7,
+            (0, 1) => DungeonSize::X7Z9,
+            (1, 0) => DungeonSize::X9Z7,
+            _ => DungeonSize::X9Z9,
+        };
+        let tiles: Vec<_> = (0..size.floor_area())
+            .map(|_| {
+                if r.next_int_n_j(4) == 0 {
+                    DungeonFloorTile::Cobble
+                } else {
+                    DungeonFloorTile::Mossy
+                }
+            })
+            .collect();
+        let mut floor = MossyFloor { size, tiles };
+
+        let Chunk { x: cx, z: cz } = spawner_coordinates_to_chunk_1_13(x as i64, z as i64);
+        assert_eq!((cx, cz), (chunk_x, chunk_z));
+        assert_eq!(
+            populate_1_18_check_dungeon(world_seed, cx, cz, (x, y, z), Some(&floor)),
+            Some(decoration_seed)
+        );
+        assert!(dungeons_match_1_18(
+            &[((x, y, z), Some(floor.clone()))],
+            world_seed
+        ));
+
+        // Change one tile
+        floor.tiles[10] = match floor.tiles[10] {
+            DungeonFloorTile::Cobble => DungeonFloorTile::Mossy,
+            _ => DungeonFloorTile::Cobble,
+        };
+        assert_eq!(
+            populate_1_18_check_dungeon(world_seed, cx, cz, (x, y, z), Some(&floor)),
+            None
+        );
+    }
+
+    #[test]
+    fn dungeons_1_18_vanilla() {
+        // Values computed using a port of the vanilla WorldgenRandom, setDecorationSeed and
+        // MonsterRoomFeature to Java: the first dungeon attempt of the chunk, with a solid floor
+        let world_seed: i64 = -4172144997902289642;
+        let dungeons = [
+            (
+                (-7, 12),
+                7808958717210975014u64,
+                (-106, 233, 203),
+                "MCMMMCMMC;MMMMMMMMC;MMMMMMMCM;MCCMMMCMM;MMMMMMMMM;MCMMCMCMM;MMMMMMMMC;",
+            ),
+            (
+                (20, -3),
+                -6693288347846262202i64 as u64,
+                (321, 18, -42),
+                "CMMMMMCMC;MCCMMCMMC;MMCMMCMCC;MCMMMMMCC;MMMCMMMMM;MMCMMCCCM;MMMMMMCMM;MMMMMMCMC;MMMMCCCCM;",
+            ),
+            (
+                (-41, -9),
+                5117676423610673494u64,
+                (-649, 174, -139),
+                "MCMMMMC;MMMMCMC;MMMMMMM;MMMMCCM;MMCMMMM;CMCMMMM;MMCMMCM;MMCMMCC;MMMMCMM;",
+            ),
+        ];
+
+        let mut decoration_seeds = vec![];
+        for ((chunk_x, chunk_z), decoration_seed, (x, y, z), floor) in dungeons {
+            let floor = MossyFloor::parse(floor).unwrap();
+            assert_eq!(
+                world_seed_to_decoration_seed_1_18(world_seed, chunk_x, chunk_z),
+                decoration_seed
+            );
+            assert_eq!(
+                populate_1_18_check_dungeon(world_seed, chunk_x, chunk_z, (x, y, z), Some(&floor)),
+                Some(decoration_seed)
+            );
+            let lower_48 = world_seed as u64 & ((1 << 48) - 1);
+            assert_eq!(
+                dungeon_decoration_seeds_1_18((x, y, z), &floor, lower_48),
+                vec![decoration_seed]
+            );
+            decoration_seeds.push((decoration_seed, chunk_x, chunk_z));
+        }
+
+        let ws = decoration_seed_to_world_seed_1_18(
+            decoration_seeds[0],
+            decoration_seeds[1],
+            decoration_seeds[2],
+        );
+        assert_eq!(ws, vec![world_seed]);
+    }
+
+    #[test]
+    fn mossy_floor_from_floor_blocks() {
+        // 9 blocks in the x axis, 7 blocks in the z axis
+        let floor = [
+            "MMMCMMM", "CMMMMMM", "MCAMMCC", "CMMCMMM", "CCMMCMM", "MMCCMMC", "CMCCCCM", "MMMMMMM",
+            "CMMCMMC",
+        ];
+        let idx = |dx: i32, dz: i32| ((dz + 5) * 11 + (dx + 5)) as usize;
+        let mut blocks = vec!["minecraft:stone".to_string(); 11 * 11];
+        for (i, line) in floor.iter().enumerate() {
+            for (j, c) in line.chars().enumerate() {
+                let (dx, dz) = (i as i32 - 4, 3 - j as i32);
+                blocks[idx(dx, dz)] = match c {
+                    'C' => "minecraft:cobblestone",
+                    'M' => "minecraft:mossy_cobblestone",
+                    'A' => "minecraft:cave_air",
+                    _ => unreachable!(),
+                }
+                .to_string();
+            }
+        }
+        // Blocks broken or placed by players
+        blocks[idx(1, -2)] = "minecraft:air".to_string();
+        blocks[idx(-4, 2)] = "minecraft:torch".to_string();
+
+        let mossy_floor =
+            MossyFloor::from_floor_blocks(&blocks, MinecraftVersion::Java1_13).unwrap();
+        assert_eq!(
+            mossy_floor.to_pretty_string(),
+            "M?MCMMM\n\
+             CMMMMMM\n\
+             MCAMMCC\n\
+             CMMCMMM\n\
+             CCMMCMM\n\
+             MMCCM?C\n\
+             CMCCCCM\n\
+             MMMMMMM\n\
+             CMMCMMC\n"
+        );
+
+        // Before 1.13 the air generated by dungeons is "minecraft:air"
+        let mossy_floor =
+            MossyFloor::from_floor_blocks(&blocks, MinecraftVersion::Java1_11).unwrap();
+        assert_eq!(
+            mossy_floor.to_pretty_string(),
+            "M?MCMMM\n\
+             CMMMMMM\n\
+             MCAMMCC\n\
+             CMMCMMM\n\
+             CCMMCMM\n\
+             MMCCMAC\n\
+             CMCCCCM\n\
+             MMMMMMM\n\
+             CMMCMMC\n"
         );
     }
 
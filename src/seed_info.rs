@@ -1,6 +1,7 @@
 use crate::chunk::Chunk;
 use crate::chunk::Point;
 use crate::chunk::Point4;
+use crate::biome_info::biome_id;
 use crate::biome_layers::Area;
 use crate::biome_layers::Map;
 use std::collections::HashMap;
@@ -36,6 +37,10 @@ pub enum MinecraftVersion {
     Java1_16, // From 1.16.2 to 1.16.5
     Java1_17,
     Java1_18,
+    Java1_19, // Deep dark and mangrove swamp
+    Java1_20, // Cherry grove
+    Java1_21_3, // From 1.21 to 1.21.3
+    Java1_21, // Pale garden, from 1.21.4
 }
 
 impl MinecraftVersion {
@@ -53,6 +58,10 @@ impl MinecraftVersion {
             MinecraftVersion::Java1_16 => 51, // actually 52 but bamboo jungle is inlined...
             MinecraftVersion::Java1_17 => 51, // actually 52 but bamboo jungle is inlined...
             MinecraftVersion::Java1_18 => 9, // TODO: may change
+            MinecraftVersion::Java1_19 => 9,
+            MinecraftVersion::Java1_20 => 9,
+            MinecraftVersion::Java1_21_3 => 9,
+            MinecraftVersion::Java1_21 => 9,
             _ => panic!("Biome generator for version {:?} is not implemented", self),
         }
     }
@@ -84,6 +93,84 @@ impl FromStr for MinecraftVersion {
             }
             "1.17" => MinecraftVersion::Java1_17,
             "1.18" => MinecraftVersion::Java1_18,
+            "1.19" => MinecraftVersion::Java1_19,
+            "1.20" => MinecraftVersion::Java1_20,
+            "1.21" => {
+                // Same as 1.16: the pale garden was added in 1.21.4, so the
+                // older 1.21 versions must be explicitly selected
+                match trim_at_dot(3, s) {
+                    "1.21.0" | "1.21.1" | "1.21.2" | "1.21.3" => MinecraftVersion::Java1_21_3,
+                    _ => MinecraftVersion::Java1_21,
+                }
+            }
+            _ => return Err(s.to_string())
+        })
+    }
+}
+
+/// Minecraft edition. Starting from 1.18, Bedrock Edition uses the same biome
+/// noise as Java Edition, but most of the other features are different.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edition {
+    #[default]
+    Java,
+    Bedrock,
+}
+
+impl FromStr for Edition {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "java" => Edition::Java,
+            "bedrock" => Edition::Bedrock,
+            _ => return Err(s.to_string())
+        })
+    }
+}
+
+impl MinecraftVersion {
+    /// Parse a version string of the given edition. Bedrock versions are
+    /// converted into the Java version with the same biome generation, so only
+    /// Bedrock 1.18 and later are supported.
+    pub fn parse_edition(s: &str, edition: Edition) -> Result<Self, String> {
+        match edition {
+            Edition::Java => s.parse(),
+            Edition::Bedrock => {
+                let x = trim_at_dot(2, s);
+                Ok(match x {
+                    "1.18" => MinecraftVersion::Java1_18,
+                    "1.19" => MinecraftVersion::Java1_19,
+                    "1.20" => MinecraftVersion::Java1_20,
+                    "1.21" => {
+                        // The pale garden was added in Bedrock 1.21.50
+                        match s.split('.').nth(2).map(|patch| patch.parse::<u32>()) {
+                            Some(Ok(patch)) if patch < 50 => MinecraftVersion::Java1_21_3,
+                            Some(Err(_)) => return Err(s.to_string()),
+                            _ => MinecraftVersion::Java1_21,
+                        }
+                    }
+                    _ => return Err(s.to_string())
+                })
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dimension {
+    Overworld,
+    Nether,
+    End,
+}
+
+impl FromStr for Dimension {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "overworld" => Dimension::Overworld,
+            "nether" | "the_nether" => Dimension::Nether,
+            "end" | "the_end" => Dimension::End,
             _ => return Err(s.to_string())
         })
     }
@@ -120,6 +207,10 @@ pub struct Options {
     pub error_margin_slime_chunks: u8,
     #[serde(default, skip_serializing_if = "is_default")]
     pub error_margin_slime_chunks_negative: u8,
+    // Maximum difference between the log-likelihood of the slime evidence of a candidate and the
+    // log-likelihood of a perfect match. If not set, slime::DEFAULT_SLIME_EVIDENCE_MARGIN is used.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub slime_evidence_margin: Option<f64>,
     #[serde(flatten)]
     pub other: HashMap<String, serde_json::Value>,
 }
@@ -134,6 +225,152 @@ pub struct TreasureMap {
     pub map: Vec<u8>,
 }
 
+/// A dungeon with a mob spawner, used to find the population seed of the chunk
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct Dungeon {
+    /// Block coordinates of the spawner
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+    /// Block layout of the dungeon floor, in the format used by
+    /// `MossyFloor::parse`
+    pub floor: String,
+}
+
+/// Slimes seen spawning in one chunk, used as a weighted slime chunk clue: seeing many slimes
+/// is strong evidence of a slime chunk, and spending a long time without seeing any is strong
+/// evidence of a normal chunk.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct SlimeEvidence {
+    /// Chunk coordinates
+    pub x: i32,
+    pub z: i32,
+    /// Number of slimes seen spawning below y=40 in this chunk
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub slimes: u32,
+    /// Minutes spent near the chunk while slimes could spawn, or 0 if unknown
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub minutes: f64,
+}
+
+impl SlimeEvidence {
+    pub fn chunk(&self) -> Chunk {
+        Chunk::new(self.x, self.z)
+    }
+}
+
+impl TreasureMap {
+    /// Convert the treasure map into a biome map at 1:2 scale, using ocean,
+    /// plains and river as the only biomes. Unknown pixels are marked as 255.
+    /// Panics if the map contains an invalid pixel value.
+    pub fn to_map(&self) -> Map {
+        let mut pmap = Map::new(Area {
+            x: (-64 + 256 * self.fragment_x) / 2,
+            z: (-64 + 256 * self.fragment_z) / 2,
+            w: 128,
+            h: 128,
+        });
+        for (i, v) in self.map.iter().enumerate() {
+            let (x, z) = (i % 128, i / 128);
+            pmap.a[(x, z)] = match v {
+                0 => biome_id::ocean,
+                1 => biome_id::plains,
+                2 => biome_id::river,
+                // Unknown biome
+                255 => 255,
+                _ => panic!("Invalid id: {}", v),
+            };
+        }
+
+        pmap
+    }
+}
+
+/// A map item made by a player, as stored in the `data/map_<id>.dat` files of the world.
+/// The pixels are map color ids from Java Edition 1.13 and later: `base_color * 4 + shade`.
+/// Only used to remove 64-bit candidates, see `biome_layers::filter_seeds_using_land_and_water`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct ItemMap {
+    pub x_center: i64,
+    pub z_center: i64,
+    /// Zoom level, from 0 (1 block per pixel) to 4 (16x16 blocks per pixel)
+    pub scale: u8,
+    pub colors: Vec<u8>,
+}
+
+impl ItemMap {
+    /// Color of unexplored pixels
+    const COLOR_NONE: u8 = 0;
+    /// Color of ice, used in frozen oceans but also in frozen rivers and lakes
+    const COLOR_ICE: u8 = 5;
+    /// Color of water. The shade depends on the depth of the water
+    const COLOR_WATER: u8 = 12;
+
+    /// Number of blocks per pixel
+    pub fn blocks_per_pixel(&self) -> i64 {
+        1 << self.scale
+    }
+
+    /// Convert the map into a biome map with one element per pixel, so at 1:2^scale scale.
+    /// The map colors depend on the surface blocks, not on the biomes, so deep water is marked as
+    /// ocean, land is marked as plains, and everything else is marked as 255. Shallow water and
+    /// ice are also unknown, because they are common in lakes and swamps.
+    /// Panics if the map does not have 128x128 pixels.
+    pub fn to_map(&self) -> Map {
+        assert_eq!(self.colors.len(), 128 * 128, "Invalid map size");
+        let b = self.blocks_per_pixel();
+        // Same as the game, this uses integer division rounding towards 0
+        let mut pmap = Map::new(Area {
+            x: self.x_center / b - 64,
+            z: self.z_center / b - 64,
+            w: 128,
+            h: 128,
+        });
+        for (i, v) in self.colors.iter().enumerate() {
+            let (x, z) = (i % 128, i / 128);
+            let (color, shade) = (v / 4, v % 4);
+            pmap.a[(x, z)] = match color {
+                Self::COLOR_NONE | Self::COLOR_ICE => 255,
+                // Shade 0 means that the water is more than 8 or 10 blocks deep
+                Self::COLOR_WATER if shade == 0 => biome_id::ocean,
+                Self::COLOR_WATER => 255,
+                _ => biome_id::plains,
+            };
+        }
+
+        pmap
+    }
+
+    /// Land and water observations, using the same biomes as `to_map`, sampled once every `step`
+    /// pixels in each axis. The coordinates are the block in the center of each pixel.
+    pub fn land_and_water(&self, step: usize) -> Vec<(BiomeId, Point)> {
+        let pmap = self.to_map();
+        let area = pmap.area();
+        let b = self.blocks_per_pixel();
+        let mut v = vec![];
+        for x in (0..area.w as usize).step_by(step) {
+            for z in (0..area.h as usize).step_by(step) {
+                let id = pmap.a[(x, z)];
+                if id != 255 {
+                    let p = Point {
+                        x: (area.x + x as i64) * b + b / 2,
+                        z: (area.z + z as i64) * b + b / 2,
+                    };
+                    v.push((BiomeId(id), p));
+                }
+            }
+        }
+
+        v
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 #[serde(deny_unknown_fields)]
@@ -180,6 +417,8 @@ pub struct SeedStructures {
 pub struct SeedInfo {
     /// Minecraft version used to generate the world
     pub version: String,
+    /// Java or Bedrock Edition
+    pub edition: Edition,
     /// Seed of the world, if known
     pub world_seed: Option<i64>,
     /// Hashed world seed. Starting from Minecraft 1.15, this is sent by the server
@@ -190,8 +429,17 @@ pub struct SeedInfo {
     pub options: Options,
     pub biomes: HashMap<BiomeId, Vec<Point>>,
     pub biomes_quarter_scale: HashMap<BiomeId, Vec<Point4>>,
+    /// Biomes observed in the nether
+    pub nether_biomes: HashMap<BiomeId, Vec<Point>>,
+    /// Biomes observed in the End
+    pub end_biomes: HashMap<BiomeId, Vec<Point>>,
     pub end_pillars: Vec<u8>,
     pub treasure_maps: Vec<TreasureMap>,
+    /// Maps made by players
+    pub item_maps: Vec<ItemMap>,
+    pub dungeons: Vec<Dungeon>,
+    /// Weighted slime chunk clues
+    pub slime_evidence: Vec<SlimeEvidence>,
     pub positive: SeedStructures,
     // Coords of structures that do not exist, useful to remove duplicates
     pub negative: SeedStructures,
@@ -210,7 +458,19 @@ impl SeedInfo {
     }
 
     pub fn version(&self) -> Result<MinecraftVersion, String> {
-        self.version.parse()
+        MinecraftVersion::parse_edition(&self.version, self.edition)
+    }
+
+    /// Add the maps that are not already in `item_maps`, returns the number of added maps
+    pub fn add_item_maps<I: IntoIterator<Item = ItemMap>>(&mut self, item_maps: I) -> usize {
+        let old_len = self.item_maps.len();
+        for item_map in item_maps {
+            if !self.item_maps.contains(&item_map) {
+                self.item_maps.push(item_map);
+            }
+        }
+
+        self.item_maps.len() - old_len
     }
 }
 
@@ -220,6 +480,9 @@ impl SeedInfo {
 pub struct SeedInfoV0_1 {
     /// Minecraft version used to generate the world
     pub version: String,
+    /// Java or Bedrock Edition
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub edition: Edition,
     /// Seed of the world, if known
     #[serde(default, skip_serializing_if = "is_default", with = "opt_string")]
     pub world_seed: Option<i64>,
@@ -238,10 +501,26 @@ pub struct SeedInfoV0_1 {
     #[serde(default, skip_serializing_if = "is_default")]
     #[serde(deserialize_with = "deserialize_biomes4")]
     pub biomes_quarter_scale: HashMap<BiomeId, Vec<Point4>>,
+    /// Biomes observed in the nether
+    #[serde(default, skip_serializing_if = "is_default")]
+    #[serde(deserialize_with = "deserialize_biomes")]
+    pub nether_biomes: HashMap<BiomeId, Vec<Point>>,
+    /// Biomes observed in the End
+    #[serde(default, skip_serializing_if = "is_default")]
+    #[serde(deserialize_with = "deserialize_biomes")]
+    pub end_biomes: HashMap<BiomeId, Vec<Point>>,
     #[serde(default, skip_serializing_if = "is_default")]
     pub end_pillars: Vec<u8>,
     #[serde(default, skip_serializing_if = "is_default")]
     pub treasure_maps: Vec<TreasureMap>,
+    /// Maps made by players
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub item_maps: Vec<ItemMap>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub dungeons: Vec<Dungeon>,
+    /// Weighted slime chunk clues
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub slime_evidence: Vec<SlimeEvidence>,
     #[serde(flatten)]
     pub positive: SeedStructures,
     // Coords of structures that do not exist, useful to remove duplicates
@@ -257,14 +536,20 @@ impl From<SeedInfoV0_1> for SeedInfo {
     fn from(s: SeedInfoV0_1) -> SeedInfo {
         SeedInfo {
             version: s.version,
+            edition: s.edition,
             world_seed: s.world_seed,
             world_seed_hash: s.world_seed_hash,
             description: s.description,
             options: s.options,
             biomes: s.biomes,
             biomes_quarter_scale: s.biomes_quarter_scale,
+            nether_biomes: s.nether_biomes,
+            end_biomes: s.end_biomes,
             end_pillars: s.end_pillars,
             treasure_maps: s.treasure_maps,
+            item_maps: s.item_maps,
+            dungeons: s.dungeons,
+            slime_evidence: s.slime_evidence,
             positive: s.positive,
             negative: s.negative,
             and: s.and,
@@ -276,14 +561,20 @@ impl From<SeedInfo> for SeedInfoV0_1 {
     fn from(s: SeedInfo) -> SeedInfoV0_1 {
         SeedInfoV0_1 {
             version: s.version,
+            edition: s.edition,
             world_seed: s.world_seed,
             world_seed_hash: s.world_seed_hash,
             description: s.description,
             options: s.options,
             biomes: s.biomes,
             biomes_quarter_scale: s.biomes_quarter_scale,
+            nether_biomes: s.nether_biomes,
+            end_biomes: s.end_biomes,
             end_pillars: s.end_pillars,
             treasure_maps: s.treasure_maps,
+            item_maps: s.item_maps,
+            dungeons: s.dungeons,
+            slime_evidence: s.slime_evidence,
             positive: s.positive,
             negative: s.negative,
             and: s.and,
@@ -596,6 +887,30 @@ mod tests {
         assert_eq!(seed_info.biomes[&BiomeId(7)], vec![Point { x: 0, z: 0 }, Point { x: 2, z: 2 }]);
     }
 
+    #[test]
+    fn item_map_land_and_water() {
+        // Map made at x = -100, z = 0 with scale 2
+        let mut colors = vec![0; 128 * 128];
+        // Deep water, shallow water, grass, ice
+        colors[0] = 12 * 4;
+        colors[1] = 12 * 4 + 2;
+        colors[128] = 4 + 1;
+        colors[129] = 5 * 4 + 1;
+        let item_map = ItemMap { x_center: -320, z_center: 192, scale: 2, colors };
+        let m = item_map.to_map();
+        assert_eq!(m.area(), Area { x: -144, z: -16, w: 128, h: 128 });
+        assert_eq!(m.a[(0, 0)], biome_id::ocean);
+        assert_eq!(m.a[(1, 0)], 255);
+        assert_eq!(m.a[(0, 1)], biome_id::plains);
+        assert_eq!(m.a[(1, 1)], 255);
+        assert_eq!(m.a[(2, 0)], 255);
+
+        assert_eq!(item_map.land_and_water(1), vec![
+            (BiomeId(biome_id::ocean), Point { x: -576 + 2, z: -64 + 2 }),
+            (BiomeId(biome_id::plains), Point { x: -576 + 2, z: -64 + 4 + 2 }),
+        ]);
+    }
+
     #[test]
     fn world_seed_string() {
         let json = r#"{
@@ -642,4 +957,37 @@ mod tests {
         assert_eq!(MinecraftVersion::from_str("1.16.2"), Ok(MinecraftVersion::Java1_16));
         assert_eq!(MinecraftVersion::from_str("1.16.3"), Ok(MinecraftVersion::Java1_16));
     }
+
+    #[test]
+    fn parse_bedrock_version() {
+        let parse = |s| MinecraftVersion::parse_edition(s, Edition::Bedrock);
+        assert_eq!(parse("1.18.30"), Ok(MinecraftVersion::Java1_18));
+        assert_eq!(parse("1.20.80"), Ok(MinecraftVersion::Java1_20));
+        assert_eq!(parse("1.21.40"), Ok(MinecraftVersion::Java1_21_3));
+        assert_eq!(parse("1.21.50"), Ok(MinecraftVersion::Java1_21));
+        assert_eq!(parse("1.21"), Ok(MinecraftVersion::Java1_21));
+        // Before 1.18 the biome generation is different
+        assert!(parse("1.17.40").is_err());
+    }
+
+    #[test]
+    fn parse_1_21_version() {
+        assert_eq!(MinecraftVersion::from_str("1.19.2"), Ok(MinecraftVersion::Java1_19));
+        assert_eq!(MinecraftVersion::from_str("1.20.6"), Ok(MinecraftVersion::Java1_20));
+        assert_eq!(MinecraftVersion::from_str("1.21"), Ok(MinecraftVersion::Java1_21));
+        assert_eq!(MinecraftVersion::from_str("1.21.1"), Ok(MinecraftVersion::Java1_21_3));
+        assert_eq!(MinecraftVersion::from_str("1.21.3"), Ok(MinecraftVersion::Java1_21_3));
+        assert_eq!(MinecraftVersion::from_str("1.21.4"), Ok(MinecraftVersion::Java1_21));
+        assert_eq!(MinecraftVersion::from_str("1.21.11"), Ok(MinecraftVersion::Java1_21));
+    }
+
+    #[test]
+    fn add_item_maps_once() {
+        let item_map = |x_center| ItemMap { x_center, z_center: 0, scale: 0, colors: vec![0; 128 * 128] };
+        let mut seed_info = SeedInfo::default();
+        assert_eq!(seed_info.add_item_maps(vec![item_map(0), item_map(128)]), 2);
+        // Reading the same world again only adds the new maps
+        assert_eq!(seed_info.add_item_maps(vec![item_map(128), item_map(256)]), 1);
+        assert_eq!(seed_info.item_maps, vec![item_map(0), item_map(128), item_map(256)]);
+    }
 }
@@ -1,9 +1,15 @@
 use crate::biome_layers::{Area, Map};
 use crate::chunk::Chunk;
+use crate::java_rng::lcg_const;
+use crate::java_rng::mask;
 use crate::java_rng::JavaRng;
+use crate::mt19937;
+use crate::seed_info::Edition;
+use crate::seed_info::SlimeEvidence;
 use log::info;
 use std::cmp::min;
 use std::num::Wrapping;
+use std::ops::Range;
 
 pub struct SlimeChunks {
     slime_data: Vec<u64>,
@@ -54,23 +60,71 @@ impl SlimeChunks {
         let mut v = vec![];
 
         for &l in &self.low_18_candidates {
-            for seed in lo..hi {
-                let seed = ((seed as u64) << 18) | (l as u64);
+            self.find_seed_high_bits(l as u64, 18, lo as u64..hi as u64, &mut v);
+        }
 
-                if self.try_seed_skip_18(seed) {
-                    info!("Found seed: {:012X}", seed);
-                    v.push(seed);
-                }
+        v
+    }
+
+    /// Try all the seeds `(high << num_bits) | low_bits` for `high` in `high_range`, `LANES`
+    /// seeds at a time, and push the ones that meet the requirements to `v`.
+    fn find_seed_high_bits(
+        &self,
+        low_bits: u64,
+        num_bits: u32,
+        high_range: Range<u64>,
+        v: &mut Vec<u64>,
+    ) {
+        let mut high = high_range.start;
+        while high + LANES as u64 <= high_range.end {
+            let seeds: [u64; LANES] =
+                std::array::from_fn(|i| ((high + i as u64) << num_bits) | low_bits);
+            let mut found = self.try_seeds_skip_18(&seeds);
+            while found != 0 {
+                let seed = seeds[found.trailing_zeros() as usize];
+                found &= found - 1;
+                info!("Found seed: {:012X}", seed);
+                v.push(seed);
             }
+            high += LANES as u64;
         }
 
+        for high in high..high_range.end {
+            let seed = (high << num_bits) | low_bits;
+            if self.try_seed_skip_18(seed) {
+                info!("Found seed: {:012X}", seed);
+                v.push(seed);
+            }
+        }
+    }
+
+    /// Find all the 48-bit seeds whose lower `num_bits` bits are equal to
+    /// `low_bits`, for example the 26-bit candidates found using rivers.
+    /// `num_bits` must be at least 18.
+    pub fn find_seed_with_low_bits(&self, low_bits: u64, num_bits: u32) -> Vec<u64> {
+        assert!((18..=48).contains(&num_bits));
+        let low_bits = low_bits & ((1 << num_bits) - 1);
+        const MASK18: u64 = (1 << 18) - 1;
+        let low18 = (low_bits & MASK18) as u32;
+        if self.low_18_candidates.binary_search(&low18).is_err() {
+            return vec![];
+        }
+
+        let mut v = vec![];
+        self.find_seed_high_bits(low_bits, num_bits, 0..(1u64 << (48 - num_bits)), &mut v);
+
         v
     }
 
     // true if the seeds meets the requirements, skip the low18 check
     pub fn try_seed_skip_18(&self, seed: u64) -> bool {
-        let mut errors = 0;
-        for &x in &self.slime_data {
+        self.try_seed_skip_18_from(seed, 0, 0)
+    }
+
+    /// Same as `try_seed_skip_18`, but the first `checked` slime chunks were already checked and
+    /// had `errors` errors.
+    fn try_seed_skip_18_from(&self, seed: u64, checked: usize, mut errors: usize) -> bool {
+        for &x in &self.slime_data[checked..] {
             let mut r = rng_with_slime_data(seed, x);
             let mod_ten = r.next_int_n(10);
             if mod_ten != 0 {
@@ -96,6 +150,91 @@ impl SlimeChunks {
         true
     }
 
+    /// Same as `try_seed_skip_18`, but checking `LANES` seeds at once. Returns a bitmask where
+    /// bit `i` is set if `seeds[i]` meets the requirements.
+    ///
+    /// The slime chunks are checked for all the seeds at the same time, in a loop that the
+    /// compiler can vectorize. Each slime chunk discards 90% of the seeds, so after a few chunks
+    /// only a few seeds are left, and these are checked again one at a time.
+    pub fn try_seeds_skip_18(&self, seeds: &[u64; LANES]) -> u32 {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx512dq") {
+                // Safety: the CPU supports AVX-512
+                return unsafe { self.try_seeds_skip_18_avx512(seeds) };
+            }
+            if is_x86_feature_detected!("avx2") {
+                // Safety: the CPU supports AVX2
+                return unsafe { self.try_seeds_skip_18_avx2(seeds) };
+            }
+        }
+
+        self.try_seeds_skip_18_lanes(seeds)
+    }
+
+    /// AVX-512 has a 64-bit multiplication, with AVX2 it needs 3 32-bit multiplications
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx512f,avx512dq")]
+    unsafe fn try_seeds_skip_18_avx512(&self, seeds: &[u64; LANES]) -> u32 {
+        self.try_seeds_skip_18_lanes(seeds)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn try_seeds_skip_18_avx2(&self, seeds: &[u64; LANES]) -> u32 {
+        self.try_seeds_skip_18_lanes(seeds)
+    }
+
+    #[inline(always)]
+    fn try_seeds_skip_18_lanes(&self, seeds: &[u64; LANES]) -> u32 {
+        let max_errors = u32::try_from(self.max_errors).unwrap_or(u32::MAX);
+        let mut errors = [0u32; LANES];
+        // Seeds where nextInt(10) needs more than one call to next(31)
+        let mut rejected = [false; LANES];
+        let mut checked = 0;
+        for &x in &self.slime_data {
+            for i in 0..LANES {
+                // Same as rng_with_slime_data(seeds[i], x).next(31), but easier to vectorize
+                let state = (seeds[i].wrapping_add(x) ^ slime_const::E ^ lcg_const::A)
+                    .wrapping_mul(lcg_const::A)
+                    .wrapping_add(lcg_const::C);
+                let bits = ((state & mask(48)) >> 17) as u32;
+                // When bits >= NEXT_INT_10_LIMIT, nextInt(10) calls next(31) again. This only
+                // happens for 8 out of 2^31 outputs, so these seeds are checked again later using
+                // the scalar code. Until then, the chunk is not counted as an error.
+                rejected[i] |= bits >= NEXT_INT_10_LIMIT;
+                errors[i] += (bits < NEXT_INT_10_LIMIT && !bits.is_multiple_of(10)) as u32;
+            }
+            checked += 1;
+            let num_alive = errors.iter().filter(|&&e| e <= max_errors).count();
+            if num_alive <= LANES / 8 {
+                break;
+            }
+        }
+
+        let mut alive = 0u32;
+        for (i, &e) in errors.iter().enumerate() {
+            alive |= ((e <= max_errors) as u32) << i;
+        }
+
+        // Check the remaining seeds one at a time, including the non slime chunks
+        let mut found = 0;
+        while alive != 0 {
+            let i = alive.trailing_zeros() as usize;
+            alive &= alive - 1;
+            let ok = if rejected[i] {
+                self.try_seed_skip_18(seeds[i])
+            } else {
+                self.try_seed_skip_18_from(seeds[i], checked, errors[i] as usize)
+            };
+            if ok {
+                found |= 1 << i;
+            }
+        }
+
+        found
+    }
+
     // true if the seeds meets the requirements
     pub fn try_seed(&self, seed: u64) -> bool {
         // Check the low 18 bits first
@@ -108,6 +247,11 @@ impl SlimeChunks {
         self.try_seed_skip_18(seed)
     }
 
+    /// Sorted list of all the possible values of the lower 18 bits
+    pub fn low_18_candidates(&self) -> &[u32] {
+        &self.low_18_candidates
+    }
+
     pub fn num_low_18_candidates(&self) -> usize {
         self.low_18_candidates.len()
     }
@@ -124,6 +268,443 @@ impl SlimeChunks {
     }
 }
 
+/// Rotation and mirroring of a slime chunk pattern, used when the orientation of the pattern is
+/// not known, for example when it was copied from a screenshot.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkTransform {
+    /// Negate the x coordinate, before rotating
+    pub mirror: bool,
+    /// Number of 90 degree clockwise rotations, as seen from above with north up
+    pub rotation: u8,
+}
+
+impl ChunkTransform {
+    pub const IDENTITY: ChunkTransform = ChunkTransform {
+        mirror: false,
+        rotation: 0,
+    };
+
+    /// All the transforms to try: only the identity, the 4 rotations, the identity and its
+    /// mirror image, or all the 8 combinations.
+    pub fn all(rotations: bool, mirror: bool) -> Vec<ChunkTransform> {
+        let num_rotations = if rotations { 4 } else { 1 };
+        let mirrors: &[bool] = if mirror { &[false, true] } else { &[false] };
+        let mut v = vec![];
+        for &mirror in mirrors {
+            for rotation in 0..num_rotations {
+                v.push(ChunkTransform { mirror, rotation });
+            }
+        }
+
+        v
+    }
+
+    pub fn apply(&self, c: &Chunk) -> Chunk {
+        let (mut x, mut z) = (c.x, c.z);
+        if self.mirror {
+            x = -x;
+        }
+        for _ in 0..self.rotation % 4 {
+            // x is east and z is south, so (1, 0) becomes (0, 1)
+            (x, z) = (-z, x);
+        }
+
+        Chunk::new(x, z)
+    }
+}
+
+/// A seed found by `SlimeChunksPattern`, together with the position of the pattern: chunk `c`
+/// of the pattern is `transform.apply(c) + offset` in the world.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PatternMatch {
+    pub seed: u64,
+    pub offset: Chunk,
+    pub transform: ChunkTransform,
+}
+
+impl PatternMatch {
+    /// World coordinates of chunk `c` of the pattern
+    pub fn absolute_chunk(&self, c: &Chunk) -> Chunk {
+        let t = self.transform.apply(c);
+        Chunk::new(t.x + self.offset.x, t.z + self.offset.z)
+    }
+}
+
+/// Slime chunks and non slime chunks with relative coordinates. The position of the pattern in
+/// the world is unknown, but it is somewhere inside `window`, and the orientation is one of
+/// `transforms`.
+///
+/// Each possible position is searched independently, so the search is `window.w * window.h *
+/// transforms.len()` times slower than `SlimeChunks`. Keep the window small.
+pub struct SlimeChunksPattern {
+    slime_chunks: Vec<Chunk>,
+    max_errors: usize,
+    no_slime_chunks: Vec<Chunk>,
+    max_no_errors: usize,
+    /// The `SlimeChunks` of each possible position, with its 18-bit candidates
+    placed: Vec<(Chunk, ChunkTransform, SlimeChunks)>,
+}
+
+impl SlimeChunksPattern {
+    /// `window` is the area of possible offsets, in chunks. This finds the 18-bit candidates of
+    /// every position, so it takes `num_placements` times longer than `SlimeChunks::new`.
+    pub fn new(
+        slime_chunks: &[Chunk],
+        max_errors: usize,
+        no_slime_chunks: &[Chunk],
+        max_no_errors: usize,
+        window: Area,
+        transforms: Vec<ChunkTransform>,
+    ) -> SlimeChunksPattern {
+        let mut placed = vec![];
+        for t in transforms {
+            for j in 0..window.h as i64 {
+                for i in 0..window.w as i64 {
+                    let offset = Chunk::new((window.x + i) as i32, (window.z + j) as i32);
+                    let m = PatternMatch {
+                        seed: 0,
+                        offset,
+                        transform: t,
+                    };
+                    let place = |v: &[Chunk]| -> Vec<Chunk> {
+                        v.iter().map(|c| m.absolute_chunk(c)).collect()
+                    };
+                    let sc = SlimeChunks::new(
+                        &place(slime_chunks),
+                        max_errors,
+                        &place(no_slime_chunks),
+                        max_no_errors,
+                    );
+                    placed.push((offset, t, sc));
+                }
+            }
+        }
+
+        SlimeChunksPattern {
+            slime_chunks: slime_chunks.to_vec(),
+            max_errors,
+            no_slime_chunks: no_slime_chunks.to_vec(),
+            max_no_errors,
+            placed,
+        }
+    }
+
+    /// Number of possible positions of the pattern
+    pub fn num_placements(&self) -> usize {
+        self.placed.len()
+    }
+
+    /// All the possible positions of the pattern, as `(offset, transform)`
+    pub fn placements(&self) -> impl Iterator<Item = (Chunk, ChunkTransform)> + '_ {
+        self.placed.iter().map(|&(offset, t, _)| (offset, t))
+    }
+
+    /// Total number of 18-bit candidates, adding all the positions. The time needed to search
+    /// the remaining bits is proportional to this number.
+    pub fn num_low_18_candidates(&self) -> usize {
+        self.placed
+            .iter()
+            .map(|(_, _, sc)| sc.num_low_18_candidates())
+            .sum()
+    }
+
+    /// The `SlimeChunks` used to search for the pattern at this position
+    pub fn slime_chunks_at(
+        &self,
+        offset: Chunk,
+        transform: ChunkTransform,
+    ) -> Option<&SlimeChunks> {
+        self.placed
+            .iter()
+            .find(|&&(o, t, _)| o == offset && t == transform)
+            .map(|(_, _, sc)| sc)
+    }
+
+    pub fn find_seed(&self) -> Vec<PatternMatch> {
+        self.find_seed_range(0, 1 << (48 - 18))
+    }
+
+    /// Same as `SlimeChunks::find_seed_range`, for all the possible positions
+    pub fn find_seed_range(&self, lo: u32, count: u32) -> Vec<PatternMatch> {
+        self.find_seed_with(|sc| sc.find_seed_range(lo, count))
+    }
+
+    /// Same as `SlimeChunks::find_seed_with_low_bits`, for all the possible positions
+    pub fn find_seed_with_low_bits(&self, low_bits: u64, num_bits: u32) -> Vec<PatternMatch> {
+        self.find_seed_with(|sc| sc.find_seed_with_low_bits(low_bits, num_bits))
+    }
+
+    fn find_seed_with<F>(&self, f: F) -> Vec<PatternMatch>
+    where
+        F: Fn(&SlimeChunks) -> Vec<u64>,
+    {
+        let mut v = vec![];
+        for &(offset, transform, ref sc) in &self.placed {
+            for seed in f(sc) {
+                info!(
+                    "Found seed: {:012X} at offset {:?} {:?}",
+                    seed, offset, transform
+                );
+                v.push(PatternMatch {
+                    seed,
+                    offset,
+                    transform,
+                });
+            }
+        }
+
+        v
+    }
+
+    /// All the positions where this seed has the pattern
+    pub fn try_seed(&self, seed: u64) -> Vec<PatternMatch> {
+        self.placements()
+            .map(|(offset, transform)| PatternMatch {
+                seed,
+                offset,
+                transform,
+            })
+            .filter(|m| {
+                let errors = self
+                    .slime_chunks
+                    .iter()
+                    .filter(|c| !is_slime_chunk(seed, &m.absolute_chunk(c)))
+                    .count();
+                let no_errors = self
+                    .no_slime_chunks
+                    .iter()
+                    .filter(|c| is_slime_chunk(seed, &m.absolute_chunk(c)))
+                    .count();
+                errors <= self.max_errors && no_errors <= self.max_no_errors
+            })
+            .collect()
+    }
+}
+
+/// Expected number of slimes that spawn per minute in a slime chunk below y=40, when it is dark
+/// and there is room for them to spawn. This is a rough estimate, the real rate depends on the
+/// mob cap and on the number of valid spawning spaces.
+pub const SLIMES_PER_MINUTE: f64 = 1.0;
+
+/// Slimes seen in normal chunks, relative to slime chunks: slimes that walked in from a nearby
+/// chunk, slimes from a swamp, or a chunk border in the wrong place.
+pub const MISPLACED_SLIMES: f64 = 0.02;
+
+/// Default value of `Options::slime_evidence_margin`: the seed is at most 1000 times less likely
+/// than a seed that perfectly explains all the evidence.
+pub const DEFAULT_SLIME_EVIDENCE_MARGIN: f64 = 3.0 * std::f64::consts::LN_10;
+
+/// Log-likelihood of the observation, if the chunk is a slime chunk or not. The slimes are
+/// modeled as a Poisson process, and the constant terms that do not depend on the seed are
+/// omitted.
+pub fn slime_evidence_log_likelihood(e: &SlimeEvidence, is_slime_chunk: bool) -> f64 {
+    let minutes = if e.minutes > 0.0 {
+        e.minutes
+    } else {
+        // Unknown time: assume the minimum time needed to see that many slimes
+        f64::from(e.slimes) / SLIMES_PER_MINUTE
+    };
+    let rate = if is_slime_chunk {
+        SLIMES_PER_MINUTE
+    } else {
+        SLIMES_PER_MINUTE * MISPLACED_SLIMES
+    };
+    let mean = rate * minutes;
+    if e.slimes == 0 {
+        -mean
+    } else {
+        f64::from(e.slimes) * mean.ln() - mean
+    }
+}
+
+/// Ranks seeds using weighted slime chunk clues. Instead of counting errors, each observation
+/// adds a loss: the difference between its log-likelihood and the log-likelihood of the most
+/// likely outcome. Seeds with a total loss greater than `max_loss` are rejected.
+pub struct SlimeEvidenceScore {
+    /// `(slime_data, loss if it is a slime chunk, loss if it is not)`, sorted by the maximum loss
+    /// so the seeds can be rejected sooner
+    chunks: Vec<(u64, f64, f64)>,
+    max_loss: f64,
+    low_18_candidates: Vec<u32>,
+}
+
+impl SlimeEvidenceScore {
+    pub fn new(evidence: &[SlimeEvidence], max_loss: f64) -> SlimeEvidenceScore {
+        let mut chunks: Vec<(u64, f64, f64)> = evidence
+            .iter()
+            .map(|e| {
+                let yes = slime_evidence_log_likelihood(e, true);
+                let no = slime_evidence_log_likelihood(e, false);
+                let best = yes.max(no);
+                (calculate_slime_data(&e.chunk()), best - yes, best - no)
+            })
+            .collect();
+        chunks.sort_by(|a, b| b.1.max(b.2).total_cmp(&a.1.max(a.2)));
+
+        let mut sc = SlimeEvidenceScore {
+            chunks,
+            max_loss,
+            low_18_candidates: vec![],
+        };
+        sc.low_18_candidates = (0..(1u32 << 18))
+            .filter(|&low18| sc.low_18_min_loss(low18) <= max_loss)
+            .collect();
+
+        sc
+    }
+
+    /// Minimum loss of the seeds with these low 18 bits. When `next_int_n(10)` is odd, the chunk
+    /// cannot be a slime chunk independently of the other bits.
+    fn low_18_min_loss(&self, low18: u32) -> f64 {
+        let mut loss = 0.0;
+        for &(x, _, loss_no) in &self.chunks {
+            let mut r = rng_with_slime_data(u64::from(low18), x);
+            if r.next_int_n(10) % 2 != 0 {
+                loss += loss_no;
+                if loss > self.max_loss {
+                    break;
+                }
+            }
+        }
+
+        loss
+    }
+
+    /// Sorted list of all the possible values of the lower 18 bits
+    pub fn low_18_candidates(&self) -> &[u32] {
+        &self.low_18_candidates
+    }
+
+    pub fn num_low_18_candidates(&self) -> usize {
+        self.low_18_candidates.len()
+    }
+
+    /// Log-likelihood of the evidence for this seed, relative to a perfect match. This is always
+    /// negative or zero, and higher is better.
+    pub fn score(&self, seed: u64) -> f64 {
+        -self
+            .chunks
+            .iter()
+            .map(|&(x, loss_yes, loss_no)| {
+                if is_slime_data(seed, x) {
+                    loss_yes
+                } else {
+                    loss_no
+                }
+            })
+            .sum::<f64>()
+    }
+
+    /// Sort the seeds by score, best first
+    pub fn rank(&self, seeds: &[u64]) -> Vec<(u64, f64)> {
+        let mut v: Vec<(u64, f64)> = seeds.iter().map(|&s| (s, self.score(s))).collect();
+        v.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        v
+    }
+
+    // true if the seeds meets the requirements
+    pub fn try_seed(&self, seed: u64) -> bool {
+        const MASK18: u64 = (1 << 18) - 1;
+        let low18 = (seed & MASK18) as u32;
+        if self.low_18_candidates.binary_search(&low18).is_err() {
+            return false;
+        }
+
+        self.try_seed_skip_18(seed)
+    }
+
+    pub fn try_seed_skip_18(&self, seed: u64) -> bool {
+        let mut loss = 0.0;
+        for &(x, loss_yes, loss_no) in &self.chunks {
+            loss += if is_slime_data(seed, x) {
+                loss_yes
+            } else {
+                loss_no
+            };
+            if loss > self.max_loss {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Same as `SlimeChunks::find_seed_range`
+    pub fn find_seed_range(&self, lo: u32, count: u32) -> Vec<u64> {
+        let hi = min(lo + count, 1 << 30);
+        let mut v = vec![];
+
+        for &l in &self.low_18_candidates {
+            for high in lo..hi {
+                let seed = (u64::from(high) << 18) | u64::from(l);
+                if self.try_seed_skip_18(seed) {
+                    info!("Found seed: {:012X}", seed);
+                    v.push(seed);
+                }
+            }
+        }
+
+        v
+    }
+
+    /// Same as `SlimeChunks::find_seed_with_low_bits`
+    pub fn find_seed_with_low_bits(&self, low_bits: u64, num_bits: u32) -> Vec<u64> {
+        assert!((18..=48).contains(&num_bits));
+        let low_bits = low_bits & ((1 << num_bits) - 1);
+        const MASK18: u64 = (1 << 18) - 1;
+        let low18 = (low_bits & MASK18) as u32;
+        if self.low_18_candidates.binary_search(&low18).is_err() {
+            return vec![];
+        }
+
+        (0..(1u64 << (48 - num_bits)))
+            .map(|high| (high << num_bits) | low_bits)
+            .filter(|&seed| self.try_seed_skip_18(seed))
+            .collect()
+    }
+
+    /// Probability that a random 48-bit seed has a loss of at most `max_loss`, assuming that 10%
+    /// of the chunks are slime chunks. The losses are rounded down to multiples of `max_loss /
+    /// 1000`, so this slightly overestimates the probability.
+    pub fn false_positive_rate(&self) -> f64 {
+        const BINS: usize = 1000;
+        let bin_size = self.max_loss / BINS as f64;
+        let to_bin = |loss: f64| {
+            if loss <= 0.0 {
+                0
+            } else if bin_size > 0.0 {
+                (loss / bin_size) as usize
+            } else {
+                BINS + 1
+            }
+        };
+        // p[i]: probability that the total loss is in bin i
+        let mut p = vec![0.0; BINS + 1];
+        p[0] = 1.0;
+        for &(_, loss_yes, loss_no) in &self.chunks {
+            let mut next = vec![0.0; BINS + 1];
+            for (i, &pi) in p.iter().enumerate() {
+                for (loss, prob) in [(loss_yes, 0.1), (loss_no, 0.9)] {
+                    let j = i + to_bin(loss);
+                    if j <= BINS {
+                        next[j] += pi * prob;
+                    }
+                }
+            }
+            p = next;
+        }
+
+        p.iter().sum()
+    }
+}
+
+/// Number of seeds checked at once by `SlimeChunks::try_seeds_skip_18`
+pub const LANES: usize = 16;
+
+/// `nextInt(10)` only accepts outputs of `next(31)` below the last multiple of 10 lower than 2^31
+const NEXT_INT_10_LIMIT: u32 = (1u32 << 31) / 10 * 10;
+
 mod slime_const {
     use std::num::Wrapping;
     pub const A: Wrapping<i32> = Wrapping(0x4c1906);
@@ -251,6 +832,38 @@ pub fn gen_map_from_seed(area: Area, seed: u64) -> Map {
     m
 }
 
+/// Bedrock Edition slime chunks do not depend on the world seed: the first
+/// output of a Mersenne Twister seeded with the chunk coordinates decides if
+/// it is a slime chunk.
+pub fn is_slime_chunk_bedrock(c: &Chunk) -> bool {
+    let seed = (c.x as u32).wrapping_mul(0x1f1f1f1f) ^ (c.z as u32);
+    mt19937::first_u32(seed) % 10 == 0
+}
+
+/// Generate a Map where slime chunks are set to 1 and non slime chunks are set to 0.
+/// Slime chunks are the same for all the worlds in Bedrock Edition.
+pub fn gen_map_bedrock(area: Area) -> Map {
+    let mut m = Map::new(area);
+    for i in 0..area.w as usize {
+        for j in 0..area.h as usize {
+            m.a[(i, j)] = is_slime_chunk_bedrock(&Chunk::new(
+                area.x as i32 + i as i32,
+                area.z as i32 + j as i32,
+            )) as i32;
+        }
+    }
+
+    m
+}
+
+/// Generate a Map of slime chunks for any edition
+pub fn gen_map_from_seed_edition(area: Area, seed: u64, edition: Edition) -> Map {
+    match edition {
+        Edition::Java => gen_map_from_seed(area, seed),
+        Edition::Bedrock => gen_map_bedrock(area),
+    }
+}
+
 /// Generate a list of slime chunks and not slime chunks using the given seed
 pub fn generate_slime_chunks_and_not(
     seed: i64,
@@ -379,4 +992,192 @@ mod tests {
             //println!("{:012X}", r.get_raw_seed());
         }
     }
+
+    #[test]
+    fn try_seeds_skip_18_same_as_scalar() {
+        use crate::java_rng::lcg_const_extra::INV_A;
+        let seed: u64 = 0x1234_5678;
+        let (c, nc) = generate_slime_chunks_and_not(seed as i64, 40, 40);
+        let sc = SlimeChunks::new(&c, 3, &nc, 3);
+        let mut found = vec![];
+        // Random seeds and the seeds around the real seed
+        for start in [0, seed - 1000] {
+            for k in 0..4096 / LANES as u64 {
+                let seeds: [u64; LANES] =
+                    std::array::from_fn(|i| start + k * LANES as u64 + i as u64);
+                let mask = sc.try_seeds_skip_18(&seeds);
+                for (i, s) in seeds.iter().enumerate() {
+                    assert_eq!(mask & (1 << i) != 0, sc.try_seed_skip_18(*s), "{}", s);
+                    if mask & (1 << i) != 0 {
+                        found.push(*s);
+                    }
+                }
+            }
+        }
+        assert!(found.contains(&seed));
+
+        // Seeds where the first call to next(31) for the slime chunk at (3, -7) returns a value
+        // that nextInt(10) rejects, so the result depends on the second call
+        let chunk = Chunk::new(3, -7);
+        let x = calculate_slime_data(&chunk);
+        let seeds: [u64; LANES] = std::array::from_fn(|i| {
+            let bits = NEXT_INT_10_LIMIT as u64 + (i as u64 % 8);
+            let state1 = (bits << 17) | (i as u64 * 0x1234);
+            let state0 = (state1.wrapping_sub(lcg_const::C)).wrapping_mul(INV_A) & mask(48);
+            ((state0 ^ lcg_const::A) ^ slime_const::E).wrapping_sub(x) & mask(48)
+        });
+        for &s in &seeds {
+            let mut r = rng_with_slime_data(s, x);
+            assert!(r.next(31) as u32 >= NEXT_INT_10_LIMIT, "{}", s);
+        }
+
+        let sc = SlimeChunks::new(&[chunk], 0, &[], 0);
+        let found = sc.try_seeds_skip_18(&seeds);
+        let mut num_rejected = 0;
+        for (i, s) in seeds.iter().enumerate() {
+            let scalar = sc.try_seed_skip_18(*s);
+            assert_eq!(found & (1 << i) != 0, scalar, "{}", s);
+            num_rejected += !scalar as usize;
+        }
+        // Counting these chunks as slime chunks would accept all of these seeds
+        assert!(num_rejected > 0);
+    }
+
+    #[test]
+    fn slime_chunks_pattern_unknown_offset() {
+        let seed: u64 = 0x1234_5678_9ABC;
+        let offset = Chunk::new(3, -2);
+        let transform = ChunkTransform {
+            mirror: true,
+            rotation: 1,
+        };
+        let expected = PatternMatch {
+            seed,
+            offset,
+            transform,
+        };
+        let mut c = vec![];
+        let mut nc = vec![];
+        for x in 0..10 {
+            for z in 0..10 {
+                let rel = Chunk::new(x, z);
+                if is_slime_chunk(seed, &expected.absolute_chunk(&rel)) {
+                    c.push(rel);
+                } else {
+                    nc.push(rel);
+                }
+            }
+        }
+        let window = Area {
+            x: 0,
+            z: -4,
+            w: 5,
+            h: 5,
+        };
+        let sp = SlimeChunksPattern::new(&c, 0, &nc, 0, window, ChunkTransform::all(true, true));
+        assert_eq!(sp.num_placements(), 5 * 5 * 8);
+        assert_eq!(sp.try_seed(seed), vec![expected]);
+        let found = sp.find_seed_with_low_bits(seed & ((1 << 40) - 1), 40);
+        assert_eq!(found, vec![expected]);
+    }
+
+    #[test]
+    fn chunk_transform() {
+        let c = Chunk::new(1, 2);
+        let all = ChunkTransform::all(true, true);
+        assert_eq!(all.len(), 8);
+        assert_eq!(all[0], ChunkTransform::IDENTITY);
+        let transformed: Vec<Chunk> = all.iter().map(|t| t.apply(&c)).collect();
+        for (i, a) in transformed.iter().enumerate() {
+            for b in &transformed[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+        assert_eq!(
+            ChunkTransform::all(false, false),
+            vec![ChunkTransform::IDENTITY]
+        );
+        assert_eq!(ChunkTransform::all(true, false).len(), 4);
+        assert_eq!(ChunkTransform::all(false, true).len(), 2);
+    }
+
+    #[test]
+    fn slime_evidence_score() {
+        let seed: u64 = 0x1234_5678_9ABC;
+        let mut evidence = vec![];
+        // Far from 0, 0 to make all the 48 bits of the seed affect the slime chunks
+        for x in 1000..1008 {
+            for z in -2000..-1992 {
+                let is_slime = is_slime_chunk(seed, &Chunk::new(x, z));
+                evidence.push(SlimeEvidence {
+                    x,
+                    z,
+                    slimes: if is_slime { 5 } else { 0 },
+                    minutes: 10.0,
+                });
+            }
+        }
+        // One wrong observation: a slime chunk where no slimes spawned in 2 minutes
+        let wrong = evidence.iter_mut().find(|e| e.slimes > 0).unwrap();
+        wrong.slimes = 0;
+        wrong.minutes = 2.0;
+
+        let sc = SlimeEvidenceScore::new(&evidence, DEFAULT_SLIME_EVIDENCE_MARGIN);
+        assert!(sc.try_seed(seed));
+        assert!(sc.score(seed) < 0.0);
+        assert!(sc.score(seed) >= -DEFAULT_SLIME_EVIDENCE_MARGIN);
+        assert!(sc.false_positive_rate() < 1e-6);
+        let found = sc.find_seed_with_low_bits(seed & ((1 << 40) - 1), 40);
+        assert!(found.contains(&seed));
+        assert!(found.len() < 4, "{:?}", found);
+
+        let ranked = sc.rank(&[seed + 1, seed, seed + 2]);
+        assert_eq!(ranked[0].0, seed);
+        assert!(ranked[0].1 > ranked[1].1);
+    }
+
+    #[test]
+    fn slime_evidence_log_likelihood_weights() {
+        let saw_3 = SlimeEvidence {
+            x: 0,
+            z: 0,
+            slimes: 3,
+            minutes: 0.0,
+        };
+        let saw_none = SlimeEvidence {
+            x: 0,
+            z: 0,
+            slimes: 0,
+            minutes: 10.0,
+        };
+        let ratio =
+            |e| slime_evidence_log_likelihood(e, true) - slime_evidence_log_likelihood(e, false);
+        // Seeing slimes is evidence for a slime chunk, seeing none is evidence against it
+        assert!(ratio(&saw_3) > 0.0);
+        assert!(ratio(&saw_none) < 0.0);
+        // Waiting longer without seeing slimes is stronger evidence
+        let saw_none_1 = SlimeEvidence {
+            minutes: 1.0,
+            ..saw_none.clone()
+        };
+        assert!(ratio(&saw_none) < ratio(&saw_none_1));
+    }
+
+    #[test]
+    fn bedrock_slime_chunks() {
+        // The first output of Mt19937::new(0) is 2357136044
+        assert!(!is_slime_chunk_bedrock(&Chunk::new(0, 0)));
+        // Bedrock slime chunks are the same in every world and about 10% of
+        // the chunks are slime chunks
+        let area = Area {
+            x: -50,
+            z: -50,
+            w: 100,
+            h: 100,
+        };
+        let m = gen_map_bedrock(area);
+        assert_eq!(m, gen_map_from_seed_edition(area, 1234, Edition::Bedrock));
+        let count = m.a.iter().filter(|x| **x == 1).count();
+        assert!((800..1200).contains(&count), "{}", count);
+    }
 }
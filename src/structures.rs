@@ -1,6 +1,8 @@
 use crate::chunk::Chunk;
 use crate::java_rng::mask;
 use crate::java_rng::JavaRng;
+use crate::seed_info::MinecraftVersion;
+use crate::seed_info::SeedStructures;
 
 // ceil(2^24 / 100)
 const ONE_PERCENT_OF_2_24: i32 = 0x28f5d;
@@ -138,6 +140,491 @@ impl TreasureChunks {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StructureKind {
+    DesertTemple,
+    JungleTemple,
+    WitchHut,
+    Igloo,
+    Village,
+    OceanMonument,
+    WoodlandMansion,
+    EndCity,
+    OceanRuin,
+    Shipwreck,
+    PillagerOutpost,
+    NetherFort,
+}
+
+impl StructureKind {
+    pub fn all() -> &'static [StructureKind] {
+        use StructureKind::*;
+        &[
+            DesertTemple,
+            JungleTemple,
+            WitchHut,
+            Igloo,
+            Village,
+            OceanMonument,
+            WoodlandMansion,
+            EndCity,
+            OceanRuin,
+            Shipwreck,
+            PillagerOutpost,
+            NetherFort,
+        ]
+    }
+
+    /// List of chunks of this kind of structure from the SeedStructures
+    pub fn chunks<'a>(&self, s: &'a SeedStructures) -> &'a [Chunk] {
+        use StructureKind::*;
+        match self {
+            DesertTemple => &s.desert_temples,
+            JungleTemple => &s.jungle_temples,
+            WitchHut => &s.witch_huts,
+            Igloo => &s.igloos,
+            Village => &s.villages,
+            OceanMonument => &s.ocean_monuments,
+            WoodlandMansion => &s.woodland_mansions,
+            EndCity => &s.end_cities,
+            OceanRuin => &s.ocean_ruins,
+            Shipwreck => &s.shipwrecks,
+            PillagerOutpost => &s.pillager_outposts,
+            NetherFort => &s.nether_forts,
+        }
+    }
+
+    /// Same as `chunks`, but mutable
+    pub fn chunks_mut<'a>(&self, s: &'a mut SeedStructures) -> &'a mut Vec<Chunk> {
+        use StructureKind::*;
+        match self {
+            DesertTemple => &mut s.desert_temples,
+            JungleTemple => &mut s.jungle_temples,
+            WitchHut => &mut s.witch_huts,
+            Igloo => &mut s.igloos,
+            Village => &mut s.villages,
+            OceanMonument => &mut s.ocean_monuments,
+            WoodlandMansion => &mut s.woodland_mansions,
+            EndCity => &mut s.end_cities,
+            OceanRuin => &mut s.ocean_ruins,
+            Shipwreck => &mut s.shipwrecks,
+            PillagerOutpost => &mut s.pillager_outposts,
+            NetherFort => &mut s.nether_forts,
+        }
+    }
+
+    /// Name used in the command line, for example "witch-hut"
+    pub fn name(&self) -> &'static str {
+        use StructureKind::*;
+        match self {
+            DesertTemple => "desert-temple",
+            JungleTemple => "jungle-temple",
+            WitchHut => "witch-hut",
+            Igloo => "igloo",
+            Village => "village",
+            OceanMonument => "ocean-monument",
+            WoodlandMansion => "woodland-mansion",
+            EndCity => "end-city",
+            OceanRuin => "ocean-ruin",
+            Shipwreck => "shipwreck",
+            PillagerOutpost => "pillager-outpost",
+            NetherFort => "nether-fort",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<StructureKind> {
+        Self::all().iter().copied().find(|k| k.name() == name)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Placement {
+    /// The world is divided into square regions of `spacing` chunks, and each
+    /// region has one attempt to generate the structure at a random offset
+    /// lower than `spacing - separation`. With triangular distribution, the
+    /// offset is the average of two random numbers.
+    Region {
+        spacing: i32,
+        separation: i32,
+        salt: i32,
+        triangular: bool,
+    },
+    /// Nether fortresses before 1.16: regions of 16x16 chunks, one attempt
+    /// with probability 1/3 at a random offset from 4 to 11.
+    NetherFortLegacy,
+}
+
+/// Where a region-based structure can generate in one Minecraft version.
+///
+/// This only checks the position of the structure attempt. The structure may
+/// still fail to generate because of the biomes, so negative chunks should
+/// only include chunks where the structure would be able to generate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StructurePlacement {
+    pub kind: StructureKind,
+    pub placement: Placement,
+    version: MinecraftVersion,
+}
+
+impl StructurePlacement {
+    /// Returns None if the structure does not exist in this version, or if the
+    /// placement is not region-based.
+    pub fn new(kind: StructureKind, version: MinecraftVersion) -> Option<Self> {
+        use MinecraftVersion::*;
+        use StructureKind::*;
+
+        let region = |spacing, separation, salt| Placement::Region {
+            spacing,
+            separation,
+            salt,
+            triangular: false,
+        };
+        let triangular = |spacing, separation, salt| Placement::Region {
+            spacing,
+            separation,
+            salt,
+            triangular: true,
+        };
+        // Before 1.13 all the temples use the same placement, and the type of
+        // temple depends on the biome
+        let temple = |since: MinecraftVersion, salt_1_13| {
+            if version < since {
+                None
+            } else if version < Java1_13 {
+                Some(region(32, 8, 14357617))
+            } else {
+                Some(region(32, 8, salt_1_13))
+            }
+        };
+
+        let placement = match kind {
+            DesertTemple => temple(Java1_3, 14357617)?,
+            JungleTemple => temple(Java1_3, 14357619)?,
+            WitchHut => temple(Java1_3, 14357620)?,
+            Igloo => temple(Java1_9, 14357618)?,
+            Village if version < Java1_3 => return None,
+            Village if version < Java1_18 => region(32, 8, 10387312),
+            Village => region(34, 8, 10387312),
+            // Ocean monuments were added in 1.8, which uses the same biome
+            // generation as 1.7
+            OceanMonument if version < Java1_7 => return None,
+            OceanMonument => triangular(32, 5, 10387313),
+            WoodlandMansion if version < Java1_11 => return None,
+            WoodlandMansion => triangular(80, 20, 10387319),
+            EndCity if version < Java1_9 => return None,
+            EndCity => triangular(20, 11, 10387313),
+            OceanRuin if version < Java1_13 => return None,
+            OceanRuin if version < Java1_16_1 => region(16, 8, 14357621),
+            OceanRuin => region(20, 8, 14357621),
+            Shipwreck if version < Java1_13 => return None,
+            Shipwreck if version < Java1_16_1 => region(16, 8, 165745295),
+            Shipwreck => region(24, 4, 165745295),
+            PillagerOutpost if version < Java1_14 => return None,
+            PillagerOutpost => region(32, 8, 165745296),
+            NetherFort if version < Java1_3 => return None,
+            NetherFort if version < Java1_16_1 => Placement::NetherFortLegacy,
+            NetherFort => region(27, 4, 30084232),
+        };
+
+        Some(Self {
+            kind,
+            placement,
+            version,
+        })
+    }
+
+    /// Chunk where the structure will try to generate in the region that
+    /// contains chunk `c`, or None if there is no attempt in that region.
+    pub fn attempt_in_region_of(&self, seed: i64, c: &Chunk) -> Option<Chunk> {
+        let attempt = match self.placement {
+            Placement::Region {
+                spacing,
+                separation,
+                salt,
+                triangular,
+            } => {
+                let region_x = c.x.div_euclid(spacing);
+                let region_z = c.z.div_euclid(spacing);
+                let mut r = seeded_rng(seed, &Chunk::new(region_x, region_z), salt);
+                let n = spacing - separation;
+                let (offset_x, offset_z) = if triangular {
+                    let x = (r.next_int_n(n) + r.next_int_n(n)) / 2;
+                    let z = (r.next_int_n(n) + r.next_int_n(n)) / 2;
+                    (x, z)
+                } else {
+                    let x = r.next_int_n(n);
+                    let z = r.next_int_n(n);
+                    (x, z)
+                };
+
+                Chunk::new(region_x * spacing + offset_x, region_z * spacing + offset_z)
+            }
+            Placement::NetherFortLegacy => {
+                let region_x = c.x >> 4;
+                let region_z = c.z >> 4;
+                let mut r = region_16_rng(seed, region_x, region_z);
+                if r.next_int_n(3) != 0 {
+                    return None;
+                }
+                let x = (region_x << 4) + 4 + r.next_int_n(8);
+                let z = (region_z << 4) + 4 + r.next_int_n(8);
+
+                Chunk::new(x, z)
+            }
+        };
+
+        // Some structures have an additional check
+        match self.kind {
+            StructureKind::PillagerOutpost => {
+                let mut r = region_16_rng(seed, attempt.x >> 4, attempt.z >> 4);
+                if r.next_int_n(5) != 0 {
+                    return None;
+                }
+            }
+            // Nether fortresses and bastions are one structure set. If the
+            // bastion is selected but the biome does not allow it, the
+            // fortress is tried next, but the biomes are not checked here
+            StructureKind::NetherFort if self.version >= MinecraftVersion::Java1_18 => {
+                let index = weighted_structure_index(seed, &attempt, &NETHER_COMPLEXES_WEIGHTS);
+                if index != 0 {
+                    return None;
+                }
+            }
+            StructureKind::NetherFort if self.version >= MinecraftVersion::Java1_16_1 => {
+                // Nether fortresses and bastions share the same placement, and
+                // the large feature seed of the chunk decides which one it is
+                let mut r = large_feature_rng(seed, &attempt);
+                if r.next_int_n(5) >= 2 {
+                    return None;
+                }
+            }
+            _ => {}
+        }
+
+        Some(attempt)
+    }
+
+    /// Returns true if the structure will try to generate at this chunk
+    pub fn is_structure_chunk(&self, seed: i64, c: &Chunk) -> bool {
+        self.attempt_in_region_of(seed, c) == Some(*c)
+    }
+
+    /// Number of lower bits of the seed that can be checked independently of
+    /// the upper bits. `check_low_bits` can be used to quickly discard most of
+    /// the seeds.
+    ///
+    /// Since `next_int_n(n)` is implemented as `next(31) % n`, when n is even
+    /// but not a power of two the lower bits of the result only depend on the
+    /// lower bits of the seed.
+    pub fn num_low_bits(&self) -> u8 {
+        match self.placement {
+            Placement::Region {
+                spacing,
+                separation,
+                triangular: false,
+                ..
+            } => {
+                let n = spacing - separation;
+                let is_power_of_two = (n & -n) == n;
+                if is_power_of_two {
+                    0
+                } else {
+                    17 + n.trailing_zeros() as u8
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    /// Check the lower `num_low_bits` bits of the seed. May return false
+    /// positives, and in very rare cases false negatives.
+    pub fn check_low_bits(&self, seed: i64, c: &Chunk) -> bool {
+        match self.placement {
+            Placement::Region {
+                spacing,
+                separation,
+                salt,
+                triangular: false,
+            } => {
+                let n = spacing - separation;
+                let k = n.trailing_zeros();
+                if k == 0 || (n & -n) == n {
+                    return true;
+                }
+                let region_x = c.x.div_euclid(spacing);
+                let region_z = c.z.div_euclid(spacing);
+                let offset_x = c.x - region_x * spacing;
+                let offset_z = c.z - region_z * spacing;
+                let msk = (1 << k) - 1;
+                let mut r = seeded_rng(seed, &Chunk::new(region_x, region_z), salt);
+                // next(31) % n, ignoring the modulo bias loop
+                (r.next(31) & msk) == (offset_x & msk) && (r.next(31) & msk) == (offset_z & msk)
+            }
+            _ => true,
+        }
+    }
+
+    /// Probability that a random seed has a structure attempt at one chunk
+    pub fn probability(&self) -> f64 {
+        match self.placement {
+            // This is not exact for triangular placement, but it is good
+            // enough to estimate the number of candidates
+            Placement::Region {
+                spacing,
+                separation,
+                ..
+            } => 1.0 / f64::from((spacing - separation) * (spacing - separation)),
+            Placement::NetherFortLegacy => 1.0 / 3.0 / 64.0,
+        }
+    }
+}
+
+/// Java code: `rng.setSeed((long)(chunkX ^ chunkZ << 4) ^ seed); rng.nextInt();`
+fn region_16_rng(seed: i64, region_x: i32, region_z: i32) -> JavaRng {
+    let mut r = JavaRng::with_seed((i64::from(region_x ^ (region_z << 4)) ^ seed) as u64);
+    r.next_int();
+
+    r
+}
+
+/// Java code: `rng.setLargeFeatureSeed(seed, chunkX, chunkZ)`, the same seed
+/// used by the carvers
+fn large_feature_rng(seed: i64, c: &Chunk) -> JavaRng {
+    let mut r = JavaRng::with_seed(seed as u64);
+    let a = r.next_long();
+    let b = r.next_long();
+    let s = (i64::from(c.x).wrapping_mul(a)) ^ (i64::from(c.z).wrapping_mul(b)) ^ seed;
+
+    JavaRng::with_seed(s as u64)
+}
+
+/// Weights of the nether fortress and the bastion in the nether complexes
+/// structure set, since 1.18
+const NETHER_COMPLEXES_WEIGHTS: [i32; 2] = [2, 3];
+
+/// Index of the structure selected at chunk `c` from a structure set with
+/// these weights, since 1.18
+fn weighted_structure_index(seed: i64, c: &Chunk, weights: &[i32]) -> usize {
+    let mut r = large_feature_rng(seed, c);
+    let mut n = r.next_int_n(weights.iter().sum());
+    for (i, w) in weights.iter().enumerate() {
+        n -= w;
+        if n < 0 {
+            return i;
+        }
+    }
+
+    unreachable!()
+}
+
+/// Use all the region-based structures to find the lower 48 bits of the seed.
+pub struct StructureChunks {
+    structures: Vec<(StructurePlacement, Vec<Chunk>, Vec<Chunk>)>,
+    num_low_bits: u8,
+    low_candidates: Vec<u64>,
+}
+
+impl StructureChunks {
+    /// Structures that do not exist in this version are ignored.
+    pub fn new(
+        version: MinecraftVersion,
+        positive: &SeedStructures,
+        negative: &SeedStructures,
+    ) -> Self {
+        let structures: Vec<_> = StructureKind::all()
+            .iter()
+            .filter_map(|kind| {
+                let p = kind.chunks(positive);
+                let n = kind.chunks(negative);
+                if p.is_empty() && n.is_empty() {
+                    return None;
+                }
+                StructurePlacement::new(*kind, version).map(|sp| (sp, p.to_vec(), n.to_vec()))
+            })
+            .collect();
+
+        let num_low_bits = structures
+            .iter()
+            .filter(|(_sp, p, _n)| !p.is_empty())
+            .map(|(sp, _p, _n)| sp.num_low_bits())
+            .max()
+            .unwrap_or(0);
+
+        let low_candidates = (0..(1u64 << num_low_bits))
+            .filter(|&low| {
+                structures
+                    .iter()
+                    .all(|(sp, p, _n)| p.iter().all(|c| sp.check_low_bits(low as i64, c)))
+            })
+            .collect();
+
+        Self {
+            structures,
+            num_low_bits,
+            low_candidates,
+        }
+    }
+
+    /// Returns true if there are no structures that can be used in this
+    /// version
+    pub fn is_empty(&self) -> bool {
+        self.structures.is_empty()
+    }
+
+    /// Returns true if there is at least one positive structure, needed to find
+    /// the seed. Otherwise the structures can only be used as a filter.
+    pub fn has_positive(&self) -> bool {
+        self.structures.iter().any(|(_sp, p, _n)| !p.is_empty())
+    }
+
+    pub fn num_low_bits(&self) -> u8 {
+        self.num_low_bits
+    }
+
+    pub fn low_candidates(&self) -> &[u64] {
+        &self.low_candidates
+    }
+
+    /// Probability that a random 48-bit seed matches all the structures
+    pub fn probability(&self) -> f64 {
+        self.structures
+            .iter()
+            .map(|(sp, p, n)| {
+                let x = sp.probability();
+                x.powi(p.len() as i32) * (1.0 - x).powi(n.len() as i32)
+            })
+            .product()
+    }
+
+    pub fn try_seed(&self, seed: i64) -> bool {
+        self.structures.iter().all(|(sp, p, n)| {
+            p.iter().all(|c| sp.is_structure_chunk(seed, c))
+                && !n.iter().any(|c| sp.is_structure_chunk(seed, c))
+        })
+    }
+
+    pub fn find_seed(&self) -> Vec<i64> {
+        self.find_seed_range(0, 1 << (48 - self.num_low_bits))
+    }
+
+    /// Range units are multiples of 2^num_low_bits: the maximum value of hi
+    /// is 2^(48 - num_low_bits).
+    pub fn find_seed_range(&self, lo: u64, hi: u64) -> Vec<i64> {
+        let mut r = vec![];
+
+        for &low in &self.low_candidates {
+            for high in lo..hi {
+                let seed = ((high << self.num_low_bits) | low) as i64;
+                if self.try_seed(seed) {
+                    r.push(seed);
+                }
+            }
+        }
+
+        r
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,4 +728,124 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn structure_low_bits_match_full_check() {
+        let version = MinecraftVersion::Java1_16;
+        for kind in StructureKind::all() {
+            let sp = StructurePlacement::new(*kind, version).unwrap();
+            for seed in 0..200 {
+                let seed = seed * 0x5DEECE66D + 11;
+                let c = sp
+                    .attempt_in_region_of(seed, &Chunk::new(-100, 37))
+                    .unwrap_or(Chunk::new(-100, 37));
+                if sp.is_structure_chunk(seed, &c) {
+                    assert!(sp.check_low_bits(seed, &c), "{:?} {}", kind, seed);
+                    let low_mask = (1 << sp.num_low_bits()) - 1;
+                    assert!(
+                        sp.check_low_bits(seed & low_mask, &c),
+                        "{:?} {}",
+                        kind,
+                        seed
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn structure_versions() {
+        use MinecraftVersion::*;
+        assert_eq!(
+            StructurePlacement::new(StructureKind::PillagerOutpost, Java1_13),
+            None
+        );
+        assert_eq!(StructurePlacement::new(StructureKind::Igloo, Java1_7), None);
+        let fort = StructurePlacement::new(StructureKind::NetherFort, Java1_7).unwrap();
+        assert_eq!(fort.placement, Placement::NetherFortLegacy);
+        let village = StructurePlacement::new(StructureKind::Village, Java1_18).unwrap();
+        assert_eq!(village.num_low_bits(), 17 + 1);
+        let village = StructurePlacement::new(StructureKind::Village, Java1_17).unwrap();
+        assert_eq!(village.num_low_bits(), 17 + 3);
+    }
+
+    // Values computed using java.util.Random and the vanilla placement code
+    #[test]
+    fn nether_fort_positions() {
+        use MinecraftVersion::*;
+        let regions = |spacing: i32| {
+            (-2..=1)
+                .flat_map(move |rx| (-2..=1).map(move |rz| Chunk::new(rx * spacing, rz * spacing)))
+        };
+        let forts = |version, seed, spacing| -> Vec<(i32, i32)> {
+            let sp = StructurePlacement::new(StructureKind::NetherFort, version).unwrap();
+            regions(spacing)
+                .filter_map(|c| sp.attempt_in_region_of(seed, &c))
+                .map(|c| (c.x, c.z))
+                .collect()
+        };
+
+        assert_eq!(
+            forts(Java1_7, 0, 16),
+            vec![
+                (-27, -22),
+                (-21, -8),
+                (-22, 9),
+                (-24, 24),
+                (22, -8),
+                (25, 24)
+            ]
+        );
+        assert_eq!(
+            forts(Java1_7, -4172144997902289642, 16),
+            vec![(-11, 8), (-9, 25), (4, 9), (10, 23), (27, 21)]
+        );
+        // The bastions are at the other attempts:
+        // (-38, -16), (-27, -10), (-27, 11), (11, -14), (45, -15), (31, 38)
+        let seed_0 = vec![
+            (-32, -45),
+            (-39, 18),
+            (-45, 29),
+            (-12, -52),
+            (-20, 46),
+            (4, -36),
+            (15, 2),
+            (0, 44),
+            (33, -36),
+            (46, 9),
+        ];
+        // The bastions are at the other attempts:
+        // (-46, -15), (-43, 48), (-25, -43), (-13, 12), (20, -35), (3, -27), (14, 0), (9, 28),
+        // (39, -48), (36, 35)
+        let seed_2 = vec![
+            (-52, -46),
+            (-47, 12),
+            (-25, -5),
+            (-16, 32),
+            (36, -23),
+            (37, 16),
+        ];
+        for version in [Java1_16_1, Java1_17, Java1_18] {
+            assert_eq!(forts(version, 0, 27), seed_0);
+            assert_eq!(forts(version, -4172144997902289642, 27), seed_2);
+        }
+    }
+
+    #[test]
+    fn find_seed_using_villages() {
+        let version = MinecraftVersion::Java1_13;
+        let seed = 0x123456789ABC;
+        let mut positive = SeedStructures::default();
+        // Values computed using java.util.Random and the vanilla placement code
+        for (x, z) in [(20, 9), (39, 0), (20, -43), (-77, 83)] {
+            positive.villages.push(Chunk::new(x, z));
+        }
+        let sc = StructureChunks::new(version, &positive, &SeedStructures::default());
+        assert_eq!(sc.num_low_bits(), 20);
+        assert!(sc
+            .low_candidates()
+            .contains(&((seed as u64) & ((1 << 20) - 1))));
+        let high = (seed as u64) >> 20;
+        assert!(sc.find_seed_range(high, high + 1).contains(&seed));
+    }
 }
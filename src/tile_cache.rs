@@ -0,0 +1,510 @@
+//! Cache of generated biome maps, so rendering the same area again is instant.
+//!
+//! The maps are split into square tiles of `TILE_SIZE` elements, aligned to
+//! multiples of `TILE_SIZE`, and each tile is identified by the version, seed,
+//! layer and y offset used to generate it. The tiles are compressed and stored
+//! in memory or in a directory, and the least recently used tiles are removed
+//! when the total size exceeds the limit.
+//!
+//! The cache must be used explicitly, with `TileCache::generate_up_to_layer`.
+//! It is meant for rendering maps: generating a single point using the cache
+//! would generate the full tile.
+use crate::biome_layers;
+use crate::biome_layers::{Area, Map};
+use crate::seed_info::MinecraftVersion;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use log::*;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Width and height of the tiles
+pub const TILE_SIZE: u64 = 256;
+
+/// Extension of the tile files
+const TILE_EXTENSION: &str = "tile";
+
+/// Identifies one tile
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TileKey {
+    pub version: MinecraftVersion,
+    pub seed: i64,
+    pub layer: u32,
+    pub y_offset: u32,
+    /// Tile coordinates: the tile starts at `(tx * TILE_SIZE, tz * TILE_SIZE)`
+    pub tx: i64,
+    pub tz: i64,
+}
+
+impl TileKey {
+    pub fn area(&self) -> Area {
+        let t = TILE_SIZE as i64;
+        Area {
+            x: self.tx * t,
+            z: self.tz * t,
+            w: TILE_SIZE,
+            h: TILE_SIZE,
+        }
+    }
+
+    fn file_name(&self) -> String {
+        format!(
+            "{:?}_{}_{}_{}_{}_{}.{}",
+            self.version, self.seed, self.layer, self.y_offset, self.tx, self.tz, TILE_EXTENSION
+        )
+    }
+}
+
+struct Entry {
+    size: u64,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Index {
+    /// Tiles by file name
+    entries: HashMap<String, Entry>,
+    /// Compressed tiles, only used when the cache is in memory
+    memory: HashMap<String, Vec<u8>>,
+    total_size: u64,
+    /// Incremented every time a tile is used
+    clock: u64,
+}
+
+impl Index {
+    fn touch(&mut self, name: &str) -> bool {
+        self.clock += 1;
+        match self.entries.get_mut(name) {
+            Some(e) => {
+                e.last_used = self.clock;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Entry> {
+        let e = self.entries.remove(name)?;
+        self.memory.remove(name);
+        self.total_size -= e.size;
+
+        Some(e)
+    }
+
+    /// Name of the least recently used tile
+    fn oldest(&self) -> Option<String> {
+        self.entries
+            .iter()
+            .min_by_key(|(_, e)| e.last_used)
+            .map(|(name, _)| name.clone())
+    }
+}
+
+/// The index is shared by all the threads, but the files are read and written
+/// without holding its lock.
+pub struct TileCache {
+    dir: Option<PathBuf>,
+    max_size: u64,
+    index: Mutex<Index>,
+    /// Used to give a unique name to the temporary files
+    num_writes: AtomicU64,
+}
+
+impl TileCache {
+    /// Keep the tiles in memory, using at most `max_size` bytes
+    pub fn in_memory(max_size: u64) -> Self {
+        Self {
+            dir: None,
+            max_size,
+            index: Mutex::new(Index::default()),
+            num_writes: AtomicU64::new(0),
+        }
+    }
+
+    /// Store the tiles in `dir`, using at most `max_size` bytes. The tiles
+    /// already in the directory are reused, and the modification time of the
+    /// files is used to remember which ones were used recently.
+    pub fn on_disk(dir: PathBuf, max_size: u64) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let mut files = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(TILE_EXTENSION) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            let name = entry.file_name().to_string_lossy().into_owned();
+            files.push((modified, name, metadata.len()));
+        }
+        files.sort();
+
+        let mut index = Index::default();
+        // Oldest first
+        for (_, name, size) in files {
+            index.clock += 1;
+            index.total_size += size;
+            index.entries.insert(
+                name,
+                Entry {
+                    size,
+                    last_used: index.clock,
+                },
+            );
+        }
+        info!(
+            "Tile cache {}: {} tiles, {} bytes",
+            dir.display(),
+            index.entries.len(),
+            index.total_size
+        );
+
+        let cache = Self {
+            dir: Some(dir),
+            max_size,
+            index: Mutex::new(index),
+            num_writes: AtomicU64::new(0),
+        };
+        let evicted = cache.evict(&mut cache.index.lock().unwrap());
+        cache.remove_files(&evicted);
+
+        Ok(cache)
+    }
+
+    /// Number of tiles in the cache
+    pub fn len(&self) -> usize {
+        self.index.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total size of the compressed tiles, in bytes
+    pub fn size(&self) -> u64 {
+        self.index.lock().unwrap().total_size
+    }
+
+    pub fn get(&self, key: &TileKey) -> Option<Map> {
+        let name = key.file_name();
+        let compressed = match &self.dir {
+            None => {
+                let mut index = self.index.lock().unwrap();
+                if !index.touch(&name) {
+                    return None;
+                }
+                index.memory[&name].clone()
+            }
+            Some(dir) => {
+                if !self.index.lock().unwrap().touch(&name) {
+                    return None;
+                }
+                let path = dir.join(&name);
+                match fs::read(&path) {
+                    Ok(v) => {
+                        // Remember that this tile was used, ignoring errors
+                        let _ = fs::File::options()
+                            .write(true)
+                            .open(&path)
+                            .and_then(|f| f.set_modified(SystemTime::now()));
+                        v
+                    }
+                    Err(e) => {
+                        // The tile may have been evicted by another thread
+                        if e.kind() != io::ErrorKind::NotFound {
+                            warn!("Error reading tile {}: {}", path.display(), e);
+                        }
+                        self.index.lock().unwrap().remove(&name);
+                        return None;
+                    }
+                }
+            }
+        };
+
+        match decompress_tile(key.area(), &compressed) {
+            Ok(map) => Some(map),
+            Err(e) => {
+                warn!("Ignoring invalid tile {}: {}", name, e);
+                self.remove(&name);
+                None
+            }
+        }
+    }
+
+    /// Add a tile to the cache, removing old tiles if needed. Errors are
+    /// logged and ignored, because the tile can always be generated again.
+    pub fn insert(&self, key: &TileKey, map: &Map) {
+        assert_eq!(map.area(), key.area(), "Tile does not match its key");
+        let name = key.file_name();
+        let compressed = compress_tile(map);
+        let size = compressed.len() as u64;
+        let compressed = match &self.dir {
+            None => Some(compressed),
+            Some(dir) => {
+                // Write to a temporary file and rename it, so the tile is
+                // never left half-written
+                let path = dir.join(&name);
+                let n = self.num_writes.fetch_add(1, Ordering::Relaxed);
+                let tmp = path.with_extension(format!("{}.tmp", n));
+                if let Err(e) = fs::write(&tmp, &compressed).and_then(|()| fs::rename(&tmp, &path))
+                {
+                    warn!("Error writing tile {}: {}", path.display(), e);
+                    let _ = fs::remove_file(&tmp);
+                    return;
+                }
+                None
+            }
+        };
+        let evicted = {
+            let mut index = self.index.lock().unwrap();
+            index.remove(&name);
+            if let Some(compressed) = compressed {
+                index.memory.insert(name.clone(), compressed);
+            }
+            index.clock += 1;
+            let last_used = index.clock;
+            index.entries.insert(name, Entry { size, last_used });
+            index.total_size += size;
+            self.evict(&mut index)
+        };
+        self.remove_files(&evicted);
+    }
+
+    fn remove(&self, name: &str) {
+        let removed = self.index.lock().unwrap().remove(name).is_some();
+        if removed {
+            if let Some(dir) = &self.dir {
+                let _ = fs::remove_file(dir.join(name));
+            }
+        }
+    }
+
+    /// Remove the least recently used tiles from the index until the size is
+    /// below the limit. Returns the names of the removed tiles, the files must
+    /// be removed with `remove_files` after releasing the lock.
+    fn evict(&self, index: &mut Index) -> Vec<String> {
+        let mut evicted = vec![];
+        while index.total_size > self.max_size {
+            let name = match index.oldest() {
+                Some(name) => name,
+                None => break,
+            };
+            index.remove(&name);
+            evicted.push(name);
+        }
+
+        evicted
+    }
+
+    fn remove_files(&self, names: &[String]) {
+        if let Some(dir) = &self.dir {
+            for name in names {
+                if let Err(e) = fs::remove_file(dir.join(name)) {
+                    warn!("Error removing tile {}: {}", name, e);
+                }
+            }
+        }
+    }
+
+    /// Same as `biome_layers::generate_up_to_layer`, but using the cached
+    /// tiles. The missing tiles are generated and added to the cache.
+    pub fn generate_up_to_layer(
+        &self,
+        version: MinecraftVersion,
+        area: Area,
+        seed: i64,
+        num_layers: u32,
+        y_offset: u32,
+    ) -> Map {
+        let mut m = Map::new(area);
+        if area.w == 0 || area.h == 0 {
+            return m;
+        }
+        let t = TILE_SIZE as i64;
+        let (x_end, z_end) = (area.x + area.w as i64, area.z + area.h as i64);
+        for tz in area.z.div_euclid(t)..=(z_end - 1).div_euclid(t) {
+            for tx in area.x.div_euclid(t)..=(x_end - 1).div_euclid(t) {
+                let key = TileKey {
+                    version,
+                    seed,
+                    layer: num_layers,
+                    y_offset,
+                    tx,
+                    tz,
+                };
+                let tile = self.get(&key).unwrap_or_else(|| {
+                    let tile = biome_layers::generate_up_to_layer(
+                        version,
+                        key.area(),
+                        seed,
+                        num_layers,
+                        y_offset,
+                    );
+                    self.insert(&key, &tile);
+                    tile
+                });
+                // Copy the part of the tile that is inside the area
+                let ta = key.area();
+                for z in ta.z.max(area.z)..(ta.z + t).min(z_end) {
+                    for x in ta.x.max(area.x)..(ta.x + t).min(x_end) {
+                        m.a[((x - area.x) as usize, (z - area.z) as usize)] =
+                            tile.a[((x - ta.x) as usize, (z - ta.z) as usize)];
+                    }
+                }
+            }
+        }
+
+        m
+    }
+
+    /// Same as `biome_layers::generate_image_up_to_layer`, but using the
+    /// cached tiles
+    pub fn generate_image_up_to_layer(
+        &self,
+        version: MinecraftVersion,
+        area: Area,
+        seed: i64,
+        num_layers: u32,
+        y_offset: u32,
+    ) -> Vec<u8> {
+        let map = self.generate_up_to_layer(version, area, seed, num_layers, y_offset);
+
+        biome_layers::draw_map_image_up_to_layer(version, num_layers, &map)
+    }
+}
+
+fn compress_tile(map: &Map) -> Vec<u8> {
+    let area = map.area();
+    let mut e = ZlibEncoder::new(vec![], Compression::fast());
+    let mut buf = Vec::with_capacity((area.w * area.h * 4) as usize);
+    for x in 0..area.w as usize {
+        for z in 0..area.h as usize {
+            buf.extend_from_slice(&map.a[(x, z)].to_le_bytes());
+        }
+    }
+    e.write_all(&buf).unwrap();
+
+    e.finish().unwrap()
+}
+
+fn decompress_tile(area: Area, compressed: &[u8]) -> Result<Map, String> {
+    let mut buf = vec![];
+    ZlibDecoder::new(compressed)
+        .read_to_end(&mut buf)
+        .map_err(|e| e.to_string())?;
+    if buf.len() as u64 != area.w * area.h * 4 {
+        return Err(format!(
+            "expected {} bytes, got {}",
+            area.w * area.h * 4,
+            buf.len()
+        ));
+    }
+    let mut m = Map::new(area);
+    let mut values = buf
+        .chunks_exact(4)
+        .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    for x in 0..area.w as usize {
+        for z in 0..area.h as usize {
+            m.a[(x, z)] = values.next().unwrap();
+        }
+    }
+
+    Ok(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_map_is_the_same() {
+        let cache = TileCache::in_memory(1 << 20);
+        let version = MinecraftVersion::Java1_7;
+        // Not aligned to the tiles: uses 4 tiles
+        let area = Area {
+            x: -20,
+            z: 240,
+            w: 40,
+            h: 30,
+        };
+        let layer = 30;
+        let expected = biome_layers::generate_up_to_layer(version, area, 1234, layer, 0);
+        assert_eq!(
+            cache.generate_up_to_layer(version, area, 1234, layer, 0),
+            expected
+        );
+        assert_eq!(cache.len(), 4);
+        // Second time all the tiles are in the cache
+        assert_eq!(
+            cache.generate_up_to_layer(version, area, 1234, layer, 0),
+            expected
+        );
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let key = |tx| TileKey {
+            version: MinecraftVersion::Java1_7,
+            seed: 1,
+            layer: 1,
+            y_offset: 0,
+            tx,
+            tz: 0,
+        };
+        let tile = |tx| {
+            let mut m = Map::new(key(tx).area());
+            m.a[(0, 0)] = tx as i32;
+            m
+        };
+        let size = compress_tile(&tile(0)).len() as u64;
+        // Enough space for 2 tiles, but not 3
+        let cache = TileCache::in_memory(size * 5 / 2);
+        cache.insert(&key(0), &tile(0));
+        cache.insert(&key(1), &tile(1));
+        // Use tile 0, so tile 1 is the oldest
+        assert_eq!(cache.get(&key(0)), Some(tile(0)));
+        cache.insert(&key(2), &tile(2));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&key(1)), None);
+        assert_eq!(cache.get(&key(0)), Some(tile(0)));
+        assert_eq!(cache.get(&key(2)), Some(tile(2)));
+    }
+
+    #[test]
+    fn tiles_on_disk() {
+        let dir = std::env::temp_dir().join("slime_seed_finder_tile_cache_test");
+        let _ = fs::remove_dir_all(&dir);
+        let key = TileKey {
+            version: MinecraftVersion::Java1_18,
+            seed: -5,
+            layer: 3,
+            y_offset: 20,
+            tx: -1,
+            tz: 2,
+        };
+        let mut tile = Map::new(key.area());
+        tile.a[(3, 4)] = 7;
+        {
+            let cache = TileCache::on_disk(dir.clone(), 1 << 20).unwrap();
+            assert!(cache.is_empty());
+            cache.insert(&key, &tile);
+        }
+        // Open the cache again
+        let cache = TileCache::on_disk(dir.clone(), 1 << 20).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&key), Some(tile));
+        // A size limit of 0 removes all the tiles
+        let cache = TileCache::on_disk(dir.clone(), 0).unwrap();
+        assert!(cache.is_empty());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
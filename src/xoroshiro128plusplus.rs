@@ -49,6 +49,36 @@ impl Xoroshiro128PlusPlus {
     pub fn next_double(&mut self) -> f64 {
         ((self.next_long() >> (64 - 53)) as f64) * 1.1102230246251565E-16
     }
+
+    /// Same as `java.util.Random.next`, using the upper bits of `next_long`. Since 1.18, this is
+    /// what the WorldgenRandom used by the features and the decoration seed calls.
+    fn next_bits_j(&mut self, bits: u8) -> i32 {
+        (self.next_long() >> (64 - bits)) as i32
+    }
+
+    /// `nextLong` of the WorldgenRandom: the upper 32 bits of two calls to `next_long`
+    pub fn next_long_j(&mut self) -> i64 {
+        let a = self.next_bits_j(32);
+        let b = self.next_bits_j(32);
+
+        (i64::from(a) << 32).wrapping_add(i64::from(b))
+    }
+
+    /// `nextInt(n)` of the WorldgenRandom, same algorithm as `JavaRng::next_int_n`
+    pub fn next_int_n_j(&mut self, n: u32) -> i32 {
+        let n = n as i32;
+        if n & (n - 1) == 0 {
+            return ((i64::from(n) * i64::from(self.next_bits_j(31))) >> 31) as i32;
+        }
+
+        loop {
+            let bits = self.next_bits_j(31);
+            let val = bits % n;
+            if bits.wrapping_sub(val).wrapping_add(n - 1) >= 0 {
+                return val;
+            }
+        }
+    }
 }
 
 pub fn mix_stafford_13(mut i: u64) -> u64 {
@@ -234,6 +264,50 @@ mod tests {
         }
     }
 
+    mod xoroshiro_j {
+        use super::*;
+
+        // values computed using java.util.Random on the outputs of next_long from the tests above
+
+        #[test]
+        fn next_long_j() {
+            let mut xs = Xoroshiro128PlusPlus::new(1, 0);
+
+            assert_eq!(xs.next_long_j(), 139328);
+            assert_eq!(xs.next_long_j(), 579350284112897140);
+            assert_eq!(xs.next_long_j(), 543646121519753318);
+
+            let mut xs = Xoroshiro128PlusPlus::new(1, 1);
+
+            assert_eq!(xs.next_long_j(), 131072);
+            assert_eq!(xs.next_long_j(), 2814767023128596);
+            assert_eq!(xs.next_long_j(), 4900490409612447504);
+            assert_eq!(xs.next_long_j(), -6777270499932091194);
+        }
+
+        #[test]
+        fn next_int_j_power_of_2() {
+            let mut xs = Xoroshiro128PlusPlus::new(0, 1);
+
+            assert_eq!(xs.next_int_n_j(16), 0);
+            assert_eq!(xs.next_int_n_j(16), 0);
+            assert_eq!(xs.next_int_n_j(16), 0);
+            assert_eq!(xs.next_int_n_j(16), 8);
+            assert_eq!(xs.next_int_n_j(16), 4);
+        }
+
+        #[test]
+        fn next_int_j() {
+            let mut xs = Xoroshiro128PlusPlus::new(1, 0);
+
+            assert_eq!(xs.next_int_n_j(1000), 0);
+            assert_eq!(xs.next_int_n_j(1000), 664);
+            assert_eq!(xs.next_int_n_j(1000), 250);
+            assert_eq!(xs.next_int_n_j(1000), 218);
+            assert_eq!(xs.next_int_n_j(1000), 738);
+        }
+    }
+
     mod mix_stafford {
         use super::*;
 